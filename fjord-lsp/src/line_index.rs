@@ -0,0 +1,90 @@
+use lsp_types::Position;
+use text_size::TextSize;
+
+/// Converts between the byte offsets used by Fjord and the line and column positions used by
+/// LSP, which count columns in UTF-16 code units.
+pub(crate) struct LineIndex<'a> {
+    text: &'a str,
+    line_starts: Vec<usize>,
+}
+
+impl<'a> LineIndex<'a> {
+    pub(crate) fn new(text: &'a str) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(text.match_indices('\n').map(|(idx, _)| idx + 1))
+            .collect();
+
+        Self { text, line_starts }
+    }
+
+    pub(crate) fn position(&self, offset: TextSize) -> Position {
+        let offset = usize::from(offset);
+        let line = self.line_starts.partition_point(|start| *start <= offset) - 1;
+        let line_start = self.line_starts[line];
+
+        Position {
+            line: line as u32,
+            character: utf16_len(&self.text[line_start..offset]),
+        }
+    }
+
+    /// Positions past the end of a line are treated as the end of that line, and positions past
+    /// the last line as the end of the text.
+    pub(crate) fn offset(&self, position: Position) -> TextSize {
+        let line_start = match self.line_starts.get(position.line as usize) {
+            Some(line_start) => *line_start,
+            None => return TextSize::of(self.text),
+        };
+
+        let line_end = self
+            .line_starts
+            .get(position.line as usize + 1)
+            .map_or(self.text.len(), |next_line_start| next_line_start - 1);
+
+        let mut offset = line_start;
+        let mut character = 0;
+
+        for c in self.text[line_start..line_end].chars() {
+            if character >= position.character {
+                break;
+            }
+
+            character += c.len_utf16() as u32;
+            offset += c.len_utf8();
+        }
+
+        TextSize::from(offset as u32)
+    }
+}
+
+fn utf16_len(s: &str) -> u32 {
+    s.chars().map(|c| c.len_utf16() as u32).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn convert_offsets_to_positions_and_back() {
+        let text = "let a = 5\necho «é» $a\n";
+        let index = LineIndex::new(text);
+
+        for (offset, line, character) in [(0, 0, 0), (9, 0, 9), (10, 1, 0), (23, 1, 10), (25, 2, 0)]
+        {
+            let offset = TextSize::from(offset);
+            let position = Position { line, character };
+
+            assert_eq!(index.position(offset), position);
+            assert_eq!(index.offset(position), offset);
+        }
+    }
+
+    #[test]
+    fn clamp_positions_past_end_of_line() {
+        let index = LineIndex::new("ab\ncd");
+
+        assert_eq!(index.offset(Position::new(0, 10)), TextSize::from(2));
+        assert_eq!(index.offset(Position::new(5, 0)), TextSize::from(5));
+    }
+}
@@ -0,0 +1,323 @@
+//! A language server for Fjord, which speaks LSP over stdin and stdout.
+
+mod line_index;
+
+use fjord::env::Env;
+use fjord::ide::CompletionKind;
+use fjord::parser::{ContainsErrors, ParseOutput, Parser, TextEdit};
+use line_index::LineIndex;
+use lsp_server::{Connection, Message, Notification, Request, RequestId, Response};
+use lsp_types::notification::{
+    DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument, Notification as _,
+    PublishDiagnostics,
+};
+use lsp_types::request::{Completion, GotoDefinition, HoverRequest, Request as _};
+use lsp_types::{
+    CompletionItem, CompletionItemKind, CompletionOptions, CompletionResponse, Diagnostic,
+    DiagnosticSeverity, GotoDefinitionResponse, Hover, HoverContents, HoverProviderCapability,
+    InitializeParams, Location, MarkupContent, MarkupKind, OneOf, PublishDiagnosticsParams, Range,
+    ServerCapabilities, TextDocumentContentChangeEvent, TextDocumentPositionParams,
+    TextDocumentSyncCapability, TextDocumentSyncKind, Url,
+};
+use std::collections::HashMap;
+use std::error::Error;
+use text_size::{TextRange, TextSize};
+
+type Result<T> = std::result::Result<T, Box<dyn Error + Send + Sync>>;
+
+fn main() -> Result<()> {
+    let (connection, io_threads) = Connection::stdio();
+
+    let capabilities = ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(
+            TextDocumentSyncKind::INCREMENTAL,
+        )),
+        definition_provider: Some(OneOf::Left(true)),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        completion_provider: Some(CompletionOptions {
+            trigger_characters: Some(vec!["$".to_string()]),
+            ..CompletionOptions::default()
+        }),
+        ..ServerCapabilities::default()
+    };
+
+    let initialize_params = connection.initialize(serde_json::to_value(capabilities)?)?;
+    let _: InitializeParams = serde_json::from_value(initialize_params)?;
+
+    Server::new(connection).run()?;
+    io_threads.join()?;
+
+    Ok(())
+}
+
+struct Document {
+    text: String,
+    parse_output: ParseOutput<ContainsErrors>,
+}
+
+impl Document {
+    fn new(text: String) -> Self {
+        let parse_output = Parser::new(&text).parse();
+        Self { text, parse_output }
+    }
+
+    // Edits are applied by reparsing only what they affect, since editors send one after every
+    // keystroke.
+    fn apply_change(&mut self, change: TextDocumentContentChangeEvent) {
+        let range = match change.range {
+            Some(range) => self.text_range(range),
+            None => {
+                *self = Self::new(change.text);
+                return;
+            }
+        };
+
+        let edit = TextEdit::new(range, change.text);
+        self.parse_output = self.parse_output.reparse(&edit);
+        self.text = edit.apply(&self.text);
+    }
+
+    fn offset(&self, position: lsp_types::Position) -> TextSize {
+        LineIndex::new(&self.text).offset(position)
+    }
+
+    fn text_range(&self, range: Range) -> TextRange {
+        let line_index = LineIndex::new(&self.text);
+        TextRange::new(line_index.offset(range.start), line_index.offset(range.end))
+    }
+
+    fn range(&self, range: TextRange) -> Range {
+        let line_index = LineIndex::new(&self.text);
+        Range::new(
+            line_index.position(range.start()),
+            line_index.position(range.end()),
+        )
+    }
+
+    fn diagnostics(&self) -> Vec<Diagnostic> {
+        self.parse_output
+            .errors()
+            .iter()
+            .map(|error| Diagnostic {
+                range: self.range(error.range()),
+                severity: Some(DiagnosticSeverity::ERROR),
+                source: Some("fjord".to_string()),
+                message: error.message().to_string(),
+                ..Diagnostic::default()
+            })
+            .collect()
+    }
+}
+
+struct Server {
+    connection: Connection,
+    documents: HashMap<Url, Document>,
+    // Only used to find the commands in PATH for completions.
+    env: Env<'static>,
+}
+
+impl Server {
+    fn new(connection: Connection) -> Self {
+        let search_path = std::env::var_os("PATH")
+            .map(|path| std::env::split_paths(&path).collect())
+            .unwrap_or_default();
+
+        Self {
+            connection,
+            documents: HashMap::new(),
+            env: Env::new(search_path),
+        }
+    }
+
+    fn run(mut self) -> Result<()> {
+        while let Ok(message) = self.connection.receiver.recv() {
+            match message {
+                Message::Request(request) => {
+                    if self.connection.handle_shutdown(&request)? {
+                        return Ok(());
+                    }
+
+                    self.handle_request(request)?;
+                }
+                Message::Notification(notification) => self.handle_notification(notification)?,
+                Message::Response(_) => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    fn handle_request(&mut self, request: Request) -> Result<()> {
+        let response = match request.method.as_str() {
+            GotoDefinition::METHOD => match extract::<GotoDefinition>(request) {
+                Ok((id, params)) => self.goto_definition(id, params.text_document_position_params),
+                Err(response) => response,
+            },
+            HoverRequest::METHOD => match extract::<HoverRequest>(request) {
+                Ok((id, params)) => self.hover(id, params.text_document_position_params),
+                Err(response) => response,
+            },
+            Completion::METHOD => match extract::<Completion>(request) {
+                Ok((id, params)) => self.completion(id, params.text_document_position),
+                Err(response) => response,
+            },
+            _ => Response::new_err(
+                request.id,
+                lsp_server::ErrorCode::MethodNotFound as i32,
+                format!("unsupported request: {}", request.method),
+            ),
+        };
+
+        self.connection.sender.send(Message::Response(response))?;
+
+        Ok(())
+    }
+
+    fn goto_definition(&self, id: RequestId, params: TextDocumentPositionParams) -> Response {
+        let uri = params.text_document.uri;
+        let position = params.position;
+
+        let location = self.documents.get(&uri).and_then(|document| {
+            let offset = document.offset(position);
+            let range = document.parse_output.goto_definition(offset)?;
+
+            Some(GotoDefinitionResponse::Scalar(Location {
+                uri: uri.clone(),
+                range: document.range(range),
+            }))
+        });
+
+        Response::new_ok(id, location)
+    }
+
+    fn hover(&self, id: RequestId, params: TextDocumentPositionParams) -> Response {
+        let hover = self
+            .documents
+            .get(&params.text_document.uri)
+            .and_then(|document| {
+                let offset = document.offset(params.position);
+                let hover = document.parse_output.hover(offset)?;
+
+                Some(Hover {
+                    contents: HoverContents::Markup(MarkupContent {
+                        kind: MarkupKind::PlainText,
+                        value: hover.text().to_string(),
+                    }),
+                    range: Some(document.range(hover.range())),
+                })
+            });
+
+        Response::new_ok(id, hover)
+    }
+
+    fn completion(&self, id: RequestId, params: TextDocumentPositionParams) -> Response {
+        let items = match self.documents.get(&params.text_document.uri) {
+            Some(document) => {
+                let offset = document.offset(params.position);
+
+                document
+                    .parse_output
+                    .completions(offset, &self.env)
+                    .into_iter()
+                    .map(|completion| CompletionItem {
+                        label: completion.label().to_string(),
+                        kind: Some(match completion.kind() {
+                            CompletionKind::Binding => CompletionItemKind::VARIABLE,
+                            CompletionKind::Builtin | CompletionKind::Command => {
+                                CompletionItemKind::FUNCTION
+                            }
+                            CompletionKind::Path if completion.label().ends_with('/') => {
+                                CompletionItemKind::FOLDER
+                            }
+                            CompletionKind::Path => CompletionItemKind::FILE,
+                        }),
+                        ..CompletionItem::default()
+                    })
+                    .collect()
+            }
+            None => Vec::new(),
+        };
+
+        Response::new_ok(id, CompletionResponse::Array(items))
+    }
+
+    fn handle_notification(&mut self, notification: Notification) -> Result<()> {
+        match notification.method.as_str() {
+            DidOpenTextDocument::METHOD => {
+                let params = notification
+                    .extract::<lsp_types::DidOpenTextDocumentParams>(DidOpenTextDocument::METHOD)?;
+                let uri = params.text_document.uri;
+
+                self.documents
+                    .insert(uri.clone(), Document::new(params.text_document.text));
+                self.publish_diagnostics(uri)?;
+            }
+            DidChangeTextDocument::METHOD => {
+                let params = notification.extract::<lsp_types::DidChangeTextDocumentParams>(
+                    DidChangeTextDocument::METHOD,
+                )?;
+                let uri = params.text_document.uri;
+
+                if let Some(document) = self.documents.get_mut(&uri) {
+                    for change in params.content_changes {
+                        document.apply_change(change);
+                    }
+
+                    self.publish_diagnostics(uri)?;
+                }
+            }
+            DidCloseTextDocument::METHOD => {
+                let params = notification.extract::<lsp_types::DidCloseTextDocumentParams>(
+                    DidCloseTextDocument::METHOD,
+                )?;
+                let uri = params.text_document.uri;
+
+                self.documents.remove(&uri);
+                self.publish_diagnostics(uri)?;
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    // Closed documents have their diagnostics cleared.
+    fn publish_diagnostics(&self, uri: Url) -> Result<()> {
+        let diagnostics = self
+            .documents
+            .get(&uri)
+            .map(Document::diagnostics)
+            .unwrap_or_default();
+
+        let params = PublishDiagnosticsParams {
+            uri,
+            diagnostics,
+            version: None,
+        };
+
+        self.connection
+            .sender
+            .send(Message::Notification(Notification::new(
+                PublishDiagnostics::METHOD.to_string(),
+                params,
+            )))?;
+
+        Ok(())
+    }
+}
+
+// A request with parameters that can’t be deserialised is answered with an error, rather than
+// shutting the server down.
+fn extract<R: lsp_types::request::Request>(
+    request: Request,
+) -> std::result::Result<(RequestId, R::Params), Response> {
+    let id = request.id.clone();
+
+    request.extract(R::METHOD).map_err(|error| {
+        Response::new_err(
+            id,
+            lsp_server::ErrorCode::InvalidParams as i32,
+            error.to_string(),
+        )
+    })
+}
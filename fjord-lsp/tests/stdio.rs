@@ -0,0 +1,325 @@
+//! Drives the language server over stdin and stdout, just like an editor would.
+
+use serde_json::{json, Value};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+
+// The language server’s tests can’t see the library’s test-only modules, so the fixtures are
+// included directly.
+#[allow(dead_code)]
+#[path = "../../src/test_support.rs"]
+mod test_support;
+
+use test_support::TempDir;
+
+const URI: &str = "file:///test.fj";
+
+struct Server {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+    next_id: u64,
+}
+
+impl Server {
+    fn start() -> Self {
+        Self::start_with_path("")
+    }
+
+    fn start_with_path(path: impl AsRef<std::ffi::OsStr>) -> Self {
+        let mut server = Self::spawn(path);
+        server.initialize();
+
+        server
+    }
+
+    fn spawn(path: impl AsRef<std::ffi::OsStr>) -> Self {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_fjord-lsp"))
+            .env("PATH", path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+
+        let stdin = child.stdin.take().unwrap();
+        let stdout = BufReader::new(child.stdout.take().unwrap());
+
+        Self {
+            child,
+            stdin,
+            stdout,
+            next_id: 0,
+        }
+    }
+
+    // Returns the capabilities the server advertises.
+    fn initialize(&mut self) -> Value {
+        let result = self.request("initialize", json!({ "capabilities": {} }));
+        self.notify("initialized", json!({}));
+
+        result["capabilities"].clone()
+    }
+
+    fn send(&mut self, message: Value) {
+        let content = message.to_string();
+        write!(
+            self.stdin,
+            "Content-Length: {}\r\n\r\n{}",
+            content.len(),
+            content
+        )
+        .unwrap();
+        self.stdin.flush().unwrap();
+    }
+
+    fn receive(&mut self) -> Value {
+        let mut content_length = None;
+
+        loop {
+            let mut header = String::new();
+            self.stdout.read_line(&mut header).unwrap();
+            let header = header.trim_end();
+
+            if header.is_empty() {
+                break;
+            }
+
+            if let Some(length) = header.strip_prefix("Content-Length: ") {
+                content_length = Some(length.parse().unwrap());
+            }
+        }
+
+        let mut content = vec![0; content_length.unwrap()];
+        self.stdout.read_exact(&mut content).unwrap();
+
+        serde_json::from_slice(&content).unwrap()
+    }
+
+    fn request(&mut self, method: &str, params: Value) -> Value {
+        let response = self.respond(method, params);
+        assert!(response.get("error").is_none(), "{}", response);
+
+        response["result"].clone()
+    }
+
+    // Returns the whole response, which holds either a result or an error. Any notifications
+    // received while waiting for it are skipped.
+    fn respond(&mut self, method: &str, params: Value) -> Value {
+        self.next_id += 1;
+        let id = self.next_id;
+
+        self.send(json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }));
+
+        loop {
+            let message = self.receive();
+
+            if message["id"] == id {
+                return message;
+            }
+        }
+    }
+
+    fn notify(&mut self, method: &str, params: Value) {
+        self.send(json!({ "jsonrpc": "2.0", "method": method, "params": params }));
+    }
+
+    fn receive_notification(&mut self, method: &str) -> Value {
+        loop {
+            let message = self.receive();
+
+            if message["method"] == method {
+                return message["params"].clone();
+            }
+        }
+    }
+
+    fn open(&mut self, text: &str) -> Value {
+        self.notify(
+            "textDocument/didOpen",
+            json!({
+                "textDocument": { "uri": URI, "languageId": "fjord", "version": 1, "text": text },
+            }),
+        );
+
+        self.receive_notification("textDocument/publishDiagnostics")
+    }
+
+    fn request_at(&mut self, method: &str, line: u32, character: u32) -> Value {
+        self.request(
+            method,
+            json!({
+                "textDocument": { "uri": URI },
+                "position": { "line": line, "character": character },
+            }),
+        )
+    }
+
+    fn shut_down(mut self) {
+        self.request("shutdown", Value::Null);
+        self.notify("exit", Value::Null);
+
+        assert!(self.child.wait().unwrap().success());
+    }
+}
+
+#[test]
+fn advertise_capabilities() {
+    let mut server = Server::spawn("");
+    let capabilities = server.initialize();
+
+    assert_eq!(capabilities["textDocumentSync"], 2);
+    assert_eq!(capabilities["definitionProvider"], true);
+    assert_eq!(capabilities["hoverProvider"], true);
+    assert_eq!(
+        capabilities["completionProvider"]["triggerCharacters"],
+        json!(["$"])
+    );
+
+    server.shut_down();
+}
+
+#[test]
+fn publish_syntax_errors_as_diagnostics() {
+    let mut server = Server::start();
+
+    let diagnostics = server.open("let a = 5\nlet = 10");
+
+    assert_eq!(diagnostics["uri"], URI);
+    assert_eq!(
+        diagnostics["diagnostics"][0]["range"],
+        json!({ "start": { "line": 1, "character": 4 }, "end": { "line": 1, "character": 5 } }),
+    );
+    assert_eq!(
+        diagnostics["diagnostics"][0]["message"],
+        "expected binding name",
+    );
+
+    server.shut_down();
+}
+
+#[test]
+fn update_diagnostics_after_incremental_change() {
+    let mut server = Server::start();
+
+    let diagnostics = server.open("let a = 5\nlet = 10");
+    assert_ne!(diagnostics["diagnostics"], json!([]));
+
+    server.notify(
+        "textDocument/didChange",
+        json!({
+            "textDocument": { "uri": URI, "version": 2 },
+            "contentChanges": [{
+                "range": {
+                    "start": { "line": 1, "character": 4 },
+                    "end": { "line": 1, "character": 4 },
+                },
+                "text": "b ",
+            }],
+        }),
+    );
+
+    let diagnostics = server.receive_notification("textDocument/publishDiagnostics");
+    assert_eq!(diagnostics["diagnostics"], json!([]));
+
+    // The change was applied to the server’s copy of the document, so requests see it.
+    let hover = server.request_at("textDocument/hover", 1, 4);
+    assert_eq!(hover["contents"]["value"], "let b: Number");
+
+    server.shut_down();
+}
+
+#[test]
+fn goto_definition() {
+    let mut server = Server::start();
+    server.open("let f = |x| $x\nlet a = 5\necho $a");
+
+    let definition = server.request_at("textDocument/definition", 2, 6);
+    assert_eq!(
+        definition,
+        json!({
+            "uri": URI,
+            "range": {
+                "start": { "line": 1, "character": 4 },
+                "end": { "line": 1, "character": 5 },
+            },
+        }),
+    );
+
+    let definition = server.request_at("textDocument/definition", 0, 13);
+    assert_eq!(
+        definition["range"]["start"],
+        json!({ "line": 0, "character": 9 }),
+    );
+
+    assert_eq!(
+        server.request_at("textDocument/definition", 2, 1),
+        Value::Null
+    );
+
+    server.shut_down();
+}
+
+#[test]
+fn hover() {
+    let mut server = Server::start();
+    server.open("let greeting = \"hi\"\necho $greeting");
+
+    let hover = server.request_at("textDocument/hover", 1, 8);
+    assert_eq!(hover["contents"]["value"], "let greeting: Str");
+    assert_eq!(
+        hover["range"],
+        json!({ "start": { "line": 1, "character": 6 }, "end": { "line": 1, "character": 14 } }),
+    );
+
+    server.shut_down();
+}
+
+#[test]
+fn reply_to_malformed_params_with_error() {
+    let mut server = Server::start();
+    server.open("let x = 1\n$x");
+
+    let response = server.respond("textDocument/hover", json!({ "position": "start" }));
+
+    // Invalid parameters have an error code of -32602.
+    assert_eq!(response["error"]["code"], -32602);
+    assert!(server.request_at("textDocument/hover", 1, 1).is_object());
+
+    server.shut_down();
+}
+
+#[cfg(unix)]
+#[test]
+fn complete_bindings_and_commands() {
+    let dir = TempDir::new("lsp-completion");
+    dir.create_executable("lsd");
+
+    let mut server = Server::start_with_path(&*dir);
+    server.open("let lst = [1 2]\nls\necho $l");
+
+    let labels = |completions: Value| -> Vec<(String, u64)> {
+        completions
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|item| {
+                (
+                    item["label"].as_str().unwrap().to_string(),
+                    item["kind"].as_u64().unwrap(),
+                )
+            })
+            .collect()
+    };
+
+    // Variables have a completion item kind of 6 and functions of 3.
+    assert_eq!(
+        labels(server.request_at("textDocument/completion", 1, 2)),
+        [("lst".to_string(), 6), ("lsd".to_string(), 3)],
+    );
+    assert_eq!(
+        labels(server.request_at("textDocument/completion", 2, 7)),
+        [("lst".to_string(), 6)],
+    );
+
+    server.shut_down();
+}
@@ -23,6 +23,16 @@ macro_rules! ast_node {
             pub(crate) fn text_range(&self) -> TextRange {
                 self.0.text_range()
             }
+
+            #[allow(unused)]
+            pub(crate) fn text(&self) -> String {
+                self.0.text().to_string()
+            }
+
+            #[allow(unused)]
+            pub(crate) fn syntax(&self) -> &SyntaxNode {
+                &self.0
+            }
         }
     };
 }
@@ -39,12 +49,20 @@ pub(crate) struct Item(SyntaxElement);
 
 pub(crate) enum ItemKind {
     BindingDef(BindingDef),
+    Assignment(Assignment),
+    Import(Import),
+    Alias(Alias),
     Expr(Expr),
 }
 
 impl Item {
     fn cast(element: SyntaxElement) -> Option<Self> {
-        if element.clone().into_node().map(BindingDef::cast).is_some()
+        let node = element.clone().into_node();
+
+        if node.clone().and_then(BindingDef::cast).is_some()
+            || node.clone().and_then(Assignment::cast).is_some()
+            || node.clone().and_then(Import::cast).is_some()
+            || node.and_then(Alias::cast).is_some()
             || Expr::cast(element.clone()).is_some()
         {
             Some(Self(element))
@@ -59,35 +77,130 @@ impl Item {
             .into_node()
             .and_then(BindingDef::cast)
             .map(ItemKind::BindingDef)
+            .or_else(|| {
+                self.0
+                    .clone()
+                    .into_node()
+                    .and_then(Assignment::cast)
+                    .map(ItemKind::Assignment)
+            })
+            .or_else(|| {
+                self.0
+                    .clone()
+                    .into_node()
+                    .and_then(Import::cast)
+                    .map(ItemKind::Import)
+            })
+            .or_else(|| {
+                self.0
+                    .clone()
+                    .into_node()
+                    .and_then(Alias::cast)
+                    .map(ItemKind::Alias)
+            })
             .or_else(|| Expr::cast(self.0.clone()).map(ItemKind::Expr))
             .unwrap()
     }
+
+    pub(crate) fn text_range(&self) -> TextRange {
+        self.0.text_range()
+    }
+
+    /// Returns whether the parser had to wrap any tokens in this item up as errors.
+    pub(crate) fn contains_error_tokens(&self) -> bool {
+        match &self.0 {
+            SyntaxElement::Node(node) => node
+                .descendants_with_tokens()
+                .any(|element| element.kind() == SyntaxKind::Error),
+            SyntaxElement::Token(token) => token.kind() == SyntaxKind::Error,
+        }
+    }
 }
 
 ast_node!(BindingDef, SyntaxKind::BindingDef);
 
 impl BindingDef {
     pub(crate) fn binding_name(&self) -> Option<SmolStr> {
+        self.binding_name_atom().map(|atom| atom.text().clone())
+    }
+
+    pub(crate) fn binding_name_atom(&self) -> Option<Atom> {
         self.0
             .children_with_tokens()
             .filter_map(NodeOrToken::into_token)
-            .find(|token| token.kind() == SyntaxKind::Atom)
-            .map(|token| token.text().clone())
+            .find_map(Atom::cast)
+    }
+
+    pub(crate) fn is_mutable(&self) -> bool {
+        self.0
+            .children_with_tokens()
+            .any(|element| element.kind() == SyntaxKind::MutKw)
     }
 
     pub(crate) fn expr(&self) -> Option<Expr> {
-        let mut children = self.0.children_with_tokens();
+        expr_after_equals(&self.0)
+    }
+}
 
-        loop {
-            let element = children.next()?;
+fn expr_after_equals(node: &SyntaxNode) -> Option<Expr> {
+    let mut children = node.children_with_tokens();
 
-            if element.into_token().and_then(Equals::cast).is_some() {
-                return children.find_map(Expr::cast);
-            }
+    loop {
+        let element = children.next()?;
+
+        if element.into_token().and_then(Equals::cast).is_some() {
+            return children.find_map(Expr::cast);
         }
     }
 }
 
+ast_node!(Assignment, SyntaxKind::Assignment);
+
+impl Assignment {
+    pub(crate) fn binding_name(&self) -> Option<Atom> {
+        self.0.first_token().and_then(Atom::cast)
+    }
+
+    pub(crate) fn expr(&self) -> Option<Expr> {
+        expr_after_equals(&self.0)
+    }
+}
+
+ast_node!(Import, SyntaxKind::Import);
+
+impl Import {
+    pub(crate) fn path(&self) -> Option<StringLiteral> {
+        self.0
+            .children_with_tokens()
+            .filter_map(NodeOrToken::into_token)
+            .find_map(StringLiteral::cast)
+    }
+
+    /// Returns whether this is a `source` rather than an `import`, meaning that the module’s
+    /// bindings are merged into the current environment rather than being namespaced.
+    pub(crate) fn is_source(&self) -> bool {
+        self.0
+            .children_with_tokens()
+            .any(|element| element.kind() == SyntaxKind::SourceKw)
+    }
+}
+
+ast_node!(Alias, SyntaxKind::Alias);
+
+impl Alias {
+    pub(crate) fn name(&self) -> Option<Atom> {
+        self.0
+            .children_with_tokens()
+            .filter_map(NodeOrToken::into_token)
+            .find_map(Atom::cast)
+    }
+
+    /// Returns the call the alias expands to, which the parser ensures is a function call.
+    pub(crate) fn call(&self) -> Option<Expr> {
+        expr_after_equals(&self.0)
+    }
+}
+
 pub(crate) struct Expr(SyntaxElement);
 
 pub(crate) enum ExprKind {
@@ -97,15 +210,24 @@ pub(crate) enum ExprKind {
     Lambda(Lambda),
     BindingUsage(BindingUsage),
     Block(Block),
+    Glob(Glob),
+    Background(Background),
+    List(List),
+    Record(Record),
+    Match(Match),
+    For(For),
+    While(While),
+    Break,
+    Continue,
     Atom(Atom),
     NumberLiteral(Digits),
     StringLiteral(StringLiteral),
-    True(True),
-    False(False),
+    True,
+    False,
 }
 
 impl Expr {
-    fn cast(element: SyntaxElement) -> Option<Self> {
+    pub(crate) fn cast(element: SyntaxElement) -> Option<Self> {
         let is_expr = match element {
             SyntaxElement::Node(ref node) => {
                 BinOp::cast(node.clone()).is_some()
@@ -114,6 +236,13 @@ impl Expr {
                     || Lambda::cast(node.clone()).is_some()
                     || BindingUsage::cast(node.clone()).is_some()
                     || Block::cast(node.clone()).is_some()
+                    || Glob::cast(node.clone()).is_some()
+                    || Background::cast(node.clone()).is_some()
+                    || List::cast(node.clone()).is_some()
+                    || Record::cast(node.clone()).is_some()
+                    || Match::cast(node.clone()).is_some()
+                    || For::cast(node.clone()).is_some()
+                    || While::cast(node.clone()).is_some()
             }
             SyntaxElement::Token(ref token) => {
                 token.kind() == SyntaxKind::Atom
@@ -121,6 +250,8 @@ impl Expr {
                     || token.kind() == SyntaxKind::StringLiteral
                     || token.kind() == SyntaxKind::True
                     || token.kind() == SyntaxKind::False
+                    || token.kind() == SyntaxKind::BreakKw
+                    || token.kind() == SyntaxKind::ContinueKw
             }
         };
 
@@ -140,13 +271,22 @@ impl Expr {
                 .or_else(|| Lambda::cast(node.clone()).map(ExprKind::Lambda))
                 .or_else(|| BindingUsage::cast(node.clone()).map(ExprKind::BindingUsage))
                 .or_else(|| Block::cast(node.clone()).map(ExprKind::Block))
+                .or_else(|| Glob::cast(node.clone()).map(ExprKind::Glob))
+                .or_else(|| Background::cast(node.clone()).map(ExprKind::Background))
+                .or_else(|| List::cast(node.clone()).map(ExprKind::List))
+                .or_else(|| Record::cast(node.clone()).map(ExprKind::Record))
+                .or_else(|| Match::cast(node.clone()).map(ExprKind::Match))
+                .or_else(|| For::cast(node.clone()).map(ExprKind::For))
+                .or_else(|| While::cast(node.clone()).map(ExprKind::While))
                 .unwrap(),
             SyntaxElement::Token(token) => Atom::cast(token.clone())
                 .map(ExprKind::Atom)
                 .or_else(|| Digits::cast(token.clone()).map(ExprKind::NumberLiteral))
                 .or_else(|| StringLiteral::cast(token.clone()).map(ExprKind::StringLiteral))
-                .or_else(|| True::cast(token.clone()).map(ExprKind::True))
-                .or_else(|| False::cast(token.clone()).map(ExprKind::False))
+                .or_else(|| True::cast(token.clone()).map(|_| ExprKind::True))
+                .or_else(|| False::cast(token.clone()).map(|_| ExprKind::False))
+                .or_else(|| BreakKw::cast(token.clone()).map(|_| ExprKind::Break))
+                .or_else(|| ContinueKw::cast(token.clone()).map(|_| ExprKind::Continue))
                 .unwrap(),
         }
     }
@@ -154,6 +294,13 @@ impl Expr {
     pub(crate) fn text_range(&self) -> TextRange {
         self.0.text_range()
     }
+
+    pub(crate) fn text(&self) -> String {
+        match &self.0 {
+            SyntaxElement::Node(node) => node.text().to_string(),
+            SyntaxElement::Token(token) => token.text().to_string(),
+        }
+    }
 }
 
 ast_node!(BinOp, SyntaxKind::BinOp);
@@ -184,17 +331,18 @@ impl If {
 
     pub(crate) fn true_branch(&self) -> Option<Expr> {
         self.0
-            .children()
-            .find_map(Block::cast)
-            .and_then(|block| Expr::cast(block.0.into()))
+            .children_with_tokens()
+            .skip_while(|element| element.kind() != SyntaxKind::ThenKw)
+            .find_map(Expr::cast)
     }
 
+    /// Returns the expression following `else`, which is either a block or, in an else-if chain,
+    /// another if-expression. Returns `None` if there is no else branch.
     pub(crate) fn false_branch(&self) -> Option<Expr> {
         self.0
-            .children()
-            .filter_map(Block::cast)
-            .nth(1)
-            .and_then(|block| Expr::cast(block.0.into()))
+            .children_with_tokens()
+            .skip_while(|element| element.kind() != SyntaxKind::ElseKw)
+            .find_map(Expr::cast)
     }
 }
 
@@ -220,35 +368,74 @@ ast_node!(FunctionCallParams, SyntaxKind::FunctionCallParams);
 ast_node!(Lambda, SyntaxKind::Lambda);
 
 impl Lambda {
-    pub(crate) fn param_names(&self) -> Option<impl Iterator<Item = SmolStr>> {
+    pub(crate) fn param_atoms(&self) -> Option<impl Iterator<Item = Atom>> {
+        Some(self.params()?.map(|(atom, _)| atom))
+    }
+
+    /// Returns the names of the parameters apart from the rest parameter, along with the default
+    /// value of those that have one.
+    pub(crate) fn params(&self) -> Option<impl Iterator<Item = (Atom, Option<DefaultParam>)>> {
         let params = LambdaParams::cast(self.0.first_child()?)?;
 
         Some(
             params
                 .0
                 .children_with_tokens()
-                .filter_map(NodeOrToken::into_token)
-                .filter_map(Atom::cast)
-                .map(|atom| atom.text().clone()),
+                .filter_map(|element| match element {
+                    NodeOrToken::Token(token) => Some((Atom::cast(token)?, None)),
+                    NodeOrToken::Node(node) => {
+                        let default_param = DefaultParam::cast(node)?;
+                        Some((default_param.name()?, Some(default_param)))
+                    }
+                }),
         )
     }
 
+    pub(crate) fn rest_param(&self) -> Option<RestParam> {
+        self.0.first_child()?.children().find_map(RestParam::cast)
+    }
+
     pub(crate) fn body(&self) -> Option<Expr> {
         self.0.children_with_tokens().find_map(Expr::cast)
     }
 }
 
+ast_node!(DefaultParam, SyntaxKind::DefaultParam);
+
+impl DefaultParam {
+    pub(crate) fn name(&self) -> Option<Atom> {
+        self.0.first_token().and_then(Atom::cast)
+    }
+
+    pub(crate) fn value(&self) -> Option<Expr> {
+        expr_after_equals(&self.0)
+    }
+}
+
+ast_node!(RestParam, SyntaxKind::RestParam);
+
+impl RestParam {
+    /// Returns the name extra arguments are collected into, or `None` if they are discarded (as
+    /// in `|first ...|`).
+    pub(crate) fn binding_name(&self) -> Option<SmolStr> {
+        rest_binding_name(&self.0)
+    }
+}
+
 ast_node!(LambdaParams, SyntaxKind::LambdaParams);
 
 ast_node!(BindingUsage, SyntaxKind::BindingUsage);
 
 impl BindingUsage {
     pub(crate) fn binding_name(&self) -> Option<SmolStr> {
+        self.binding_name_atom().map(|atom| atom.text().clone())
+    }
+
+    pub(crate) fn binding_name_atom(&self) -> Option<Atom> {
         self.0
             .children_with_tokens()
             .filter_map(NodeOrToken::into_token)
-            .find(|token| token.kind() == SyntaxKind::Atom)
-            .map(|token| token.text().clone())
+            .find_map(Atom::cast)
     }
 }
 
@@ -260,6 +447,252 @@ impl Block {
     }
 }
 
+ast_node!(Glob, SyntaxKind::Glob);
+
+impl Glob {
+    pub(crate) fn pattern(&self) -> String {
+        self.0.text().to_string()
+    }
+}
+
+ast_node!(Background, SyntaxKind::Background);
+
+impl Background {
+    pub(crate) fn expr(&self) -> Option<Expr> {
+        self.0.children_with_tokens().find_map(Expr::cast)
+    }
+}
+
+ast_node!(List, SyntaxKind::List);
+
+impl List {
+    pub(crate) fn elements(&self) -> impl Iterator<Item = Expr> {
+        self.0.children_with_tokens().filter_map(Expr::cast)
+    }
+}
+
+ast_node!(Record, SyntaxKind::Record);
+
+impl Record {
+    pub(crate) fn fields(&self) -> impl Iterator<Item = RecordField> {
+        self.0.children().filter_map(RecordField::cast)
+    }
+}
+
+ast_node!(RecordField, SyntaxKind::RecordField);
+
+impl RecordField {
+    pub(crate) fn name(&self) -> Option<SmolStr> {
+        field_name(&self.0)
+    }
+
+    pub(crate) fn value(&self) -> Option<Expr> {
+        self.0
+            .children_with_tokens()
+            .skip_while(|element| element.kind() != SyntaxKind::Equals)
+            .find_map(Expr::cast)
+    }
+}
+
+fn field_name(node: &SyntaxNode) -> Option<SmolStr> {
+    node.first_token()
+        .and_then(Atom::cast)
+        .map(|atom| atom.text().clone())
+}
+
+ast_node!(Match, SyntaxKind::Match);
+
+impl Match {
+    pub(crate) fn scrutinee(&self) -> Option<Expr> {
+        self.0.children_with_tokens().find_map(Expr::cast)
+    }
+
+    pub(crate) fn arms(&self) -> impl Iterator<Item = MatchArm> {
+        self.0.children().filter_map(MatchArm::cast)
+    }
+}
+
+ast_node!(MatchArm, SyntaxKind::MatchArm);
+
+impl MatchArm {
+    pub(crate) fn pattern(&self) -> Option<Pattern> {
+        self.0.children_with_tokens().find_map(Pattern::cast)
+    }
+
+    pub(crate) fn body(&self) -> Option<Expr> {
+        self.0
+            .children_with_tokens()
+            .skip_while(|element| element.kind() != SyntaxKind::FatArrow)
+            .find_map(Expr::cast)
+    }
+}
+
+ast_node!(For, SyntaxKind::For);
+
+impl For {
+    pub(crate) fn binding_name(&self) -> Option<SmolStr> {
+        self.binding_name_atom().map(|atom| atom.text().clone())
+    }
+
+    pub(crate) fn binding_name_atom(&self) -> Option<Atom> {
+        self.0
+            .children_with_tokens()
+            .filter_map(NodeOrToken::into_token)
+            .find_map(Atom::cast)
+    }
+
+    pub(crate) fn iterable(&self) -> Option<Expr> {
+        self.exprs_after_in().next()
+    }
+
+    pub(crate) fn body(&self) -> Option<Expr> {
+        self.exprs_after_in().nth(1)
+    }
+
+    fn exprs_after_in(&self) -> impl Iterator<Item = Expr> {
+        self.0
+            .children_with_tokens()
+            .skip_while(|element| element.kind() != SyntaxKind::InKw)
+            .filter_map(Expr::cast)
+    }
+}
+
+ast_node!(While, SyntaxKind::While);
+
+impl While {
+    pub(crate) fn condition(&self) -> Option<Expr> {
+        self.0.children_with_tokens().find_map(Expr::cast)
+    }
+
+    pub(crate) fn body(&self) -> Option<Expr> {
+        self.0.children_with_tokens().filter_map(Expr::cast).nth(1)
+    }
+}
+
+pub(crate) struct Pattern(SyntaxElement);
+
+pub(crate) enum PatternKind {
+    Wildcard,
+    Ty(Atom),
+    Binding(Atom),
+    NumberLiteral(Digits),
+    StringLiteral(StringLiteral),
+    True,
+    False,
+    List(ListPattern),
+    Record(RecordPattern),
+}
+
+impl Pattern {
+    fn cast(element: SyntaxElement) -> Option<Self> {
+        let is_pattern = match element {
+            SyntaxElement::Node(ref node) => {
+                ListPattern::cast(node.clone()).is_some()
+                    || RecordPattern::cast(node.clone()).is_some()
+            }
+            SyntaxElement::Token(ref token) => matches!(
+                token.kind(),
+                SyntaxKind::Atom
+                    | SyntaxKind::Digits
+                    | SyntaxKind::StringLiteral
+                    | SyntaxKind::True
+                    | SyntaxKind::False
+            ),
+        };
+
+        if is_pattern {
+            Some(Self(element))
+        } else {
+            None
+        }
+    }
+
+    pub(crate) fn kind(&self) -> PatternKind {
+        match &self.0 {
+            SyntaxElement::Node(node) => ListPattern::cast(node.clone())
+                .map(PatternKind::List)
+                .or_else(|| RecordPattern::cast(node.clone()).map(PatternKind::Record))
+                .unwrap(),
+            SyntaxElement::Token(token) => Atom::cast(token.clone())
+                .map(|atom| {
+                    // Type names are capitalised, which sets them apart from bindings.
+                    if atom.text() == "_" {
+                        PatternKind::Wildcard
+                    } else if atom.text().starts_with(char::is_uppercase) {
+                        PatternKind::Ty(atom)
+                    } else {
+                        PatternKind::Binding(atom)
+                    }
+                })
+                .or_else(|| Digits::cast(token.clone()).map(PatternKind::NumberLiteral))
+                .or_else(|| StringLiteral::cast(token.clone()).map(PatternKind::StringLiteral))
+                .or_else(|| True::cast(token.clone()).map(|_| PatternKind::True))
+                .or_else(|| False::cast(token.clone()).map(|_| PatternKind::False))
+                .unwrap(),
+        }
+    }
+
+    pub(crate) fn text_range(&self) -> TextRange {
+        self.0.text_range()
+    }
+}
+
+ast_node!(ListPattern, SyntaxKind::ListPattern);
+
+impl ListPattern {
+    pub(crate) fn elements(&self) -> impl Iterator<Item = Pattern> {
+        self.0.children_with_tokens().filter_map(Pattern::cast)
+    }
+
+    pub(crate) fn rest(&self) -> Option<RestPattern> {
+        self.0.children().find_map(RestPattern::cast)
+    }
+}
+
+ast_node!(RestPattern, SyntaxKind::RestPattern);
+
+impl RestPattern {
+    /// Returns the name the rest of the list is bound to, or `None` if it is discarded (as in
+    /// `[first ...]`).
+    pub(crate) fn binding_name(&self) -> Option<SmolStr> {
+        rest_binding_name(&self.0)
+    }
+}
+
+fn rest_binding_name(node: &SyntaxNode) -> Option<SmolStr> {
+    let text = node.first_token()?.text().clone();
+    let name = text.trim_start_matches("...");
+
+    if name.is_empty() {
+        None
+    } else {
+        Some(name.into())
+    }
+}
+
+ast_node!(RecordPattern, SyntaxKind::RecordPattern);
+
+impl RecordPattern {
+    pub(crate) fn fields(&self) -> impl Iterator<Item = RecordFieldPattern> {
+        self.0.children().filter_map(RecordFieldPattern::cast)
+    }
+}
+
+ast_node!(RecordFieldPattern, SyntaxKind::RecordFieldPattern);
+
+impl RecordFieldPattern {
+    pub(crate) fn name(&self) -> Option<SmolStr> {
+        field_name(&self.0)
+    }
+
+    pub(crate) fn pattern(&self) -> Option<Pattern> {
+        self.0
+            .children_with_tokens()
+            .skip_while(|element| element.kind() != SyntaxKind::Equals)
+            .find_map(Pattern::cast)
+    }
+}
+
 macro_rules! ast_token {
     ($token:ident, $kind:expr) => {
         #[derive(Clone)]
@@ -293,10 +726,23 @@ ast_token!(Digits, SyntaxKind::Digits);
 
 ast_token!(StringLiteral, SyntaxKind::StringLiteral);
 
+impl StringLiteral {
+    pub(crate) fn contents(&self) -> &str {
+        let text = self.text();
+
+        // Slice off quotes.
+        &text[1..text.len() - 1]
+    }
+}
+
 ast_token!(True, SyntaxKind::True);
 
 ast_token!(False, SyntaxKind::False);
 
+ast_token!(BreakKw, SyntaxKind::BreakKw);
+
+ast_token!(ContinueKw, SyntaxKind::ContinueKw);
+
 ast_token!(Equals, SyntaxKind::Equals);
 
 pub(crate) struct OpToken(SyntaxToken);
@@ -307,6 +753,8 @@ impl OpToken {
             || Minus::cast(token.clone()).is_some()
             || Star::cast(token.clone()).is_some()
             || Slash::cast(token.clone()).is_some()
+            || DoubleAmpersand::cast(token.clone()).is_some()
+            || DoublePipe::cast(token.clone()).is_some()
         {
             Some(Self(token))
         } else {
@@ -320,6 +768,8 @@ impl OpToken {
             .or_else(|| Minus::cast(self.0.clone()).map(|_| Op::Sub))
             .or_else(|| Star::cast(self.0.clone()).map(|_| Op::Mul))
             .or_else(|| Slash::cast(self.0.clone()).map(|_| Op::Div))
+            .or_else(|| DoubleAmpersand::cast(self.0.clone()).map(|_| Op::And))
+            .or_else(|| DoublePipe::cast(self.0.clone()).map(|_| Op::Or))
     }
 }
 
@@ -330,3 +780,7 @@ ast_token!(Minus, SyntaxKind::Minus);
 ast_token!(Star, SyntaxKind::Star);
 
 ast_token!(Slash, SyntaxKind::Slash);
+
+ast_token!(DoubleAmpersand, SyntaxKind::DoubleAmpersand);
+
+ast_token!(DoublePipe, SyntaxKind::DoublePipe);
@@ -1,64 +1,431 @@
 //! The evaluation environment, which holds all state needed to evaluate Fjord code.
 
+mod cancellation;
 mod commands;
+mod jobs;
+mod modules;
+pub use cancellation::CancellationToken;
+pub(crate) use cancellation::ForegroundGuard;
+pub use jobs::FinishedJob;
 
-use crate::eval::EvalErrorKind;
-use crate::val::{FuncOrCommand, Val};
+use crate::eval::{Builtin, EvalErrorKind};
+use crate::hir::{Hir, Lambda};
+use crate::val::{Alias, Callee, FuncOrCommand, Val};
 use commands::Commands;
+use is_executable::IsExecutable;
+use jobs::Jobs;
+use modules::{Module, Modules};
 use smol_str::SmolStr;
+use std::cell::RefCell;
 use std::collections::HashMap;
 use std::io;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::process::{Child, ExitStatus};
 use std::rc::Rc;
 
+/// What to do with a glob pattern passed to a command that does not match any files.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum GlobNoMatch {
+    /// Pass the pattern to the command unchanged. This is the default.
+    PassThrough,
+    /// Leave the pattern out of the command’s arguments entirely.
+    Remove,
+    /// Fail with an evaluation error.
+    Error,
+}
+
 /// See the module-level documentation.
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub struct Env<'parent> {
-    bindings: HashMap<SmolStr, Val>,
+    bindings: HashMap<SmolStr, Binding>,
+    // Like bindings, aliases are scoped to the environment they are defined in.
+    aliases: HashMap<SmolStr, Alias>,
     parent: Option<&'parent Self>,
+    // The module the lambda being called in this environment was defined in, if any. Its bindings
+    // and aliases are looked up after those of this environment, but before those of the parent.
+    module: Option<Rc<Module>>,
     commands: Rc<Commands>,
+    jobs: Rc<RefCell<Jobs>>,
+    cancellation_token: CancellationToken,
+    working_dir: PathBuf,
+    glob_no_match: GlobNoMatch,
+    partial_application: bool,
+    module_search_path: Rc<Vec<PathBuf>>,
+    // The modules currently being imported, innermost last, which lets us detect import cycles and
+    // resolve imports relative to the module doing the importing.
+    importing: Rc<RefCell<Vec<PathBuf>>>,
+    modules: Rc<RefCell<Modules>>,
+}
+
+// Bindings can be reassigned from child environments, which only hold a shared reference to their
+// parent, so the value is kept in a RefCell.
+#[derive(Debug, Clone, Eq, PartialEq)]
+struct Binding {
+    val: RefCell<Val>,
+    is_mutable: bool,
+}
+
+pub(crate) struct ImportGuard {
+    importing: Rc<RefCell<Vec<PathBuf>>>,
+}
+
+impl Drop for ImportGuard {
+    fn drop(&mut self) {
+        self.importing.borrow_mut().pop();
+    }
 }
 
 impl<'parent> Env<'parent> {
     /// Constructs a new evaluation environment given a vector of paths to search for executables.
-    pub fn new(search_path: Vec<PathBuf>) -> io::Result<Self> {
-        Ok(Self {
+    ///
+    /// Executables are looked up lazily when they are first called, and paths in `search_path`
+    /// that do not exist are ignored. The working directory defaults to that of the current
+    /// process.
+    pub fn new(search_path: Vec<PathBuf>) -> Self {
+        Self {
             bindings: HashMap::new(),
+            aliases: HashMap::new(),
             parent: None,
-            commands: Rc::new(Commands::new(search_path)?),
+            module: None,
+            commands: Rc::new(Commands::new(search_path)),
+            jobs: Rc::new(RefCell::new(Jobs::default())),
+            cancellation_token: CancellationToken::default(),
+            working_dir: std::env::current_dir().unwrap_or_else(|_| PathBuf::from(".")),
+            glob_no_match: GlobNoMatch::PassThrough,
+            partial_application: false,
+            module_search_path: Rc::new(Vec::new()),
+            importing: Rc::new(RefCell::new(Vec::new())),
+            modules: Rc::new(RefCell::new(Modules::default())),
+        }
+    }
+
+    /// Sets the directory that commands are run in, and that commands invoked by a relative path
+    /// (such as `./configure`) are resolved against.
+    pub fn set_working_dir(&mut self, working_dir: PathBuf) {
+        self.working_dir = working_dir;
+    }
+
+    pub(crate) fn working_dir(&self) -> &Path {
+        &self.working_dir
+    }
+
+    /// Sets what happens when a glob pattern passed to a command does not match any files.
+    pub fn set_glob_no_match(&mut self, glob_no_match: GlobNoMatch) {
+        self.glob_no_match = glob_no_match;
+    }
+
+    pub(crate) fn glob_no_match(&self) -> GlobNoMatch {
+        self.glob_no_match
+    }
+
+    /// Sets whether calling a lambda with too few arguments returns a lambda that takes the rest
+    /// of them, rather than failing with an evaluation error. This is off by default.
+    pub fn set_partial_application(&mut self, partial_application: bool) {
+        self.partial_application = partial_application;
+    }
+
+    pub(crate) fn partial_application(&self) -> bool {
+        self.partial_application
+    }
+
+    /// Sets the directories that modules are searched for in by `import` and `source` when they
+    /// aren’t found relative to the file doing the importing (or the working directory, for code
+    /// that isn’t in a file).
+    pub fn set_module_search_path(&mut self, module_search_path: Vec<PathBuf>) {
+        self.module_search_path = Rc::new(module_search_path);
+    }
+
+    pub(crate) fn resolve_module(&self, path: &str) -> Option<PathBuf> {
+        let base_dir = match self.importing.borrow().last() {
+            Some(importer) => importer.parent().map(Path::to_path_buf),
+            None => Some(self.working_dir.clone()),
+        };
+
+        base_dir
+            .into_iter()
+            .chain(self.module_search_path.iter().cloned())
+            .map(|dir| dir.join(path))
+            .find(|path| path.is_file())
+    }
+
+    /// Marks the module at the given path as being imported until the returned guard is dropped.
+    /// Fails if the module is already being imported, since that would be an import cycle.
+    pub(crate) fn start_importing(&self, path: PathBuf) -> Result<ImportGuard, EvalErrorKind> {
+        let mut importing = self.importing.borrow_mut();
+
+        if importing.contains(&path) {
+            return Err(EvalErrorKind::ImportCycle { path });
+        }
+
+        importing.push(path);
+
+        Ok(ImportGuard {
+            importing: Rc::clone(&self.importing),
         })
     }
 
+    /// Discards all cached executable locations and names, so that executables that have been added
+    /// to or removed from the search path since they were first called or listed are picked up.
+    pub fn rehash(&self) {
+        self.commands.rehash();
+    }
+
+    pub(crate) fn command_names(&self) -> Vec<String> {
+        self.commands.names()
+    }
+
+    /// Returns a token that interrupts evaluation in this environment (and all environments
+    /// derived from it) when cancelled.
+    pub fn cancellation_token(&self) -> CancellationToken {
+        self.cancellation_token.clone()
+    }
+
+    pub(crate) fn is_cancelled(&self) -> bool {
+        self.cancellation_token.is_cancelled()
+    }
+
+    pub(crate) fn run_in_foreground(&self, pid: u32) -> ForegroundGuard<'_> {
+        self.cancellation_token.run_in_foreground(pid)
+    }
+
+    /// Returns all background jobs that have finished since they were last checked on. Each
+    /// finished job is only ever returned once.
+    pub fn reap_finished_jobs(&self) -> Vec<FinishedJob> {
+        self.jobs.borrow_mut().reap_finished()
+    }
+
+    pub(crate) fn add_job(&self, command: String, child: Child) -> u32 {
+        self.jobs.borrow_mut().add(command, child)
+    }
+
+    pub(crate) fn most_recent_job(&self) -> Option<u32> {
+        self.jobs.borrow().most_recent()
+    }
+
+    pub(crate) fn job_pid(&self, id: u32) -> Option<u32> {
+        self.jobs.borrow().pid(id)
+    }
+
+    pub(crate) fn wait_for_job(&self, id: u32) -> Option<io::Result<ExitStatus>> {
+        self.jobs.borrow_mut().wait(id)
+    }
+
+    pub(crate) fn list_jobs(&self) -> Vec<(u32, String, Option<ExitStatus>)> {
+        self.jobs.borrow_mut().list()
+    }
+
     pub(crate) fn create_child(&'parent self) -> Self {
         Self {
             bindings: HashMap::new(),
+            aliases: HashMap::new(),
             parent: Some(self),
+            module: None,
+            commands: Rc::clone(&self.commands),
+            jobs: Rc::clone(&self.jobs),
+            cancellation_token: self.cancellation_token.clone(),
+            working_dir: self.working_dir.clone(),
+            glob_no_match: self.glob_no_match,
+            partial_application: self.partial_application,
+            module_search_path: Rc::clone(&self.module_search_path),
+            importing: Rc::clone(&self.importing),
+            modules: Rc::clone(&self.modules),
+        }
+    }
+
+    /// Creates an environment to evaluate an imported module in. It shares all state with this
+    /// environment except for bindings, so that modules can’t see the bindings of whatever imports
+    /// them.
+    pub(crate) fn create_module_env(&self) -> Env<'static> {
+        Env {
+            bindings: HashMap::new(),
+            aliases: HashMap::new(),
+            parent: None,
+            module: None,
             commands: Rc::clone(&self.commands),
+            jobs: Rc::clone(&self.jobs),
+            cancellation_token: self.cancellation_token.clone(),
+            working_dir: self.working_dir.clone(),
+            glob_no_match: self.glob_no_match,
+            partial_application: self.partial_application,
+            module_search_path: Rc::clone(&self.module_search_path),
+            importing: Rc::clone(&self.importing),
+            modules: Rc::clone(&self.modules),
         }
     }
 
+    /// Creates an environment to call `lambda` in. A lambda that was defined in a module can see
+    /// the rest of the module (including the bindings and aliases it doesn’t export) from wherever
+    /// it is called.
+    pub(crate) fn create_lambda_env(&'parent self, lambda: &Lambda) -> Self {
+        let mut env = self.create_child();
+        env.module = self.modules.borrow().defining(lambda);
+
+        env
+    }
+
+    /// Returns the path of the module that the lambda this environment was created for was defined
+    /// in.
+    pub(crate) fn module_path(&self) -> Option<&Path> {
+        self.module.as_deref().map(Module::path)
+    }
+
+    /// Keeps a module that has been evaluated in `module_env` around, so that the lambdas defined
+    /// in it can be called against it.
+    pub(crate) fn add_module(&self, path: PathBuf, hir: Rc<Hir>, module_env: Env<'static>) {
+        self.modules.borrow_mut().add(path, hir, module_env);
+    }
+
+    /// Returns the values of all bindings defined directly in this environment.
+    pub(crate) fn bindings(&self) -> impl Iterator<Item = (SmolStr, Val)> + '_ {
+        self.bindings
+            .iter()
+            .map(|(name, binding)| (name.clone(), binding.val.borrow().clone()))
+    }
+
+    /// Returns the aliases defined directly in this environment.
+    pub(crate) fn aliases(&self) -> impl Iterator<Item = (SmolStr, Alias)> + '_ {
+        self.aliases
+            .iter()
+            .map(|(name, alias)| (name.clone(), alias.clone()))
+    }
+
+    /// Binds `name` to `val` in this environment, replacing any existing binding with that name.
+    pub fn set_binding(&mut self, name: &str, val: Val) {
+        self.store_binding(name.into(), val);
+    }
+
     pub(crate) fn store_binding(&mut self, name: SmolStr, val: Val) {
-        self.bindings.insert(name, val);
+        self.insert_binding(name, val, false);
+    }
+
+    pub(crate) fn store_mutable_binding(&mut self, name: SmolStr, val: Val) {
+        self.insert_binding(name, val, true);
+    }
+
+    fn insert_binding(&mut self, name: SmolStr, val: Val, is_mutable: bool) {
+        self.bindings.insert(
+            name,
+            Binding {
+                val: RefCell::new(val),
+                is_mutable,
+            },
+        );
+    }
+
+    /// Returns the value bound to `name`, looking in parent environments if it isn’t bound in this
+    /// one.
+    pub fn binding(&self, name: &str) -> Option<Val> {
+        self.get_binding(&name.into())
     }
 
     pub(crate) fn get_binding(&self, name: &SmolStr) -> Option<Val> {
+        self.find_binding(name)
+            .map(|binding| binding.val.borrow().clone())
+    }
+
+    fn find_binding(&self, name: &SmolStr) -> Option<&Binding> {
         self.bindings
             .get(name)
-            .cloned()
-            .or_else(|| self.parent.and_then(|parent| parent.get_binding(name)))
+            .or_else(|| {
+                self.module
+                    .as_ref()
+                    .and_then(|module| module.env().find_binding(name))
+            })
+            .or_else(|| self.parent.and_then(|parent| parent.find_binding(name)))
+    }
+
+    /// Returns the names of all bindings visible from this environment, with those in this
+    /// environment first and those in its ancestors after. Names that are shadowed are included
+    /// more than once.
+    pub(crate) fn binding_names(&self) -> Vec<SmolStr> {
+        let mut names: Vec<_> = self.bindings.keys().cloned().collect();
+        names.sort();
+
+        if let Some(parent) = self.parent {
+            names.extend(parent.binding_names());
+        }
+
+        names
+    }
+
+    /// Updates the nearest binding with the given name, which may be in a parent environment.
+    pub(crate) fn assign_binding(&self, name: &SmolStr, val: Val) -> Result<(), EvalErrorKind> {
+        match self.find_binding(name) {
+            Some(binding) if binding.is_mutable => {
+                *binding.val.borrow_mut() = val;
+                Ok(())
+            }
+            Some(_) => Err(EvalErrorKind::AssignToImmutableBinding),
+            None => Err(EvalErrorKind::AssignToUndeclaredBinding),
+        }
+    }
+
+    pub(crate) fn store_alias(&mut self, name: SmolStr, alias: Alias) {
+        self.aliases.insert(name, alias);
     }
 
-    pub(crate) fn get_func_or_command(
+    fn get_alias(&self, name: &SmolStr) -> Option<&Alias> {
+        self.aliases
+            .get(name)
+            .or_else(|| {
+                self.module
+                    .as_ref()
+                    .and_then(|module| module.env().get_alias(name))
+            })
+            .or_else(|| self.parent.and_then(|parent| parent.get_alias(name)))
+    }
+
+    /// Resolves the name of a function call. Aliases take precedence over everything else, and are
+    /// resolved to whatever they expand to.
+    pub(crate) fn get_func_or_command(&self, name: &SmolStr) -> Result<Callee, EvalErrorKind> {
+        // What an alias expands to is never looked up as an alias itself, which means that an alias
+        // can refer to a command with the same name (as in `alias ls = ls -F`) and that aliases
+        // can’t form cycles.
+        match self.get_alias(name) {
+            Some(alias) => Ok(Callee {
+                func_or_command: self.get_func_or_command_ignoring_aliases(&alias.name)?,
+                alias_args: alias.args.clone(),
+            }),
+            None => Ok(Callee {
+                func_or_command: self.get_func_or_command_ignoring_aliases(name)?,
+                alias_args: Vec::new(),
+            }),
+        }
+    }
+
+    fn get_func_or_command_ignoring_aliases(
         &self,
         name: &SmolStr,
     ) -> Result<FuncOrCommand, EvalErrorKind> {
-        match (self.get_binding(name), self.commands.get(name.as_str())) {
-            // If we have a lambda, then we use that over a command.
-            (Some(Val::Lambda(lambda)), _) => Ok(FuncOrCommand::Func(lambda)),
+        // Names containing a path separator (e.g. ‘./configure’ or ‘/usr/local/bin/tool’) refer to
+        // a specific file, so we don’t look for them among bindings or in the search path.
+        if name.contains(std::path::is_separator) {
+            let path = self.working_dir.join(name.as_str());
+
+            return if !path.is_file() {
+                Err(EvalErrorKind::FuncOrCommandDoesNotExist)
+            } else if !path.is_executable() {
+                Err(EvalErrorKind::NotExecutable)
+            } else {
+                Ok(FuncOrCommand::Command(path))
+            };
+        }
+
+        let binding = self.get_binding(name);
+
+        // If we have a lambda, then we use that over a builtin or a command.
+        if let Some(Val::Lambda(lambda)) = binding {
+            return Ok(FuncOrCommand::Func(lambda));
+        }
+
+        if let Some(builtin) = Builtin::from_name(name) {
+            return Ok(FuncOrCommand::Builtin(builtin));
+        }
 
+        match (binding, self.commands.get(name.as_str())) {
             // In this case we either don’t have a binding with that name, or we do have a binding
             // but it isn’t a lambda, and we have a command with the name requested.
-            (_, Some(path)) => Ok(FuncOrCommand::Command(path.to_path_buf())),
+            (_, Some(path)) => Ok(FuncOrCommand::Command(path)),
 
             // Here we have a binding with the name, but it isn’t a lambda.
             (Some(val), None) => Err(EvalErrorKind::CallNonLambda { ty: val.ty() }),
@@ -0,0 +1,159 @@
+use std::sync::atomic::{AtomicBool, AtomicI32, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// A handle that can be used to interrupt evaluation, including from another thread.
+///
+/// Once cancelled, evaluation stops at the next item or function call with an error, and any
+/// command running in the foreground is sent an interrupt signal (on Unix). The token stays
+/// cancelled until it is reset.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    cancelled: AtomicBool,
+    // Zero until a signal asking the process to terminate is received.
+    termination_signal: AtomicI32,
+    foreground_pids: Mutex<Vec<u32>>,
+}
+
+impl CancellationToken {
+    /// Cancels evaluation.
+    pub fn cancel(&self) {
+        #[cfg(unix)]
+        self.interrupt(libc::SIGINT, false);
+
+        #[cfg(not(unix))]
+        self.inner.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    /// Returns whether the token has been cancelled since it was last reset.
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Clears a previous cancellation, so that evaluation can proceed again.
+    pub fn reset(&self) {
+        self.inner.cancelled.store(false, Ordering::SeqCst);
+    }
+
+    /// Returns the signal asking the process to terminate (such as SIGTERM) that has been
+    /// received, if any. Unlike cancellation this isn’t cleared by resetting the token, since the
+    /// process is expected to exit once evaluation has stopped.
+    pub fn termination_signal(&self) -> Option<i32> {
+        match self.inner.termination_signal.load(Ordering::SeqCst) {
+            0 => None,
+            signal => Some(signal),
+        }
+    }
+
+    /// Installs handlers for SIGINT and SIGTERM, which replace the default behaviour of terminating
+    /// the process.
+    ///
+    /// SIGINT cancels this token, and is forwarded to any command running in the foreground that
+    /// didn’t already receive it from the terminal. SIGTERM also cancels this token and is
+    /// forwarded to any command running in the foreground, after which it is recorded as the
+    /// termination signal and `on_termination` is called with it. Exiting is left to the caller.
+    #[cfg(unix)]
+    pub fn install_signal_handlers(
+        &self,
+        on_termination: impl Fn(i32) + Send + 'static,
+    ) -> std::io::Result<()> {
+        use signal_hook::consts::{SIGINT, SIGTERM};
+        use signal_hook::iterator::Signals;
+
+        let mut signals = Signals::new([SIGINT, SIGTERM])?;
+        let token = self.clone();
+
+        std::thread::spawn(move || {
+            for signal in signals.forever() {
+                if signal == SIGTERM {
+                    token.interrupt(SIGTERM, false);
+                    token
+                        .inner
+                        .termination_signal
+                        .store(SIGTERM, Ordering::SeqCst);
+                    on_termination(SIGTERM);
+                } else {
+                    token.interrupt(signal, true);
+                }
+            }
+        });
+
+        Ok(())
+    }
+
+    // When the signal was sent by the terminal (as with Ctrl-C) every process in the terminal’s
+    // foreground process group has received it already, so it’s only forwarded to those in other
+    // groups.
+    #[cfg(unix)]
+    fn interrupt(&self, signal: libc::c_int, skip_terminal_foreground: bool) {
+        self.inner.cancelled.store(true, Ordering::SeqCst);
+
+        // SAFETY: tcgetpgrp has no memory safety requirements, and returns -1 if standard input
+        // isn’t a terminal.
+        let terminal_foreground = unsafe { libc::tcgetpgrp(libc::STDIN_FILENO) };
+
+        for pid in self.inner.foreground_pids.lock().unwrap().iter() {
+            let pid = *pid as libc::pid_t;
+
+            // SAFETY: getpgid and kill have no memory safety requirements.
+            unsafe {
+                if skip_terminal_foreground
+                    && terminal_foreground != -1
+                    && libc::getpgid(pid) == terminal_foreground
+                {
+                    continue;
+                }
+
+                libc::kill(pid, signal);
+            }
+        }
+    }
+
+    /// Marks the process with the given ID as running in the foreground until the returned guard
+    /// is dropped, during which time it is interrupted if the token is cancelled.
+    pub(crate) fn run_in_foreground(&self, pid: u32) -> ForegroundGuard<'_> {
+        self.inner.foreground_pids.lock().unwrap().push(pid);
+
+        // The token might have been cancelled between the process being started and it being
+        // registered here, in which case it would have missed the signal.
+        #[cfg(unix)]
+        {
+            if self.is_cancelled() {
+                // SAFETY: kill has no memory safety requirements.
+                unsafe {
+                    libc::kill(pid as libc::pid_t, libc::SIGINT);
+                }
+            }
+        }
+
+        ForegroundGuard { token: self, pid }
+    }
+}
+
+// All environments derived from the same root share a token, so tokens are compared by state.
+impl PartialEq for CancellationToken {
+    fn eq(&self, other: &Self) -> bool {
+        self.is_cancelled() == other.is_cancelled()
+    }
+}
+
+impl Eq for CancellationToken {}
+
+pub(crate) struct ForegroundGuard<'a> {
+    token: &'a CancellationToken,
+    pid: u32,
+}
+
+impl Drop for ForegroundGuard<'_> {
+    fn drop(&mut self) {
+        let mut foreground_pids = self.token.inner.foreground_pids.lock().unwrap();
+
+        if let Some(idx) = foreground_pids.iter().position(|pid| *pid == self.pid) {
+            foreground_pids.remove(idx);
+        }
+    }
+}
@@ -1,57 +1,238 @@
 use is_executable::IsExecutable;
+use std::cell::RefCell;
 use std::collections::HashMap;
 use std::ffi::{OsStr, OsString};
+use std::fs;
 use std::path::{Path, PathBuf};
-use std::{fs, io};
 
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub(super) struct Commands {
-    commands: HashMap<OsString, PathBuf>,
+    // Commands are looked up lazily the first time they are requested and are then cached here,
+    // so that we don’t have to hit the filesystem on every call.
+    cache: RefCell<Cache>,
     search_path: Vec<PathBuf>,
 }
 
+#[derive(Debug, Default, Clone, Eq, PartialEq)]
+struct Cache {
+    paths: HashMap<OsString, PathBuf>,
+    // Listing every command means reading every directory in the search path, which is too slow
+    // to do each time a completion is requested.
+    names: Option<Vec<String>>,
+}
+
 impl Commands {
-    pub(super) fn new(search_path: Vec<PathBuf>) -> io::Result<Self> {
-        let mut commands = HashMap::new();
-
-        let process_possible_command = |path: &Path| {
-            if !path.is_executable() {
-                return None;
-            }
-
-            // If we’re on Windows the user should not have to type out ‘.exe’ after the name of
-            // every command they want to run, so we return the ‘file stem’ (file name without
-            // extension).
-            #[cfg(windows)]
-            {
-                path.file_stem().map(OsStr::to_os_string)
-            }
-
-            // If we’re not on Windows, then we don’t have to worry about the file extenion, and the
-            // user can just type out the full file name.
-            #[cfg(not(windows))]
-            {
-                path.file_name().map(OsStr::to_os_string)
-            }
-        };
-
-        for path in &search_path {
-            for dir_entry in fs::read_dir(path)? {
-                let path = dir_entry?.path();
-
-                if let Some(name) = process_possible_command(&path) {
-                    commands.insert(name, path);
-                }
-            }
+    pub(super) fn new(search_path: Vec<PathBuf>) -> Self {
+        Self {
+            cache: RefCell::new(Cache::default()),
+            search_path,
         }
+    }
 
-        Ok(Self {
-            commands,
-            search_path,
+    pub(crate) fn get(&self, name: impl AsRef<OsStr>) -> Option<PathBuf> {
+        let name = name.as_ref();
+
+        if let Some(path) = self.cache.borrow().paths.get(name) {
+            return Some(path.clone());
+        }
+
+        let path = self.search(name)?;
+        self.cache
+            .borrow_mut()
+            .paths
+            .insert(name.to_os_string(), path.clone());
+
+        Some(path)
+    }
+
+    /// Lists the names of every command in the search path, sorted and without duplicates.
+    pub(crate) fn names(&self) -> Vec<String> {
+        if let Some(names) = &self.cache.borrow().names {
+            return names.clone();
+        }
+
+        let names = self.list_names();
+        self.cache.borrow_mut().names = Some(names.clone());
+
+        names
+    }
+
+    fn list_names(&self) -> Vec<String> {
+        let mut names: Vec<_> = self
+            .search_path
+            .iter()
+            .filter_map(|dir| fs::read_dir(dir).ok())
+            .flatten()
+            .filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .filter(|path| path.is_file() && path.is_executable())
+            .filter_map(|path| command_name(&path))
+            .collect();
+
+        names.sort();
+        names.dedup();
+
+        names
+    }
+
+    /// Forgets all previously found commands and command names, so that commands which have been
+    /// installed, removed or moved since they were last looked up are found in their new location.
+    pub(super) fn rehash(&self) {
+        *self.cache.borrow_mut() = Cache::default();
+    }
+
+    fn search(&self, name: &OsStr) -> Option<PathBuf> {
+        // Search path entries which do not exist (or are not directories) are simply skipped,
+        // since it’s common for PATH to contain directories that haven’t been created.
+        self.search_path
+            .iter()
+            .filter(|dir| dir.is_dir())
+            .flat_map(|dir| candidates(dir, name))
+            .find(|path| path.is_executable())
+    }
+}
+
+// If we’re on Windows the user should not have to type out ‘.exe’ after the name of every command
+// they want to run, so we try each of the extensions executables commonly have.
+#[cfg(windows)]
+fn candidates(dir: &Path, name: &OsStr) -> Vec<PathBuf> {
+    let mut candidates: Vec<_> = ["exe", "com", "bat", "cmd"]
+        .iter()
+        .map(|extension| {
+            let mut file_name = name.to_os_string();
+            file_name.push(".");
+            file_name.push(extension);
+
+            dir.join(file_name)
         })
+        .collect();
+
+    candidates.push(dir.join(name));
+
+    candidates
+}
+
+// If we’re not on Windows, then we don’t have to worry about the file extension, and the user can
+// just type out the full file name.
+#[cfg(not(windows))]
+fn candidates(dir: &Path, name: &OsStr) -> Vec<PathBuf> {
+    vec![dir.join(name)]
+}
+
+// The reverse of `candidates`: the name a command found at the given path is called by.
+#[cfg(windows)]
+fn command_name(path: &Path) -> Option<String> {
+    let has_executable_extension = path.extension().map_or(false, |extension| {
+        ["exe", "com", "bat", "cmd"]
+            .iter()
+            .any(|executable_extension| extension.eq_ignore_ascii_case(executable_extension))
+    });
+
+    if has_executable_extension {
+        path.file_stem()?.to_str().map(str::to_string)
+    } else {
+        path.file_name()?.to_str().map(str::to_string)
     }
+}
+
+#[cfg(not(windows))]
+fn command_name(path: &Path) -> Option<String> {
+    path.file_name()?.to_str().map(str::to_string)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+    use std::fs;
+
+    #[test]
+    fn skip_search_path_entries_that_do_not_exist() {
+        let dir = TempDir::new("commands-skip-missing");
+        dir.create_executable("foo");
+
+        let commands = Commands::new(vec![dir.join("does-not-exist"), dir.to_path_buf()]);
+
+        assert_eq!(commands.get("foo"), Some(dir.join("foo")));
+    }
+
+    #[test]
+    fn do_not_find_non_executable_files() {
+        let dir = TempDir::new("commands-non-executable");
+        fs::write(dir.join("foo"), "").unwrap();
+
+        let commands = Commands::new(vec![dir.to_path_buf()]);
+
+        assert_eq!(commands.get("foo"), None);
+    }
+
+    #[test]
+    fn prefer_earlier_search_path_entries() {
+        let first = TempDir::new("commands-precedence-first");
+        let second = TempDir::new("commands-precedence-second");
+        first.create_executable("foo");
+        second.create_executable("foo");
+
+        let commands = Commands::new(vec![first.to_path_buf(), second.to_path_buf()]);
+
+        assert_eq!(commands.get("foo"), Some(first.join("foo")));
+    }
+
+    #[test]
+    fn find_command_installed_after_construction() {
+        let dir = TempDir::new("commands-installed-later");
+        let commands = Commands::new(vec![dir.to_path_buf()]);
+
+        assert_eq!(commands.get("foo"), None);
+
+        dir.create_executable("foo");
+
+        assert_eq!(commands.get("foo"), Some(dir.join("foo")));
+    }
+
+    #[test]
+    fn list_command_names() {
+        let first = TempDir::new("commands-names-first");
+        let second = TempDir::new("commands-names-second");
+        first.create_executable("foo");
+        second.create_executable("bar");
+        second.create_executable("foo");
+        fs::write(first.join("not-executable"), "").unwrap();
+        fs::create_dir(first.join("dir")).unwrap();
+
+        let commands = Commands::new(vec![first.to_path_buf(), second.to_path_buf()]);
+
+        assert_eq!(commands.names(), ["bar", "foo"]);
+    }
+
+    #[test]
+    fn rehash_forgets_moved_commands() {
+        let first = TempDir::new("commands-rehash-first");
+        let second = TempDir::new("commands-rehash-second");
+        second.create_executable("foo");
+
+        let commands = Commands::new(vec![first.to_path_buf(), second.to_path_buf()]);
+        assert_eq!(commands.get("foo"), Some(second.join("foo")));
+
+        first.create_executable("foo");
+        assert_eq!(commands.get("foo"), Some(second.join("foo")));
+
+        commands.rehash();
+        assert_eq!(commands.get("foo"), Some(first.join("foo")));
+    }
+
+    #[test]
+    fn rehash_lists_installed_commands() {
+        let dir = TempDir::new("commands-rehash-names");
+        dir.create_executable("foo");
+
+        let commands = Commands::new(vec![dir.to_path_buf()]);
+        assert_eq!(commands.names(), ["foo"]);
+
+        dir.create_executable("bar");
+        assert_eq!(commands.names(), ["foo"]);
 
-    pub(crate) fn get(&self, name: impl AsRef<OsStr>) -> Option<&Path> {
-        self.commands.get(name.as_ref()).map(PathBuf::as_path)
+        commands.rehash();
+        assert_eq!(commands.names(), ["bar", "foo"]);
     }
 }
@@ -0,0 +1,132 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io;
+use std::process::{Child, ExitStatus};
+
+/// A background job that has finished running.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct FinishedJob {
+    id: u32,
+    command: String,
+    exit_status: ExitStatus,
+}
+
+impl FinishedJob {
+    /// The number the job was identified by while it was running.
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    /// The source code that started the job.
+    pub fn command(&self) -> &str {
+        &self.command
+    }
+
+    /// How the job’s process exited.
+    pub fn exit_status(&self) -> ExitStatus {
+        self.exit_status
+    }
+}
+
+impl fmt::Display for FinishedJob {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = if self.exit_status.success() {
+            "done"
+        } else {
+            "exit"
+        };
+
+        write!(
+            f,
+            "[{}] {} ({})  {}",
+            self.id, state, self.exit_status, self.command
+        )
+    }
+}
+
+#[derive(Debug)]
+struct Job {
+    command: String,
+    child: Child,
+}
+
+#[derive(Debug, Default)]
+pub(super) struct Jobs {
+    jobs: BTreeMap<u32, Job>,
+    next_id: u32,
+}
+
+impl Jobs {
+    pub(super) fn add(&mut self, command: String, child: Child) -> u32 {
+        self.next_id += 1;
+        self.jobs.insert(self.next_id, Job { command, child });
+
+        self.next_id
+    }
+
+    pub(super) fn most_recent(&self) -> Option<u32> {
+        self.jobs.keys().next_back().copied()
+    }
+
+    pub(super) fn pid(&self, id: u32) -> Option<u32> {
+        self.jobs.get(&id).map(|job| job.child.id())
+    }
+
+    /// Blocks until the given job has finished, after which it is no longer tracked.
+    pub(super) fn wait(&mut self, id: u32) -> Option<io::Result<ExitStatus>> {
+        let mut job = self.jobs.remove(&id)?;
+        Some(job.child.wait())
+    }
+
+    /// Lists all jobs along with their exit status if they have finished. Finished jobs are
+    /// reported exactly once, either here or by `reap_finished`.
+    pub(super) fn list(&mut self) -> Vec<(u32, String, Option<ExitStatus>)> {
+        let list = self
+            .jobs
+            .iter_mut()
+            .map(|(id, job)| {
+                let exit_status = job.child.try_wait().ok().flatten();
+                (*id, job.command.clone(), exit_status)
+            })
+            .collect::<Vec<_>>();
+
+        for (id, _, exit_status) in &list {
+            if exit_status.is_some() {
+                self.jobs.remove(id);
+            }
+        }
+
+        list
+    }
+
+    pub(super) fn reap_finished(&mut self) -> Vec<FinishedJob> {
+        self.list()
+            .into_iter()
+            .filter_map(|(id, command, exit_status)| {
+                exit_status.map(|exit_status| FinishedJob {
+                    id,
+                    command,
+                    exit_status,
+                })
+            })
+            .collect()
+    }
+}
+
+// Jobs are owned by the root environment and shared between all of its children, so two job
+// tables are considered equal if they are tracking the same jobs.
+impl PartialEq for Jobs {
+    fn eq(&self, other: &Self) -> bool {
+        self.next_id == other.next_id
+            && self.jobs.len() == other.jobs.len()
+            && self
+                .jobs
+                .iter()
+                .zip(&other.jobs)
+                .all(|((id, job), (other_id, other_job))| {
+                    id == other_id && job.child.id() == other_job.child.id()
+                })
+    }
+}
+
+impl Eq for Jobs {}
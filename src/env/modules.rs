@@ -0,0 +1,83 @@
+use super::Env;
+use crate::hir::{Hir, Lambda};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+/// A module that has been imported. Modules are kept around after they have been evaluated so
+/// that the lambdas defined in them can still see the rest of the module when they are called.
+pub(crate) struct Module {
+    path: PathBuf,
+    hir: Rc<Hir>,
+    env: Env<'static>,
+}
+
+impl Module {
+    pub(super) fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub(super) fn env(&self) -> &Env<'static> {
+        &self.env
+    }
+}
+
+// The lowered code and environment of a module are large (and the environment refers back to the
+// table of modules), so only the path is shown.
+impl fmt::Debug for Module {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Module").field(&self.path).finish()
+    }
+}
+
+// Importing the same file twice evaluates it twice, so modules are only equal if they are the same
+// import.
+impl PartialEq for Module {
+    fn eq(&self, other: &Self) -> bool {
+        self.path == other.path && Rc::ptr_eq(&self.hir, &other.hir)
+    }
+}
+
+impl Eq for Module {}
+
+#[derive(Default)]
+pub(super) struct Modules {
+    modules: Vec<Rc<Module>>,
+}
+
+impl Modules {
+    pub(super) fn add(&mut self, path: PathBuf, hir: Rc<Hir>, env: Env<'static>) {
+        self.modules.push(Rc::new(Module { path, hir, env }));
+    }
+
+    // Every lambda defined in a module shares the module’s lowered code, which is how we tell
+    // which module a lambda came from.
+    pub(super) fn defining(&self, lambda: &Lambda) -> Option<Rc<Module>> {
+        self.modules
+            .iter()
+            .find(|module| Rc::ptr_eq(&module.hir, lambda.hir()))
+            .cloned()
+    }
+}
+
+impl fmt::Debug for Modules {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(&self.modules).finish()
+    }
+}
+
+// Modules are shared between all environments derived from the same root, and each import lowers
+// its module afresh, so two tables are considered equal if they hold the same files in the same
+// order.
+impl PartialEq for Modules {
+    fn eq(&self, other: &Self) -> bool {
+        self.modules.len() == other.modules.len()
+            && self
+                .modules
+                .iter()
+                .zip(&other.modules)
+                .all(|(module, other_module)| module.path == other_module.path)
+    }
+}
+
+impl Eq for Modules {}
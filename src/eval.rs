@@ -1,260 +1,832 @@
 //! Implementation of the Fjord interpreter and related types.
 
+mod builtins;
 mod error;
+mod expand;
+mod import;
+mod pattern;
+mod vm;
+pub(crate) use builtins::Builtin;
 pub use error::EvalError;
 pub(crate) use error::EvalErrorKind;
+pub(crate) use expand::expand_tilde;
+pub use vm::Program;
 
-use crate::ast::{
-    Atom, BinOp, BindingDef, BindingUsage, Block, Digits, Expr, ExprKind, FunctionCall, If, Item,
-    ItemKind, Lambda, Root, StringLiteral,
-};
 use crate::env::Env;
-use crate::val::{FuncOrCommand, Val};
+use crate::hir::{Expr, ExprIdx, Hir, Item, ItemIdx, Lambda, Name};
+use crate::val::{Alias, AliasArg, Callee, FuncOrCommand, Val};
 use crate::Op;
-use std::cmp::Ordering;
-use std::process::Command;
+use smol_str::SmolStr;
+use std::collections::BTreeMap;
+use std::io::Read;
+use std::path::PathBuf;
+use std::process::{Command, ExitStatus, Stdio};
+use std::rc::Rc;
 use text_size::TextRange;
 
-fn eval_items(items: Vec<Item>, env: &mut Env<'_>) -> Result<Val, EvalError> {
-    let num_items = items.len();
-    let at_last = |idx| idx == num_items - 1;
+fn check_interrupted(env: &Env<'_>, range: TextRange) -> Result<(), EvalError> {
+    if env.is_cancelled() {
+        Err(EvalError::new(EvalErrorKind::Interrupted, range))
+    } else {
+        Ok(())
+    }
+}
+
+// Evaluation needs the `Hir` in an `Rc` so that lambdas can keep the code they were defined in
+// alive.
+impl Hir {
+    pub(crate) fn eval(self: &Rc<Self>, env: &mut Env<'_>) -> Result<Val, EvalError> {
+        self.eval_items(&self.root_items, env)
+            .map_err(EvalError::outside_loop)
+    }
 
-    if items.is_empty() {
-        return Ok(Val::Nil);
+    /// Evaluates one of the items at the root of the code on its own.
+    pub(crate) fn eval_root_item(
+        self: &Rc<Self>,
+        idx: ItemIdx,
+        env: &mut Env<'_>,
+    ) -> Result<Val, EvalError> {
+        self.eval_item(idx, env).map_err(EvalError::outside_loop)
     }
 
-    for (idx, item) in items.iter().enumerate() {
-        let eval_output = item.eval(env)?;
-        if at_last(idx) {
-            return Ok(eval_output);
+    fn eval_items(self: &Rc<Self>, items: &[ItemIdx], env: &mut Env<'_>) -> Result<Val, EvalError> {
+        let mut val = Val::Nil;
+
+        for item in items {
+            val = self.eval_item(*item, env)?;
         }
+
+        Ok(val)
     }
 
-    // All lists are either empty (see the is_empty call before the for loop) or have a last
-    // item (see at_last call above), so we are guaranteed to have returned by this point.
-    unreachable!()
-}
+    fn eval_item(self: &Rc<Self>, idx: ItemIdx, env: &mut Env<'_>) -> Result<Val, EvalError> {
+        check_interrupted(env, self.source_map[idx])?;
+
+        match &self.items[idx] {
+            Item::BindingDef {
+                name,
+                is_mutable,
+                expr,
+            } => {
+                let val = self.eval_expr(*expr, env)?;
+                let name = self.names[*name].clone();
+
+                if *is_mutable {
+                    env.store_mutable_binding(name, val);
+                } else {
+                    env.store_binding(name, val);
+                }
+            }
+            Item::Assignment { name, expr } => {
+                let val = self.eval_expr(*expr, env)?;
+
+                env.assign_binding(&self.names[*name], val)
+                    .map_err(|kind| EvalError::new(kind, self.source_map.assignment_name(idx)))?;
+            }
+            Item::Import { path, is_source } => import::import(
+                path,
+                self.source_map.import_path(idx),
+                *is_source,
+                env,
+                |hir, module_env| hir.eval(module_env),
+            )?,
+            Item::Alias { name, call } => {
+                let alias = Alias {
+                    name: self.names[self.call_name(*call)].clone(),
+                    args: self.alias_args(*call, env)?,
+                };
+
+                env.store_alias(self.names[*name].clone(), alias);
+            }
+            Item::Expr(expr) => return self.eval_expr(*expr, env),
+        }
 
-impl Root {
-    pub(crate) fn eval(&self, env: &mut Env<'_>) -> Result<Val, EvalError> {
-        eval_items(self.items().collect(), env)
+        Ok(Val::Nil)
     }
-}
 
-impl Item {
-    fn eval(&self, env: &mut Env<'_>) -> Result<Val, EvalError> {
-        match self.kind() {
-            ItemKind::BindingDef(binding_def) => {
-                binding_def.eval(env)?;
+    fn eval_expr(self: &Rc<Self>, idx: ExprIdx, env: &Env<'_>) -> Result<Val, EvalError> {
+        let range = self.source_map[idx];
+
+        match &self.exprs[idx] {
+            Expr::BinOp { op, lhs, rhs } => match op {
+                Op::And | Op::Or => self.eval_logical(*op, *lhs, *rhs, env),
+                Op::Add | Op::Sub | Op::Mul | Op::Div => {
+                    let lhs = self.eval_expr(*lhs, env)?;
+                    let rhs = self.eval_expr(*rhs, env)?;
+
+                    arithmetic(*op, lhs, rhs, range)
+                }
+            },
+            Expr::If {
+                condition,
+                true_branch,
+                false_branch,
+            } => match self.eval_expr(*condition, env)? {
+                Val::Bool(true) => self.eval_expr(*true_branch, env),
+                Val::Bool(false) => match false_branch {
+                    Some(false_branch) => self.eval_expr(*false_branch, env),
+                    None => Ok(Val::Nil),
+                },
+                _ => Err(EvalError::new(
+                    EvalErrorKind::NonBoolCond,
+                    self.source_map[*condition],
+                )),
+            },
+            Expr::FunctionCall { .. } => self.eval_call(idx, env),
+            Expr::Lambda { .. } => Ok(Val::Lambda(Lambda::new(Rc::clone(self), idx))),
+            Expr::BindingUsage(name) => env
+                .get_binding(&self.names[*name])
+                .ok_or_else(|| EvalError::new(EvalErrorKind::BindingDoesNotExist, range)),
+            Expr::Block(items) => {
+                let mut child_env = env.create_child();
+                self.eval_items(items, &mut child_env)
+            }
+            Expr::Glob(pattern) => Ok(Val::Str(pattern.clone())),
+            Expr::Background { expr, command } => match self.exprs[*expr] {
+                Expr::FunctionCall { .. } => self.spawn(*expr, command, env),
+                _ => Err(EvalError::new(
+                    EvalErrorKind::BackgroundNonCommand,
+                    self.source_map[*expr],
+                )),
+            },
+            Expr::List(elements) => {
+                let elements = elements
+                    .iter()
+                    .map(|element| self.eval_expr(*element, env))
+                    .collect::<Result<_, _>>()?;
+
+                Ok(Val::List(elements))
+            }
+            Expr::Record(fields) => {
+                let mut record = BTreeMap::new();
+
+                for (name, value) in fields {
+                    record.insert(self.names[*name].clone(), self.eval_expr(*value, env)?);
+                }
+
+                Ok(Val::Record(record))
+            }
+            Expr::Match { scrutinee, arms } => {
+                let val = self.eval_expr(*scrutinee, env)?;
+
+                for (pattern, body) in arms {
+                    let mut bindings = Vec::new();
+
+                    if self.bind_pattern(*pattern, &val, &mut bindings)? {
+                        let mut arm_env = env.create_child();
+
+                        for (name, val) in bindings {
+                            arm_env.store_binding(name, val);
+                        }
+
+                        return self.eval_expr(*body, &arm_env);
+                    }
+                }
+
+                Err(EvalError::new(
+                    EvalErrorKind::NonExhaustiveMatch,
+                    self.source_map[*scrutinee],
+                ))
+            }
+            Expr::For {
+                binding_name,
+                iterable,
+                body,
+            } => {
+                for element in self.eval_iterable(*iterable, env)? {
+                    check_interrupted(env, range)?;
+
+                    let mut iteration_env = env.create_child();
+                    iteration_env.store_binding(self.names[*binding_name].clone(), element);
+
+                    if !loop_should_continue(self.eval_expr(*body, &iteration_env))? {
+                        break;
+                    }
+                }
+
                 Ok(Val::Nil)
             }
-            ItemKind::Expr(expr) => expr.eval(env),
+            Expr::While { condition, body } => loop {
+                check_interrupted(env, range)?;
+
+                let keep_going = match self.eval_expr(*condition, env)? {
+                    Val::Bool(b) => b,
+                    Val::ExitStatus(code) => code == 0,
+                    _ => {
+                        return Err(EvalError::new(
+                            EvalErrorKind::NonBoolCond,
+                            self.source_map[*condition],
+                        ))
+                    }
+                };
+
+                if !keep_going || !loop_should_continue(self.eval_expr(*body, &env.create_child()))?
+                {
+                    return Ok(Val::Nil);
+                }
+            },
+            Expr::Break => Err(EvalError::new(EvalErrorKind::Break, range)),
+            Expr::Continue => Err(EvalError::new(EvalErrorKind::Continue, range)),
+            Expr::Atom(text) => Ok(Val::Str(text.to_string())),
+            Expr::Number(n) => Ok(Val::Number(*n)),
+            Expr::Str(s) => Ok(Val::Str(s.clone())),
+            Expr::Bool(b) => Ok(Val::Bool(*b)),
+            Expr::Invalid => Err(EvalError::new(EvalErrorKind::InvalidCode, range)),
         }
     }
-}
 
-impl BindingDef {
-    fn eval(&self, env: &mut Env<'_>) -> Result<(), EvalError> {
-        let expr = self.expr().unwrap().eval(env)?;
-        let name = self.binding_name().unwrap();
+    fn eval_logical(
+        self: &Rc<Self>,
+        op: Op,
+        lhs: ExprIdx,
+        rhs: ExprIdx,
+        env: &Env<'_>,
+    ) -> Result<Val, EvalError> {
+        let lhs_val = self.eval_expr(lhs, env)?;
 
-        env.store_binding(name, expr);
+        let lhs_succeeded = match lhs_val {
+            Val::Bool(b) => b,
+            Val::ExitStatus(code) => code == 0,
+            _ => {
+                return Err(EvalError::new(
+                    EvalErrorKind::LogicalOpOnNonBool { ty: lhs_val.ty() },
+                    self.source_map[lhs],
+                ));
+            }
+        };
 
-        Ok(())
+        // `&&` only evaluates its right-hand side if the left-hand side succeeded, while `||` only
+        // does so if it failed. Otherwise, the value of the left-hand side is the result.
+        if lhs_succeeded == (op == Op::And) {
+            self.eval_expr(rhs, env)
+        } else {
+            Ok(lhs_val)
+        }
     }
-}
 
-impl Expr {
-    fn eval(&self, env: &Env<'_>) -> Result<Val, EvalError> {
-        match self.kind() {
-            ExprKind::BinOp(bin_op) => bin_op.eval(env),
-            ExprKind::If(if_) => if_.eval(env),
-            ExprKind::FunctionCall(function_call) => function_call.eval(env),
-            ExprKind::Lambda(lambda) => Ok(Val::Lambda(lambda)),
-            ExprKind::BindingUsage(binding_usage) => binding_usage.eval(env),
-            ExprKind::Block(block) => block.eval(env),
-            ExprKind::Atom(atom) => Ok(atom.eval()),
-            ExprKind::NumberLiteral(digits) => Ok(digits.eval()),
-            ExprKind::StringLiteral(string_literal) => Ok(string_literal.eval()),
-            ExprKind::True(_) => Ok(Val::Bool(true)),
-            ExprKind::False(_) => Ok(Val::Bool(false)),
+    fn eval_iterable(
+        self: &Rc<Self>,
+        iterable: ExprIdx,
+        env: &Env<'_>,
+    ) -> Result<Vec<Val>, EvalError> {
+        // Iterating over a command goes over the lines it outputs, rather than its exit status.
+        if let Expr::FunctionCall { .. } = self.exprs[iterable] {
+            if let Some(lines) = self.output_lines(iterable, env)? {
+                return Ok(lines);
+            }
+        }
+
+        match self.eval_expr(iterable, env)? {
+            Val::List(elements) => Ok(elements),
+            val => Err(EvalError::new(
+                EvalErrorKind::NonIterable { ty: val.ty() },
+                self.source_map[iterable],
+            )),
         }
     }
-}
 
-impl BinOp {
-    fn eval(&self, env: &Env<'_>) -> Result<Val, EvalError> {
-        let op = self.op().unwrap().as_op().unwrap();
+    fn eval_call(self: &Rc<Self>, call: ExprIdx, env: &Env<'_>) -> Result<Val, EvalError> {
+        // Checking here means that even an infinitely recursive lambda can be interrupted.
+        check_interrupted(env, self.source_map.call_name(call))?;
 
-        let lhs = self.lhs().unwrap().eval(env)?;
-        let rhs = self.rhs().unwrap().eval(env)?;
+        let Callee {
+            func_or_command,
+            alias_args,
+        } = self.resolve_call(call, env)?;
 
-        match (lhs, rhs) {
-            (Val::Number(lhs), Val::Number(rhs)) => {
-                let result = match op {
-                    Op::Add => lhs + rhs,
-                    Op::Sub => lhs - rhs,
-                    Op::Mul => lhs * rhs,
-                    Op::Div => lhs / rhs,
-                };
+        match func_or_command {
+            FuncOrCommand::Func(lambda) => {
+                let params_range = self.source_map.call_params(call);
+                let params = self.eval_params(call, &alias_args, env)?;
+                lambda.eval(self.source_map[call], params_range, params.into_iter(), env)
+            }
+            FuncOrCommand::Builtin(builtin) => {
+                let params_range = self.source_map.call_params(call);
+                builtin.eval(self.eval_params(call, &alias_args, env)?, params_range, env)
+            }
+            FuncOrCommand::Command(path) => {
+                let (status, _) =
+                    self.run_command(call, path, &alias_args, Stdio::inherit(), env)?;
+                Ok(Val::ExitStatus(exit_code(status)))
+            }
+        }
+    }
+
+    /// Runs the command being called and returns the lines it prints, or `None` if this is a call
+    /// to a lambda or builtin.
+    fn output_lines(
+        self: &Rc<Self>,
+        call: ExprIdx,
+        env: &Env<'_>,
+    ) -> Result<Option<Vec<Val>>, EvalError> {
+        let Callee {
+            func_or_command,
+            alias_args,
+        } = self.resolve_call(call, env)?;
+
+        let path = match func_or_command {
+            FuncOrCommand::Command(path) => path,
+            FuncOrCommand::Func(_) | FuncOrCommand::Builtin(_) => return Ok(None),
+        };
+
+        let (_, output) = self.run_command(call, path, &alias_args, Stdio::piped(), env)?;
+
+        Ok(Some(output_lines(&output)))
+    }
+
+    fn run_command(
+        self: &Rc<Self>,
+        call: ExprIdx,
+        path: PathBuf,
+        alias_args: &[AliasArg],
+        stdout: Stdio,
+        env: &Env<'_>,
+    ) -> Result<(ExitStatus, String), EvalError> {
+        let command = command(path, self.command_args(call, alias_args, env)?, env);
+
+        run_command(
+            command,
+            stdout,
+            self.source_map.call_name(call),
+            self.source_map[call],
+            env,
+        )
+    }
 
-                Ok(Val::Number(result))
+    fn spawn(
+        self: &Rc<Self>,
+        call: ExprIdx,
+        job_command: &str,
+        env: &Env<'_>,
+    ) -> Result<Val, EvalError> {
+        let Callee {
+            func_or_command,
+            alias_args,
+        } = self.resolve_call(call, env)?;
+
+        let path = match func_or_command {
+            FuncOrCommand::Command(path) => path,
+            FuncOrCommand::Func(_) | FuncOrCommand::Builtin(_) => {
+                return Err(EvalError::new(
+                    EvalErrorKind::BackgroundNonCommand,
+                    self.source_map[call],
+                ));
             }
-            (lhs, rhs) => {
-                let error_kind = EvalErrorKind::BinOpOnNonNumbers {
-                    lhs_ty: lhs.ty(),
-                    rhs_ty: rhs.ty(),
-                };
+        };
+
+        let command = command(path, self.command_args(call, &alias_args, env)?, env);
+
+        spawn_background(
+            command,
+            job_command.to_string(),
+            self.source_map.call_name(call),
+            env,
+        )
+    }
+
+    fn resolve_call(&self, call: ExprIdx, env: &Env<'_>) -> Result<Callee, EvalError> {
+        env.get_func_or_command(&self.names[self.call_name(call)])
+            .map_err(|kind| EvalError::new(kind, self.source_map.call_name(call)))
+    }
+
+    fn call_name(&self, call: ExprIdx) -> Name {
+        match &self.exprs[call] {
+            Expr::FunctionCall { name, .. } => *name,
+            _ => unreachable!(),
+        }
+    }
+
+    fn call_params(&self, call: ExprIdx) -> &[ExprIdx] {
+        match &self.exprs[call] {
+            Expr::FunctionCall { params, .. } => params,
+            _ => unreachable!(),
+        }
+    }
+
+    fn eval_params(
+        self: &Rc<Self>,
+        call: ExprIdx,
+        alias_args: &[AliasArg],
+        env: &Env<'_>,
+    ) -> Result<Vec<Val>, EvalError> {
+        let mut params: Vec<_> = alias_args.iter().map(AliasArg::to_val).collect();
+
+        for param in self.call_params(call) {
+            params.push(self.eval_expr(*param, env)?);
+        }
+
+        Ok(params)
+    }
 
-                Err(EvalError::new(error_kind, self.text_range()))
+    fn command_args(
+        self: &Rc<Self>,
+        call: ExprIdx,
+        alias_args: &[AliasArg],
+        env: &Env<'_>,
+    ) -> Result<Vec<String>, EvalError> {
+        let mut args = Vec::new();
+        push_alias_args(alias_args, self.source_map.call_name(call), env, &mut args)?;
+
+        for param in self.call_params(call) {
+            let range = self.source_map[*param];
+
+            // Only unquoted arguments are expanded, so string literals are never treated as globs
+            // and never have their tildes replaced.
+            match &self.exprs[*param] {
+                Expr::Glob(pattern) => push_word_arg(pattern, range, env, &mut args)?,
+                Expr::Atom(text) => push_word_arg(text, range, env, &mut args)?,
+                _ => push_val_arg(self.eval_expr(*param, env)?, range, &mut args)?,
             }
         }
+
+        Ok(args)
+    }
+
+    // The arguments of the call an alias expands to are evaluated when the alias is defined,
+    // except for unquoted words, which are expanded each time the alias is used.
+    fn alias_args(
+        self: &Rc<Self>,
+        call: ExprIdx,
+        env: &Env<'_>,
+    ) -> Result<Vec<AliasArg>, EvalError> {
+        self.call_params(call)
+            .iter()
+            .map(|param| match &self.exprs[*param] {
+                Expr::Glob(pattern) => Ok(AliasArg::Word(pattern.clone())),
+                Expr::Atom(text) => Ok(AliasArg::Word(text.to_string())),
+                _ => Ok(AliasArg::Val(self.eval_expr(*param, env)?)),
+            })
+            .collect()
     }
 }
 
-impl If {
-    fn eval(&self, env: &Env<'_>) -> Result<Val, EvalError> {
-        let condition = self.condition().unwrap();
+// Shared by the tree-walker and the VM, so that they can’t disagree on what arithmetic means.
+fn arithmetic(op: Op, lhs: Val, rhs: Val, range: TextRange) -> Result<Val, EvalError> {
+    match (lhs, rhs) {
+        (Val::Number(_), Val::Number(0)) if op == Op::Div => {
+            Err(EvalError::new(EvalErrorKind::DivisionByZero, range))
+        }
+        (Val::Number(lhs), Val::Number(rhs)) => {
+            let result = match op {
+                Op::Add => lhs + rhs,
+                Op::Sub => lhs - rhs,
+                Op::Mul => lhs * rhs,
+                Op::Div => lhs / rhs,
+                Op::And | Op::Or => unreachable!(),
+            };
+
+            Ok(Val::Number(result))
+        }
+        (lhs, rhs) => {
+            let error_kind = EvalErrorKind::BinOpOnNonNumbers {
+                lhs_ty: lhs.ty(),
+                rhs_ty: rhs.ty(),
+            };
 
-        match condition.eval(env)? {
-            Val::Bool(true) => self.true_branch().unwrap().eval(env),
-            Val::Bool(false) => self.false_branch().unwrap().eval(env),
-            _ => Err(EvalError::new(
-                EvalErrorKind::NonBoolCond,
-                condition.text_range(),
-            )),
+            Err(EvalError::new(error_kind, range))
         }
     }
 }
 
-impl FunctionCall {
-    fn eval(&self, env: &Env<'_>) -> Result<Val, EvalError> {
-        let name = self.name().unwrap();
+// `break` and `continue` are evaluated as errors, which propagate up to the innermost loop and are
+// caught here. This returns whether the loop should carry on.
+fn loop_should_continue(iteration_result: Result<Val, EvalError>) -> Result<bool, EvalError> {
+    match iteration_result {
+        Ok(_) => Ok(true),
+        Err(error) => match error.kind() {
+            EvalErrorKind::Break => Ok(false),
+            EvalErrorKind::Continue => Ok(true),
+            _ => Err(error),
+        },
+    }
+}
 
-        let func_or_command = env
-            .get_func_or_command(name.text())
-            .map_err(|kind| EvalError::new(kind, name.text_range()))?;
+fn command(path: PathBuf, args: Vec<String>, env: &Env<'_>) -> Command {
+    let mut command = Command::new(path);
+    command.args(args).current_dir(env.working_dir());
 
-        let params: Result<Vec<_>, _> = self
-            .param_exprs()
-            .unwrap()
-            .map(|param| param.eval(env))
-            .collect();
+    command
+}
 
-        let params = params?;
+// Adds an unquoted word to a command’s arguments, expanding tildes and globs.
+fn push_word_arg(
+    word: &str,
+    range: TextRange,
+    env: &Env<'_>,
+    args: &mut Vec<String>,
+) -> Result<(), EvalError> {
+    // Only the part of the word after the home directory is a pattern, so that globs can’t be
+    // thrown off by a home directory containing characters such as ‘*’ and ‘[’.
+    let (home, rest) = expand::split_tilde(word, env);
+
+    if expand::is_glob(rest) {
+        let paths =
+            expand::expand_glob(&home, rest, env).map_err(|kind| EvalError::new(kind, range))?;
+        args.extend(paths);
+    } else {
+        args.push(format!("{}{}", home, rest));
+    }
 
-        match func_or_command {
-            FuncOrCommand::Func(lambda) => {
-                let params_range = self.params().unwrap().text_range();
-                lambda.eval(params_range, params.into_iter(), env)
-            }
-            FuncOrCommand::Command(path) => {
-                let mut displayed_params = Vec::with_capacity(params.len());
+    Ok(())
+}
 
-                for (param, range) in params
-                    .into_iter()
-                    .zip(self.param_exprs().unwrap().map(|expr| expr.text_range()))
-                {
-                    if let Some(display_repr) = param.display_repr() {
-                        displayed_params.push(display_repr);
-                    } else {
-                        return Err(EvalError::new(
-                            EvalErrorKind::UndisplayableCommandArg,
-                            range,
-                        ));
-                    }
-                }
+// Errors in an alias’s arguments are reported at the name of the alias where it is used, since that
+// is where the arguments are expanded.
+fn push_alias_args(
+    alias_args: &[AliasArg],
+    range: TextRange,
+    env: &Env<'_>,
+    args: &mut Vec<String>,
+) -> Result<(), EvalError> {
+    for alias_arg in alias_args {
+        match alias_arg {
+            AliasArg::Word(word) => push_word_arg(word, range, env, args)?,
+            AliasArg::Val(val) => push_val_arg(val.clone(), range, args)?,
+        }
+    }
 
-                Command::new(path)
-                    .args(displayed_params)
-                    .status()
-                    .map_err(|_| {
-                        EvalError::new(EvalErrorKind::FailedRunningCommand, name.text_range())
-                    })?;
+    Ok(())
+}
 
-                Ok(Val::Nil)
-            }
+// Lists are spread out into one argument per element, so that (for example) flags collected by a
+// rest parameter can be passed on to a command.
+fn push_val_arg(val: Val, range: TextRange, args: &mut Vec<String>) -> Result<(), EvalError> {
+    if let Val::List(elements) = val {
+        for element in elements {
+            push_val_arg(element, range, args)?;
+        }
+
+        return Ok(());
+    }
+
+    match val.display_repr() {
+        Some(display_repr) => {
+            args.push(display_repr);
+            Ok(())
+        }
+        None => Err(EvalError::new(
+            EvalErrorKind::UndisplayableCommandArg,
+            range,
+        )),
+    }
+}
+
+fn run_command(
+    mut command: Command,
+    stdout: Stdio,
+    name_range: TextRange,
+    call_range: TextRange,
+    env: &Env<'_>,
+) -> Result<(ExitStatus, String), EvalError> {
+    let failed_running_command =
+        |_| EvalError::new(EvalErrorKind::FailedRunningCommand, name_range);
+
+    let mut child = command
+        .stdout(stdout)
+        .spawn()
+        .map_err(failed_running_command)?;
+
+    let mut output = Vec::new();
+
+    let status = {
+        let _foreground = env.run_in_foreground(child.id());
+
+        // If stdout is being captured we have to read it before waiting, since otherwise the
+        // child could block forever on a full pipe.
+        if let Some(mut stdout) = child.stdout.take() {
+            stdout
+                .read_to_end(&mut output)
+                .map_err(failed_running_command)?;
+        }
+
+        child.wait().map_err(failed_running_command)?
+    };
+
+    check_interrupted(env, call_range)?;
+
+    Ok((status, String::from_utf8_lossy(&output).into_owned()))
+}
+
+fn spawn_background(
+    mut command: Command,
+    job_command: String,
+    name_range: TextRange,
+    env: &Env<'_>,
+) -> Result<Val, EvalError> {
+    // Background jobs don’t get to read from the terminal, since they would be competing with
+    // whatever is running in the foreground for input.
+    let child = command
+        .stdin(Stdio::null())
+        .spawn()
+        .map_err(|_| EvalError::new(EvalErrorKind::FailedRunningCommand, name_range))?;
+
+    let id = env.add_job(job_command, child);
+
+    Ok(Val::Job(id))
+}
+
+fn output_lines(output: &str) -> Vec<Val> {
+    output
+        .lines()
+        .map(|line| Val::Str(line.to_string()))
+        .collect()
+}
+
+fn exit_code(status: ExitStatus) -> i32 {
+    if let Some(code) = status.code() {
+        return code;
+    }
+
+    // On Unix a process that was killed by a signal has no exit code, so we follow the shell
+    // convention of reporting 128 plus the signal number.
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+
+        if let Some(signal) = status.signal() {
+            return 128 + signal;
         }
     }
+
+    -1
 }
 
 impl Lambda {
     fn eval(
         &self,
+        call_range: TextRange,
         call_params_range: TextRange,
         params: impl ExactSizeIterator<Item = Val>,
         env: &Env<'_>,
     ) -> Result<Val, EvalError> {
-        let mut new_env = env.create_child();
-
-        match params.len().cmp(&self.param_names().unwrap().count()) {
-            Ordering::Less => {
-                return Err(EvalError::new(
-                    EvalErrorKind::TooFewParams,
-                    call_params_range,
-                ));
-            }
-            Ordering::Greater => {
-                return Err(EvalError::new(
-                    EvalErrorKind::TooManyParams,
-                    call_params_range,
-                ));
-            }
-            Ordering::Equal => {}
-        }
+        let params = match apply(self, params, env) {
+            Application::Call(params) => params,
+            Application::Partial(lambda) => return Ok(Val::Lambda(lambda)),
+        };
 
-        for (param_name, param_val) in self.param_names().unwrap().zip(params) {
-            new_env.store_binding(param_name, param_val);
-        }
+        let mut new_env = env.create_lambda_env(self);
+        let names = &self.hir().names;
 
-        self.body().unwrap().eval(&new_env)
+        let lambda_params: Vec<_> = self
+            .params()
+            .iter()
+            .map(|param| (names[param.name].clone(), param.default))
+            .collect();
+        let rest_param = self
+            .rest_param()
+            .map(|rest_param| rest_param.map(|name| names[name].clone()));
+
+        bind_params(
+            &lambda_params,
+            rest_param,
+            params,
+            call_params_range,
+            &mut new_env,
+            |default, env| leave_lambda(self.hir().eval_expr(*default, env), env, call_range),
+        )?;
+
+        leave_lambda(
+            self.hir().eval_expr(self.body(), &new_env),
+            &new_env,
+            call_range,
+        )
     }
 }
 
-impl BindingUsage {
-    fn eval(&self, env: &Env<'_>) -> Result<Val, EvalError> {
-        let binding_name = self.binding_name().unwrap();
+// Loops outside of a lambda can’t be broken out of or continued from inside it. The ranges of
+// errors from a lambda defined in a module are in the module’s source, so errors that escape such
+// a lambda are reported at the call, mentioning the module they came from.
+fn leave_lambda(
+    result: Result<Val, EvalError>,
+    lambda_env: &Env<'_>,
+    call_range: TextRange,
+) -> Result<Val, EvalError> {
+    result
+        .map_err(EvalError::outside_loop)
+        .map_err(|error| match lambda_env.module_path() {
+            Some(path) if !error.is_interrupted() => EvalError::new(
+                EvalErrorKind::ErrorInModule {
+                    path: path.to_path_buf(),
+                    error: Box::new(error),
+                },
+                call_range,
+            ),
+            _ => error,
+        })
+}
 
-        env.get_binding(&binding_name)
-            .ok_or_else(|| EvalError::new(EvalErrorKind::BindingDoesNotExist, self.text_range()))
-    }
+enum Application {
+    Call(std::vec::IntoIter<Val>),
+    Partial(Lambda),
 }
 
-impl Block {
-    fn eval(&self, env: &Env<'_>) -> Result<Val, EvalError> {
-        let mut child_env = env.create_child();
-        eval_items(self.items().collect(), &mut child_env)
+// Puts the arguments a lambda has already been given in front of the ones it is being called
+// with. If partial application is turned on and there still aren’t enough arguments to call the
+// lambda, we hand back the lambda with all of them bound instead of calling it.
+fn apply(
+    lambda: &Lambda,
+    params: impl ExactSizeIterator<Item = Val>,
+    env: &Env<'_>,
+) -> Application {
+    let params: Vec<_> = lambda.bound_args().iter().cloned().chain(params).collect();
+
+    if env.partial_application() && params.len() < lambda.required_param_count() {
+        return Application::Partial(lambda.with_bound_args(params));
     }
+
+    Application::Call(params.into_iter())
 }
 
-impl Atom {
-    fn eval(&self) -> Val {
-        Val::Str(self.text().to_string())
+// Binds the arguments a lambda is called with to its parameters. Parameters without an argument
+// are bound to their default value, which is evaluated after the parameters before it have been
+// bound so that it can refer to them. Arguments beyond those that have a parameter of their own
+// are only allowed if the lambda has a rest parameter, which collects them into a list.
+fn bind_params<D>(
+    params: &[(SmolStr, Option<D>)],
+    rest_param: Option<Option<SmolStr>>,
+    mut args: impl ExactSizeIterator<Item = Val>,
+    call_params_range: TextRange,
+    env: &mut Env<'_>,
+    mut eval_default: impl FnMut(&D, &mut Env<'_>) -> Result<Val, EvalError>,
+) -> Result<(), EvalError> {
+    let required_param_count = params
+        .iter()
+        .rposition(|(_, default)| default.is_none())
+        .map_or(0, |idx| idx + 1);
+
+    if args.len() < required_param_count {
+        return Err(EvalError::new(
+            EvalErrorKind::TooFewParams,
+            call_params_range,
+        ));
     }
-}
 
-impl Digits {
-    fn eval(&self) -> Val {
-        Val::Number(self.text().parse().unwrap())
+    if args.len() > params.len() && rest_param.is_none() {
+        return Err(EvalError::new(
+            EvalErrorKind::TooManyParams,
+            call_params_range,
+        ));
     }
-}
 
-impl StringLiteral {
-    fn eval(&self) -> Val {
-        let text = self.text();
+    for (name, default) in params {
+        let val = match (args.next(), default) {
+            (Some(val), _) => val,
+            (None, Some(default)) => eval_default(default, env)?,
+            (None, None) => unreachable!(),
+        };
+
+        env.store_binding(name.clone(), val);
+    }
 
-        // Slice off quotes.
-        Val::Str(text[1..text.len() - 1].to_string())
+    if let Some(Some(rest_param)) = rest_param {
+        env.store_binding(rest_param, Val::List(args.collect()));
     }
+
+    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::ast::{self, Root};
+    use crate::env::GlobNoMatch;
     use crate::parser::expr::{parse_binding_usage, parse_expr, parse_if, parse_lambda};
-    use crate::parser::item::parse_binding_def;
     use crate::parser::Parser;
+    use crate::test_support::TempDir;
     use crate::val::Ty;
+    use crate::SyntaxNode;
+
+    // An expression parsed on its own (rather than as part of a whole program), and then lowered.
+    struct LoweredExpr {
+        hir: Rc<Hir>,
+        expr: ExprIdx,
+    }
+
+    impl LoweredExpr {
+        fn new(syntax_node: SyntaxNode) -> Self {
+            let expr = ast::Expr::cast(syntax_node.into()).unwrap();
+
+            let mut hir = Hir::default();
+            let expr = hir.lower_expr(expr);
+
+            Self {
+                hir: Rc::new(hir),
+                expr,
+            }
+        }
+
+        fn eval(&self, env: &Env<'_>) -> Result<Val, EvalError> {
+            self.hir.eval_expr(self.expr, env)
+        }
+
+        fn command_args(&self, env: &Env<'_>) -> Result<Vec<String>, EvalError> {
+            self.hir.command_args(self.expr, &[], env)
+        }
+
+        fn into_lambda(self) -> Lambda {
+            Lambda::new(self.hir, self.expr)
+        }
+    }
 
     #[test]
     fn evaluate_if_expr_with_true_condition() {
@@ -263,10 +835,10 @@ mod tests {
             parse_if(&mut p);
 
             let syntax_node = p.finish_and_get_syntax();
-            If::cast(syntax_node).unwrap()
+            LoweredExpr::new(syntax_node)
         };
 
-        let env = Env::new(Vec::new()).unwrap();
+        let env = Env::new(Vec::new());
 
         assert_eq!(if_.eval(&env), Ok(Val::Number(1)));
     }
@@ -278,10 +850,10 @@ mod tests {
             parse_if(&mut p);
 
             let syntax_node = p.finish_and_get_syntax();
-            If::cast(syntax_node).unwrap()
+            LoweredExpr::new(syntax_node)
         };
 
-        let env = Env::new(Vec::new()).unwrap();
+        let env = Env::new(Vec::new());
 
         assert_eq!(if_.eval(&env), Ok(Val::Str("Goodbye".to_string())));
     }
@@ -293,10 +865,10 @@ mod tests {
             parse_if(&mut p);
 
             let syntax_node = p.finish_and_get_syntax();
-            If::cast(syntax_node).unwrap()
+            LoweredExpr::new(syntax_node)
         };
 
-        let env = Env::new(Vec::new()).unwrap();
+        let env = Env::new(Vec::new());
 
         assert_eq!(
             if_.eval(&env),
@@ -313,9 +885,9 @@ mod tests {
         parse_binding_usage(&mut p);
 
         let syntax_node = p.finish_and_get_syntax();
-        let binding_usage = BindingUsage::cast(syntax_node).unwrap();
+        let binding_usage = LoweredExpr::new(syntax_node);
 
-        let env = Env::new(Vec::new()).unwrap();
+        let env = Env::new(Vec::new());
 
         assert_eq!(
             binding_usage.eval(&env),
@@ -332,9 +904,9 @@ mod tests {
         parse_binding_usage(&mut p);
 
         let syntax_node = p.finish_and_get_syntax();
-        let binding_usage = BindingUsage::cast(syntax_node).unwrap();
+        let binding_usage = LoweredExpr::new(syntax_node);
 
-        let mut env = Env::new(Vec::new()).unwrap();
+        let mut env = Env::new(Vec::new());
         env.store_binding("foo-bar".into(), Val::Number(5));
 
         assert_eq!(binding_usage.eval(&env), Ok(Val::Number(5)));
@@ -348,7 +920,7 @@ mod tests {
 
             let syntax_node = p.finish_and_get_syntax();
 
-            Lambda::cast(syntax_node).unwrap()
+            LoweredExpr::new(syntax_node).into_lambda()
         };
 
         let apply_a_to_b_lambda = {
@@ -357,14 +929,15 @@ mod tests {
 
             let syntax_node = p.finish_and_get_syntax();
 
-            Lambda::cast(syntax_node).unwrap()
+            LoweredExpr::new(syntax_node).into_lambda()
         };
 
-        let env = Env::new(Vec::new()).unwrap();
+        let env = Env::new(Vec::new());
 
         // Applying id lambda to "hello" gives "hello".
         assert_eq!(
             apply_a_to_b_lambda.eval(
+                TextRange::default(),
                 TextRange::default(),
                 vec![Val::Lambda(id_lambda), Val::Str("hello".to_string())].into_iter(),
                 &env,
@@ -381,16 +954,17 @@ mod tests {
 
             let syntax_node = p.finish_and_get_syntax();
 
-            Lambda::cast(syntax_node).unwrap()
+            LoweredExpr::new(syntax_node).into_lambda()
         };
 
-        let env = Env::new(Vec::new()).unwrap();
+        let env = Env::new(Vec::new());
 
         // Dummy value.
         let call_range = TextRange::new(0.into(), 10.into());
 
         assert_eq!(
             id_lambda.eval(
+                call_range,
                 call_range,
                 vec![Val::Number(5), Val::Str("test".to_string())].into_iter(),
                 &env,
@@ -407,16 +981,17 @@ mod tests {
 
             let syntax_node = p.finish_and_get_syntax();
 
-            Lambda::cast(syntax_node).unwrap()
+            LoweredExpr::new(syntax_node).into_lambda()
         };
 
-        let env = Env::new(Vec::new()).unwrap();
+        let env = Env::new(Vec::new());
 
         // Dummy value.
         let call_range = TextRange::new(0.into(), 10.into());
 
         assert_eq!(
             ls_two_dirs_lambda.eval(
+                call_range,
                 call_range,
                 vec![Val::Str("~/Documents".to_string())].into_iter(),
                 &env,
@@ -426,49 +1001,195 @@ mod tests {
     }
 
     #[test]
-    fn evaluate_lambda_with_atom_param() {
-        let mut env = Env::new(Vec::new()).unwrap();
-
-        let id_lambda = {
-            let mut p = Parser::new("|x| $x");
-            parse_lambda(&mut p);
-
-            let syntax_node = p.finish_and_get_syntax();
-
-            Lambda::cast(syntax_node).unwrap()
-        };
+    fn collect_extra_params_into_rest_param() {
+        let mut env = Env::new(Vec::new());
 
-        env.store_binding("id".into(), Val::Lambda(id_lambda));
+        assert_eq!(
+            eval_root("let f = |first ...rest| [$first $rest]\nf 1 2 3", &mut env),
+            Ok(Val::List(vec![
+                Val::Number(1),
+                Val::List(vec![Val::Number(2), Val::Number(3)]),
+            ])),
+        );
+        assert_eq!(
+            eval_root("f 1", &mut env),
+            Ok(Val::List(vec![Val::Number(1), Val::List(Vec::new())])),
+        );
+        assert_eq!(
+            eval_root("f", &mut env),
+            Err(EvalError::new(
+                EvalErrorKind::TooFewParams,
+                TextRange::new(1.into(), 1.into()),
+            )),
+        );
+    }
 
-        let call_id_with_atom = {
-            let mut p = Parser::new("id this-is-an-atom");
-            parse_expr(&mut p);
+    #[test]
+    fn discard_extra_params_with_unnamed_rest_param() {
+        let mut env = Env::new(Vec::new());
 
-            let syntax_node = p.finish_and_get_syntax();
+        assert_eq!(
+            eval_root("let first = |x ...| $x\nfirst 1 2 3", &mut env),
+            Ok(Val::Number(1)),
+        );
+    }
 
-            FunctionCall::cast(syntax_node).unwrap()
-        };
+    #[test]
+    fn fill_in_missing_params_with_defaults() {
+        let mut env = Env::new(Vec::new());
 
         assert_eq!(
-            call_id_with_atom.eval(&env),
-            Ok(Val::Str("this-is-an-atom".to_string())),
+            eval_root(r#"let f = |a b=2 c="c"| [$a $b $c]"#, &mut env),
+            Ok(Val::Nil),
+        );
+        assert_eq!(
+            eval_root("f 1", &mut env),
+            Ok(Val::List(vec![
+                Val::Number(1),
+                Val::Number(2),
+                Val::Str("c".to_string()),
+            ])),
+        );
+        assert_eq!(
+            eval_root("f 1 3", &mut env),
+            Ok(Val::List(vec![
+                Val::Number(1),
+                Val::Number(3),
+                Val::Str("c".to_string()),
+            ])),
+        );
+        assert_eq!(
+            eval_root("f", &mut env),
+            Err(EvalError::new(
+                EvalErrorKind::TooFewParams,
+                TextRange::new(1.into(), 1.into()),
+            )),
+        );
+        assert_eq!(
+            eval_root("f 1 2 3 4", &mut env),
+            Err(EvalError::new(
+                EvalErrorKind::TooManyParams,
+                TextRange::new(2.into(), 9.into()),
+            )),
         );
     }
 
     #[test]
-    fn call_lambda_with_several_params() {
-        let mut env = Env::new(Vec::new()).unwrap();
+    fn evaluate_default_that_refers_to_earlier_param() {
+        let mut env = Env::new(Vec::new());
 
-        let return_first_lambda = {
-            let mut p = Parser::new("|a b| $a");
-            parse_lambda(&mut p);
+        assert_eq!(
+            eval_root(
+                "let f = |x y=($x * 2)| $x + $y
+[(f 1) (f 1 1)]",
+                &mut env
+            ),
+            Ok(Val::List(vec![Val::Number(3), Val::Number(2)])),
+        );
+    }
 
-            let syntax_node = p.finish_and_get_syntax();
+    #[test]
+    fn return_partially_applied_lambda_when_enabled() {
+        let mut env = Env::new(Vec::new());
+        env.set_partial_application(true);
 
-            Lambda::cast(syntax_node).unwrap()
-        };
+        assert_eq!(
+            eval_root(
+                "let add = |x y z=0| $x + $y + $z
+let inc = add 1
+[(inc 5) (inc 5 10) (add 1 2)]",
+                &mut env,
+            ),
+            Ok(Val::List(vec![
+                Val::Number(6),
+                Val::Number(16),
+                Val::Number(3),
+            ])),
+        );
+        assert!(matches!(env.binding("inc"), Some(Val::Lambda(_))));
+    }
 
-        env.store_binding("return-first".into(), Val::Lambda(return_first_lambda));
+    #[test]
+    fn combine_bound_args_with_rest_param() {
+        let mut env = Env::new(Vec::new());
+        env.set_partial_application(true);
+
+        assert_eq!(
+            eval_root(
+                "let f = |a b ...rest| [$a $b $rest]
+let g = f 1
+g 2 3 4",
+                &mut env
+            ),
+            Ok(Val::List(vec![
+                Val::Number(1),
+                Val::Number(2),
+                Val::List(vec![Val::Number(3), Val::Number(4)]),
+            ])),
+        );
+    }
+
+    #[test]
+    fn fail_with_too_few_params_when_partial_application_is_disabled() {
+        let mut env = Env::new(Vec::new());
+
+        assert_eq!(
+            eval_root(
+                "let add = |x y| $x + $y
+add 1",
+                &mut env
+            ),
+            Err(EvalError::new(
+                EvalErrorKind::TooFewParams,
+                TextRange::new(28.into(), 29.into()),
+            )),
+        );
+    }
+
+    #[test]
+    fn evaluate_lambda_with_atom_param() {
+        let mut env = Env::new(Vec::new());
+
+        let id_lambda = {
+            let mut p = Parser::new("|x| $x");
+            parse_lambda(&mut p);
+
+            let syntax_node = p.finish_and_get_syntax();
+
+            LoweredExpr::new(syntax_node).into_lambda()
+        };
+
+        env.store_binding("id".into(), Val::Lambda(id_lambda));
+
+        let call_id_with_atom = {
+            let mut p = Parser::new("id this-is-an-atom");
+            parse_expr(&mut p);
+
+            let syntax_node = p.finish_and_get_syntax();
+
+            LoweredExpr::new(syntax_node)
+        };
+
+        assert_eq!(
+            call_id_with_atom.eval(&env),
+            Ok(Val::Str("this-is-an-atom".to_string())),
+        );
+    }
+
+    #[test]
+    fn call_lambda_with_several_params() {
+        let mut env = Env::new(Vec::new());
+
+        let return_first_lambda = {
+            let mut p = Parser::new("|a b| $a");
+            parse_lambda(&mut p);
+
+            let syntax_node = p.finish_and_get_syntax();
+
+            LoweredExpr::new(syntax_node).into_lambda()
+        };
+
+        env.store_binding("return-first".into(), Val::Lambda(return_first_lambda));
 
         let return_first_application = {
             let mut p = Parser::new("return-first 5 10");
@@ -476,7 +1197,7 @@ mod tests {
 
             let syntax_node = p.finish_and_get_syntax();
 
-            FunctionCall::cast(syntax_node).unwrap()
+            LoweredExpr::new(syntax_node)
         };
 
         assert_eq!(return_first_application.eval(&env), Ok(Val::Number(5)));
@@ -484,7 +1205,7 @@ mod tests {
 
     #[test]
     fn call_lambda_without_any_params() {
-        let mut env = Env::new(Vec::new()).unwrap();
+        let mut env = Env::new(Vec::new());
 
         let always_return_100_lambda = {
             let mut p = Parser::new("|| 100");
@@ -492,7 +1213,7 @@ mod tests {
 
             let syntax_node = p.finish_and_get_syntax();
 
-            Lambda::cast(syntax_node).unwrap()
+            LoweredExpr::new(syntax_node).into_lambda()
         };
 
         env.store_binding(
@@ -506,7 +1227,7 @@ mod tests {
 
             let syntax_node = p.finish_and_get_syntax();
 
-            FunctionCall::cast(syntax_node).unwrap()
+            LoweredExpr::new(syntax_node)
         };
 
         assert_eq!(
@@ -517,7 +1238,7 @@ mod tests {
 
     #[test]
     fn call_non_lambda() {
-        let mut env = Env::new(Vec::new()).unwrap();
+        let mut env = Env::new(Vec::new());
         env.store_binding("foo".into(), Val::Number(100));
 
         let call = {
@@ -526,7 +1247,7 @@ mod tests {
 
             let syntax_node = p.finish_and_get_syntax();
 
-            FunctionCall::cast(syntax_node).unwrap()
+            LoweredExpr::new(syntax_node)
         };
 
         assert_eq!(
@@ -540,7 +1261,7 @@ mod tests {
 
     #[test]
     fn call_non_existent_func_or_command() {
-        let env = Env::new(Vec::new()).unwrap();
+        let env = Env::new(Vec::new());
 
         let call = {
             let mut p = Parser::new("non-existent 1 2 3");
@@ -548,7 +1269,7 @@ mod tests {
 
             let syntax_node = p.finish_and_get_syntax();
 
-            FunctionCall::cast(syntax_node).unwrap()
+            LoweredExpr::new(syntax_node)
         };
 
         assert_eq!(
@@ -560,25 +1281,307 @@ mod tests {
         );
     }
 
+    fn env_in_temp_dir(name: &str) -> (Env<'static>, TempDir) {
+        let dir = TempDir::new(&format!("eval-{}", name));
+
+        let mut env = Env::new(Vec::new());
+        env.set_working_dir(dir.to_path_buf());
+
+        (env, dir)
+    }
+
+    #[cfg(unix)]
     #[test]
-    fn evaluate_binding_def() {
-        let binding_def = {
-            let mut p = Parser::new("let a = 5");
-            parse_binding_def(&mut p);
+    fn call_command_by_relative_path() {
+        let (env, dir) = env_in_temp_dir("relative-path");
+        dir.create_executable("build.sh");
+
+        let call = {
+            let mut p = Parser::new("./build.sh");
+            parse_expr(&mut p);
+
+            let syntax_node = p.finish_and_get_syntax();
+
+            LoweredExpr::new(syntax_node)
+        };
+
+        assert_eq!(call.eval(&env), Ok(Val::ExitStatus(0)));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn call_non_executable_file_by_path() {
+        let (env, dir) = env_in_temp_dir("non-executable-path");
+        std::fs::write(dir.join("notes.txt"), "").unwrap();
+
+        let call = {
+            let mut p = Parser::new("./notes.txt");
+            parse_expr(&mut p);
+
+            let syntax_node = p.finish_and_get_syntax();
+
+            LoweredExpr::new(syntax_node)
+        };
+
+        assert_eq!(
+            call.eval(&env),
+            Err(EvalError::new(
+                EvalErrorKind::NotExecutable,
+                TextRange::new(0.into(), 11.into()),
+            )),
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn call_non_existent_file_by_path() {
+        let (env, _dir) = env_in_temp_dir("non-existent-path");
+
+        let call = {
+            let mut p = Parser::new("./configure");
+            parse_expr(&mut p);
 
             let syntax_node = p.finish_and_get_syntax();
 
-            BindingDef::cast(syntax_node).unwrap()
+            LoweredExpr::new(syntax_node)
+        };
+
+        assert_eq!(
+            call.eval(&env),
+            Err(EvalError::new(
+                EvalErrorKind::FuncOrCommandDoesNotExist,
+                TextRange::new(0.into(), 11.into()),
+            )),
+        );
+    }
+
+    fn command_args(input: &str, env: &Env<'_>) -> Result<Vec<String>, EvalError> {
+        let mut p = Parser::new(input);
+        parse_expr(&mut p);
+
+        let syntax_node = p.finish_and_get_syntax();
+
+        LoweredExpr::new(syntax_node).command_args(env)
+    }
+
+    #[test]
+    fn expand_globs_in_command_args() {
+        let (env, dir) = env_in_temp_dir("glob");
+        std::fs::write(dir.join("b.txt"), "").unwrap();
+        std::fs::write(dir.join("a.txt"), "").unwrap();
+        std::fs::write(dir.join("c.rs"), "").unwrap();
+        std::fs::write(dir.join(".hidden.txt"), "").unwrap();
+
+        assert_eq!(
+            command_args("ls *.txt c?rs [ab].txt", &env),
+            Ok(vec![
+                "a.txt".to_string(),
+                "b.txt".to_string(),
+                "c.rs".to_string(),
+                "a.txt".to_string(),
+                "b.txt".to_string(),
+            ]),
+        );
+    }
+
+    #[test]
+    fn expand_recursive_glob_in_command_args() {
+        let (env, dir) = env_in_temp_dir("recursive-glob");
+        std::fs::create_dir_all(dir.join("src").join("parser")).unwrap();
+        std::fs::write(dir.join("src").join("lib.rs"), "").unwrap();
+        std::fs::write(dir.join("src").join("parser").join("expr.rs"), "").unwrap();
+
+        assert_eq!(
+            command_args("wc src/**/*.rs", &env),
+            Ok(vec![
+                std::path::Path::new("src")
+                    .join("lib.rs")
+                    .to_string_lossy()
+                    .into_owned(),
+                std::path::Path::new("src")
+                    .join("parser")
+                    .join("expr.rs")
+                    .to_string_lossy()
+                    .into_owned(),
+            ]),
+        );
+    }
+
+    #[test]
+    fn do_not_expand_globs_in_string_literals() {
+        let (env, dir) = env_in_temp_dir("quoted-glob");
+        std::fs::write(dir.join("a.txt"), "").unwrap();
+
+        assert_eq!(
+            command_args(r#"echo "*.txt""#, &env),
+            Ok(vec!["*.txt".to_string()]),
+        );
+    }
+
+    #[test]
+    fn multiply_numbers_passed_to_lambdas() {
+        let mut env = Env::new(Vec::new());
+
+        assert_eq!(
+            eval_root("let f = |a| $a\nf 2*3", &mut env),
+            Ok(Val::Number(6)),
+        );
+    }
+
+    #[test]
+    fn pass_through_glob_without_matches_by_default() {
+        let (env, _dir) = env_in_temp_dir("glob-pass-through");
+
+        assert_eq!(command_args("ls *.md", &env), Ok(vec!["*.md".to_string()]),);
+    }
+
+    #[test]
+    fn remove_glob_without_matches() {
+        let (mut env, _dir) = env_in_temp_dir("glob-remove");
+        env.set_glob_no_match(GlobNoMatch::Remove);
+
+        assert_eq!(command_args("ls *.md", &env), Ok(Vec::new()));
+    }
+
+    #[test]
+    fn spread_lists_in_command_args() {
+        let mut env = Env::new(Vec::new());
+        env.set_binding(
+            "flags",
+            Val::List(vec![Val::Str("-l".to_string()), Val::Str("-a".to_string())]),
+        );
+
+        assert_eq!(
+            command_args("ls $flags src", &env),
+            Ok(vec!["-l".to_string(), "-a".to_string(), "src".to_string()]),
+        );
+    }
+
+    #[test]
+    fn pass_contextual_keywords_to_commands_as_words() {
+        let env = Env::new(Vec::new());
+
+        assert_eq!(
+            command_args("echo for in while match break continue source alias", &env),
+            Ok(vec![
+                "for".to_string(),
+                "in".to_string(),
+                "while".to_string(),
+                "match".to_string(),
+                "break".to_string(),
+                "continue".to_string(),
+                "source".to_string(),
+                "alias".to_string(),
+            ]),
+        );
+    }
+
+    #[test]
+    fn error_on_glob_without_matches() {
+        let (mut env, _dir) = env_in_temp_dir("glob-error");
+        env.set_glob_no_match(GlobNoMatch::Error);
+
+        assert_eq!(
+            command_args("ls a *.md", &env),
+            Err(EvalError::new(
+                EvalErrorKind::NoGlobMatches,
+                TextRange::new(5.into(), 9.into()),
+            )),
+        );
+    }
+
+    #[test]
+    fn expand_tilde_in_command_args() {
+        let mut env = Env::new(Vec::new());
+        env.store_binding("HOME".into(), Val::Str("/home/fjord".to_string()));
+
+        assert_eq!(
+            command_args("ls ~ ~/projects a~b", &env),
+            Ok(vec![
+                "/home/fjord".to_string(),
+                "/home/fjord/projects".to_string(),
+                "a~b".to_string(),
+            ]),
+        );
+    }
+
+    #[test]
+    fn do_not_expand_tilde_in_string_literals() {
+        let mut env = Env::new(Vec::new());
+        env.store_binding("HOME".into(), Val::Str("/home/fjord".to_string()));
+
+        assert_eq!(
+            command_args(r#"ls "~" "~/projects""#, &env),
+            Ok(vec!["~".to_string(), "~/projects".to_string()]),
+        );
+    }
+
+    #[test]
+    fn leave_tilde_untouched_without_home() {
+        let env = Env::new(Vec::new());
+
+        assert_eq!(
+            command_args("ls ~/projects", &env),
+            Ok(vec!["~/projects".to_string()]),
+        );
+    }
+
+    #[test]
+    fn leave_tilde_of_non_existent_user_untouched() {
+        let env = Env::new(Vec::new());
+
+        assert_eq!(
+            command_args("ls ~no-such-fjord-user/bin", &env),
+            Ok(vec!["~no-such-fjord-user/bin".to_string()]),
+        );
+    }
+
+    #[test]
+    fn expand_tilde_before_globbing() {
+        let (mut env, dir) = env_in_temp_dir("tilde-glob");
+        std::fs::write(dir.join("a.txt"), "").unwrap();
+        env.store_binding("HOME".into(), Val::Str(dir.to_string_lossy().into_owned()));
+
+        assert_eq!(
+            command_args("ls ~/*.txt", &env),
+            Ok(vec![dir.join("a.txt").to_string_lossy().into_owned()]),
+        );
+    }
+
+    #[test]
+    fn match_home_dir_literally_when_globbing() {
+        let (mut env, dir) = env_in_temp_dir("tilde-glob-home");
+        let home = dir.join("[h]o*me?");
+        std::fs::create_dir(&home).unwrap();
+        std::fs::write(home.join("a.txt"), "").unwrap();
+        env.store_binding("HOME".into(), Val::Str(home.to_string_lossy().into_owned()));
+
+        assert_eq!(
+            command_args("ls ~/*.txt ~/*.md", &env),
+            Ok(vec![
+                home.join("a.txt").to_string_lossy().into_owned(),
+                home.join("*.md").to_string_lossy().into_owned(),
+            ]),
+        );
+    }
+
+    #[test]
+    fn evaluate_binding_def() {
+        let binding_def = {
+            let p = Parser::new("let a = 5");
+            let syntax_node = p.parse().syntax();
+
+            Rc::new(Hir::lower(&Root::cast(syntax_node).unwrap()))
         };
 
         assert_eq!(
             {
-                let mut env = Env::new(Vec::new()).unwrap();
+                let mut env = Env::new(Vec::new());
                 binding_def.eval(&mut env).unwrap();
                 env
             },
             {
-                let mut env = Env::new(Vec::new()).unwrap();
+                let mut env = Env::new(Vec::new());
                 env.store_binding("a".into(), Val::Number(5));
                 env
             },
@@ -591,10 +1594,10 @@ mod tests {
             let p = Parser::new("");
             let syntax_node = p.parse().syntax();
 
-            Root::cast(syntax_node).unwrap()
+            Rc::new(Hir::lower(&Root::cast(syntax_node).unwrap()))
         };
 
-        let mut env = Env::new(Vec::new()).unwrap();
+        let mut env = Env::new(Vec::new());
 
         assert_eq!(root.eval(&mut env), Ok(Val::Nil));
     }
@@ -605,10 +1608,10 @@ mod tests {
             let p = Parser::new(r#""hello""#);
             let syntax_node = p.parse().syntax();
 
-            Root::cast(syntax_node).unwrap()
+            Rc::new(Hir::lower(&Root::cast(syntax_node).unwrap()))
         };
 
-        let mut env = Env::new(Vec::new()).unwrap();
+        let mut env = Env::new(Vec::new());
 
         assert_eq!(root.eval(&mut env), Ok(Val::Str("hello".to_string())));
     }
@@ -619,10 +1622,10 @@ mod tests {
             let p = Parser::new("let x = 1");
             let syntax_node = p.parse().syntax();
 
-            Root::cast(syntax_node).unwrap()
+            Rc::new(Hir::lower(&Root::cast(syntax_node).unwrap()))
         };
 
-        let mut env = Env::new(Vec::new()).unwrap();
+        let mut env = Env::new(Vec::new());
 
         assert_eq!(root.eval(&mut env), Ok(Val::Nil));
     }
@@ -639,11 +1642,920 @@ mod tests {
             );
             let syntax_node = p.parse().syntax();
 
-            Root::cast(syntax_node).unwrap()
+            Rc::new(Hir::lower(&Root::cast(syntax_node).unwrap()))
         };
 
-        let mut env = Env::new(Vec::new()).unwrap();
+        let mut env = Env::new(Vec::new());
 
         assert_eq!(root.eval(&mut env), Ok(Val::Number(2)));
     }
+
+    fn eval_root(input: &str, env: &mut Env<'_>) -> Result<Val, EvalError> {
+        let root = Root::cast(Parser::new(input).parse().syntax()).unwrap();
+        Rc::new(Hir::lower(&root)).eval(env)
+    }
+
+    #[test]
+    fn and_evaluates_rhs_if_lhs_is_true() {
+        let mut env = Env::new(Vec::new());
+        assert_eq!(eval_root("true && 5", &mut env), Ok(Val::Number(5)));
+    }
+
+    #[test]
+    fn and_does_not_evaluate_rhs_if_lhs_is_false() {
+        let mut env = Env::new(Vec::new());
+        assert_eq!(
+            eval_root("false && $does-not-exist", &mut env),
+            Ok(Val::Bool(false)),
+        );
+    }
+
+    #[test]
+    fn or_evaluates_rhs_if_lhs_is_false() {
+        let mut env = Env::new(Vec::new());
+        assert_eq!(eval_root("false || 5", &mut env), Ok(Val::Number(5)));
+    }
+
+    #[test]
+    fn or_does_not_evaluate_rhs_if_lhs_is_true() {
+        let mut env = Env::new(Vec::new());
+        assert_eq!(
+            eval_root("true || $does-not-exist", &mut env),
+            Ok(Val::Bool(true)),
+        );
+    }
+
+    #[test]
+    fn logical_op_on_non_bool() {
+        let mut env = Env::new(Vec::new());
+        assert_eq!(
+            eval_root("5 && true", &mut env),
+            Err(EvalError::new(
+                EvalErrorKind::LogicalOpOnNonBool { ty: Ty::Number },
+                TextRange::new(0.into(), 1.into()),
+            )),
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn command_evaluates_to_exit_status() {
+        let mut env = Env::new(Vec::new());
+        assert_eq!(
+            eval_root(r#"/bin/sh -c "exit 3""#, &mut env),
+            Ok(Val::ExitStatus(3)),
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn chain_commands_based_on_exit_status() {
+        let mut env = Env::new(Vec::new());
+
+        assert_eq!(
+            eval_root(r#"/bin/sh -c "exit 0" && 1"#, &mut env),
+            Ok(Val::Number(1)),
+        );
+        assert_eq!(
+            eval_root(r#"/bin/sh -c "exit 1" && 1"#, &mut env),
+            Ok(Val::ExitStatus(1)),
+        );
+        // Without the parentheses `|| 2` would be a lambda passed to the command.
+        assert_eq!(
+            eval_root(r#"(/bin/sh -c "exit 1") || 2"#, &mut env),
+            Ok(Val::Number(2)),
+        );
+    }
+
+    #[test]
+    fn pass_lambda_without_params_written_as_double_pipe() {
+        let mut env = Env::new(Vec::new());
+        assert_eq!(
+            eval_root("let call = |f| f\ncall || 100", &mut env),
+            Ok(Val::Number(100)),
+        );
+    }
+
+    #[test]
+    fn evaluate_items_separated_by_semicolons() {
+        let mut env = Env::new(Vec::new());
+        assert_eq!(
+            eval_root("let a = 1; let b = 2; $a + $b", &mut env),
+            Ok(Val::Number(3)),
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn run_command_in_background_and_wait_for_it() {
+        let mut env = Env::new(Vec::new());
+
+        assert_eq!(
+            eval_root(r#"let job = /bin/sh -c "exit 2" &"#, &mut env),
+            Ok(Val::Nil),
+        );
+        assert_eq!(eval_root("$job", &mut env), Ok(Val::Job(1)));
+        assert_eq!(eval_root("wait $job", &mut env), Ok(Val::ExitStatus(2)));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn list_jobs() {
+        let mut env = Env::new(Vec::new());
+        eval_root("/bin/sleep 5 &", &mut env).unwrap();
+
+        let mut job = BTreeMap::new();
+        job.insert(SmolStr::new("job"), Val::Job(1));
+        job.insert(
+            SmolStr::new("command"),
+            Val::Str("/bin/sleep 5".to_string()),
+        );
+        job.insert(SmolStr::new("status"), Val::Str("running".to_string()));
+
+        assert_eq!(
+            eval_root("jobs", &mut env),
+            Ok(Val::List(vec![Val::Record(job)]))
+        );
+
+        // SAFETY: kill has no memory safety requirements.
+        unsafe {
+            libc::kill(env.job_pid(1).unwrap() as libc::pid_t, libc::SIGKILL);
+        }
+
+        assert_eq!(eval_root("wait", &mut env), Ok(Val::ExitStatus(137)));
+        assert_eq!(eval_root("jobs", &mut env), Ok(Val::List(Vec::new())));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn wait_for_all_jobs() {
+        let mut env = Env::new(Vec::new());
+
+        assert_eq!(
+            eval_root(
+                r#"/bin/sh -c "exit 1" &; /bin/sh -c "exit 0" &; wait"#,
+                &mut env,
+            ),
+            Ok(Val::ExitStatus(1)),
+        );
+        assert_eq!(env.most_recent_job(), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn bring_job_to_foreground() {
+        let mut env = Env::new(Vec::new());
+
+        assert_eq!(
+            eval_root(r#"/bin/sh -c "exit 4" &; fg"#, &mut env),
+            Ok(Val::ExitStatus(4)),
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn reap_finished_jobs() {
+        let mut env = Env::new(Vec::new());
+        eval_root("/bin/sh -c true &", &mut env).unwrap();
+
+        let finished = loop {
+            let finished = env.reap_finished_jobs();
+
+            if !finished.is_empty() {
+                break finished;
+            }
+
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        };
+
+        assert_eq!(finished.len(), 1);
+        assert_eq!(finished[0].id(), 1);
+        assert_eq!(finished[0].command(), "/bin/sh -c true");
+        assert!(finished[0].exit_status().success());
+
+        // Jobs are only reported as finished once.
+        assert_eq!(env.reap_finished_jobs(), Vec::new());
+    }
+
+    #[test]
+    fn wait_for_non_existent_job() {
+        let mut env = Env::new(Vec::new());
+
+        assert_eq!(
+            eval_root("wait 5", &mut env),
+            Err(EvalError::new(
+                EvalErrorKind::NoSuchJob,
+                TextRange::new(5.into(), 6.into()),
+            )),
+        );
+    }
+
+    #[test]
+    fn run_non_command_in_background() {
+        let mut env = Env::new(Vec::new());
+
+        assert_eq!(
+            eval_root("let f = || 1\nf &", &mut env),
+            Err(EvalError::new(
+                EvalErrorKind::BackgroundNonCommand,
+                TextRange::new(13.into(), 14.into()),
+            )),
+        );
+    }
+
+    #[test]
+    fn stop_evaluating_items_once_cancelled() {
+        let mut env = Env::new(Vec::new());
+        env.cancellation_token().cancel();
+
+        assert_eq!(
+            eval_root("1\n2", &mut env),
+            Err(EvalError::new(
+                EvalErrorKind::Interrupted,
+                TextRange::new(0.into(), 1.into()),
+            )),
+        );
+
+        env.cancellation_token().reset();
+        assert_eq!(eval_root("1\n2", &mut env), Ok(Val::Number(2)));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn interrupt_foreground_command() {
+        let mut env = Env::new(Vec::new());
+        let token = env.cancellation_token();
+
+        let canceller = std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(100));
+            token.cancel();
+        });
+
+        let start = std::time::Instant::now();
+
+        assert_eq!(
+            eval_root("/bin/sleep 5", &mut env),
+            Err(EvalError::new(
+                EvalErrorKind::Interrupted,
+                TextRange::new(0.into(), 12.into()),
+            )),
+        );
+        assert!(start.elapsed() < std::time::Duration::from_secs(5));
+
+        canceller.join().unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn record_termination_signal_without_exiting() {
+        let env = Env::new(Vec::new());
+        let token = env.cancellation_token();
+        let (sender, receiver) = std::sync::mpsc::channel();
+
+        token
+            .install_signal_handlers(move |signal| {
+                let _ = sender.send(signal);
+            })
+            .unwrap();
+
+        // SAFETY: raise has no memory safety requirements.
+        unsafe {
+            libc::raise(libc::SIGTERM);
+        }
+
+        assert_eq!(
+            receiver.recv_timeout(std::time::Duration::from_secs(5)),
+            Ok(libc::SIGTERM),
+        );
+        assert!(token.is_cancelled());
+        assert_eq!(token.termination_signal(), Some(libc::SIGTERM));
+
+        token.reset();
+        assert_eq!(token.termination_signal(), Some(libc::SIGTERM));
+    }
+
+    #[test]
+    fn evaluate_list_and_record() {
+        let mut env = Env::new(Vec::new());
+
+        assert_eq!(
+            eval_root("[1 foo [a = true]]", &mut env),
+            Ok(Val::List(vec![
+                Val::Number(1),
+                Val::Str("foo".to_string()),
+                Val::Record(std::iter::once(("a".into(), Val::Bool(true))).collect()),
+            ])),
+        );
+    }
+
+    #[test]
+    fn match_literal() {
+        let mut env = Env::new(Vec::new());
+
+        assert_eq!(
+            eval_root(
+                "match 2 {\n  1 => \"one\"\n  2 => \"two\"\n  _ => \"many\"\n}",
+                &mut env,
+            ),
+            Ok(Val::Str("two".to_string())),
+        );
+    }
+
+    #[test]
+    fn match_type() {
+        let mut env = Env::new(Vec::new());
+
+        assert_eq!(
+            eval_root(r#"match "foo" { Number => 1; Str => 2 }"#, &mut env),
+            Ok(Val::Number(2)),
+        );
+    }
+
+    #[test]
+    fn match_wildcard() {
+        let mut env = Env::new(Vec::new());
+
+        assert_eq!(
+            eval_root("match true { false => 1; _ => 2 }", &mut env),
+            Ok(Val::Number(2)),
+        );
+    }
+
+    #[test]
+    fn destructure_list_with_rest() {
+        let mut env = Env::new(Vec::new());
+
+        assert_eq!(
+            eval_root(
+                "match [1 2 3] { [] => 0; [first ...rest] => [$first $rest] }",
+                &mut env,
+            ),
+            Ok(Val::List(vec![
+                Val::Number(1),
+                Val::List(vec![Val::Number(2), Val::Number(3)]),
+            ])),
+        );
+    }
+
+    #[test]
+    fn list_pattern_without_rest_requires_exact_length() {
+        let mut env = Env::new(Vec::new());
+
+        assert_eq!(
+            eval_root("match [1 2] { [a] => 1; [a b] => 2 }", &mut env),
+            Ok(Val::Number(2)),
+        );
+    }
+
+    #[test]
+    fn destructure_record() {
+        let mut env = Env::new(Vec::new());
+
+        assert_eq!(
+            eval_root(
+                r#"match [name = "fjord" version = 4] { [version = 3] => "old"; [name = n] => $n }"#,
+                &mut env,
+            ),
+            Ok(Val::Str("fjord".to_string())),
+        );
+    }
+
+    #[test]
+    fn bindings_from_patterns_are_scoped_to_their_arm() {
+        let mut env = Env::new(Vec::new());
+
+        assert_eq!(
+            eval_root("match 5 { x => $x }\n$x", &mut env),
+            Err(EvalError::new(
+                EvalErrorKind::BindingDoesNotExist,
+                TextRange::new(20.into(), 22.into()),
+            )),
+        );
+    }
+
+    #[test]
+    fn non_exhaustive_match() {
+        let mut env = Env::new(Vec::new());
+
+        assert_eq!(
+            eval_root("match (1 + 2) { 1 => true; Str => false }", &mut env),
+            Err(EvalError::new(
+                EvalErrorKind::NonExhaustiveMatch,
+                TextRange::new(7.into(), 12.into()),
+            )),
+        );
+    }
+
+    #[test]
+    fn match_type_that_does_not_exist() {
+        let mut env = Env::new(Vec::new());
+
+        assert_eq!(
+            eval_root("match 1 { Integer => true }", &mut env),
+            Err(EvalError::new(
+                EvalErrorKind::TyDoesNotExist,
+                TextRange::new(10.into(), 17.into()),
+            )),
+        );
+    }
+
+    #[test]
+    fn if_without_else_evaluates_to_nil_when_false() {
+        let mut env = Env::new(Vec::new());
+
+        assert_eq!(eval_root("if false then { 1 }", &mut env), Ok(Val::Nil));
+        assert_eq!(
+            eval_root("if true then { 1 }", &mut env),
+            Ok(Val::Number(1))
+        );
+    }
+
+    #[test]
+    fn evaluate_else_if_chain() {
+        let mut env = Env::new(Vec::new());
+
+        assert_eq!(
+            eval_root(
+                "if false then { 1 } else if true then { 2 } else { 3 }",
+                &mut env,
+            ),
+            Ok(Val::Number(2)),
+        );
+    }
+
+    #[test]
+    fn for_loop_iterates_over_every_element() {
+        let mut env = Env::new(Vec::new());
+
+        assert_eq!(
+            eval_root(
+                "let mut seen = 0\nfor x in [1 2 3] { seen = $seen * 10 + $x }\n$seen",
+                &mut env,
+            ),
+            Ok(Val::Number(123)),
+        );
+    }
+
+    #[test]
+    fn break_out_of_for_loop() {
+        let mut env = Env::new(Vec::new());
+
+        assert_eq!(
+            eval_root(r#"for x in [1 "a"] { break; $x + 1 }"#, &mut env),
+            Ok(Val::Nil),
+        );
+    }
+
+    #[test]
+    fn continue_for_loop() {
+        let mut env = Env::new(Vec::new());
+
+        assert_eq!(
+            eval_root("for x in [1 2] { continue; $does-not-exist }", &mut env),
+            Ok(Val::Nil),
+        );
+    }
+
+    #[test]
+    fn break_in_lambda_does_not_stop_callers_loop() {
+        let mut env = Env::new(Vec::new());
+
+        assert_eq!(
+            eval_root(
+                "let mut n = 0\nlet b = || { break }\nfor x in [1 2 3] { b; n = $n + 1 }",
+                &mut env,
+            ),
+            Err(EvalError::new(
+                EvalErrorKind::BreakOutsideLoop,
+                TextRange::new(27.into(), 32.into()),
+            )),
+        );
+        assert_eq!(env.binding("n"), Some(Val::Number(0)));
+    }
+
+    #[test]
+    fn continue_in_lambda_does_not_continue_callers_loop() {
+        let mut env = Env::new(Vec::new());
+
+        assert_eq!(
+            eval_root("let c = || { continue }\nwhile true { c }", &mut env,),
+            Err(EvalError::new(
+                EvalErrorKind::ContinueOutsideLoop,
+                TextRange::new(13.into(), 21.into()),
+            )),
+        );
+    }
+
+    #[test]
+    fn loops_inside_lambdas_can_be_broken_out_of() {
+        let mut env = Env::new(Vec::new());
+
+        assert_eq!(
+            eval_root(
+                "let f = || { for x in [1 2] { break }; 5 }\nfor y in [1 2] { f }",
+                &mut env,
+            ),
+            Ok(Val::Nil),
+        );
+    }
+
+    #[test]
+    fn for_loop_binding_is_scoped_to_loop() {
+        let mut env = Env::new(Vec::new());
+
+        assert_eq!(
+            eval_root("for x in [1] {}\n$x", &mut env),
+            Err(EvalError::new(
+                EvalErrorKind::BindingDoesNotExist,
+                TextRange::new(16.into(), 18.into()),
+            )),
+        );
+    }
+
+    #[test]
+    fn for_loop_over_non_list() {
+        let mut env = Env::new(Vec::new());
+
+        assert_eq!(
+            eval_root("for x in 5 {}", &mut env),
+            Err(EvalError::new(
+                EvalErrorKind::NonIterable { ty: Ty::Number },
+                TextRange::new(9.into(), 10.into()),
+            )),
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn for_loop_over_command_output_lines() {
+        let (mut env, dir) = env_in_temp_dir("for-command-output");
+
+        assert_eq!(
+            eval_root(
+                r#"for file in (/usr/bin/printf "a\nb\n") { /bin/touch $file }"#,
+                &mut env,
+            ),
+            Ok(Val::Nil),
+        );
+
+        assert!(dir.join("a").is_file());
+        assert!(dir.join("b").is_file());
+    }
+
+    #[test]
+    fn range() {
+        let mut env = Env::new(Vec::new());
+
+        assert_eq!(
+            eval_root("range 1 4", &mut env),
+            Ok(Val::List(vec![
+                Val::Number(1),
+                Val::Number(2),
+                Val::Number(3)
+            ])),
+        );
+        assert_eq!(
+            eval_root("range 2", &mut env),
+            Ok(Val::List(vec![Val::Number(0), Val::Number(1)])),
+        );
+        assert_eq!(
+            eval_root("range 1 a", &mut env),
+            Err(EvalError::new(
+                EvalErrorKind::NonNumberRangeBound { ty: Ty::Str },
+                TextRange::new(6.into(), 9.into()),
+            )),
+        );
+    }
+
+    #[test]
+    fn append_call_site_args_to_alias() {
+        let mut env = Env::new(Vec::new());
+
+        assert_eq!(
+            eval_root(
+                "let add = |x y| $x + $y\nalias inc = add 1\ninc 5",
+                &mut env
+            ),
+            Ok(Val::Number(6)),
+        );
+    }
+
+    #[test]
+    fn alias_expansion_is_not_itself_an_alias() {
+        let mut env = Env::new(Vec::new());
+
+        assert_eq!(
+            eval_root("alias range = range 1\nrange 3", &mut env),
+            Ok(Val::List(vec![Val::Number(1), Val::Number(2)])),
+        );
+    }
+
+    #[test]
+    fn aliases_are_scoped_to_blocks() {
+        let mut env = Env::new(Vec::new());
+
+        assert_eq!(
+            eval_root("{ alias r = range 1 }\nr 3", &mut env),
+            Err(EvalError::new(
+                EvalErrorKind::FuncOrCommandDoesNotExist,
+                TextRange::new(22.into(), 23.into()),
+            )),
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn expand_alias_args_where_alias_is_used() {
+        let (mut env, dir) = env_in_temp_dir("alias-command");
+
+        let status = eval_root(
+            "let name = \"a b\"\nalias t = /bin/touch $name *.txt\nt c.txt",
+            &mut env,
+        );
+
+        assert_eq!(status, Ok(Val::ExitStatus(0)));
+        assert!(dir.join("a b").is_file());
+        assert!(dir.join("c.txt").is_file());
+        // The glob had nothing to match when the alias was used, so it was passed through as-is.
+        assert!(dir.join("*.txt").is_file());
+    }
+
+    #[test]
+    fn while_loop() {
+        let mut env = Env::new(Vec::new());
+
+        assert_eq!(
+            eval_root("while false { $does-not-exist }", &mut env),
+            Ok(Val::Nil)
+        );
+        assert_eq!(eval_root("while true { break }", &mut env), Ok(Val::Nil));
+        assert_eq!(
+            eval_root("while 5 {}", &mut env),
+            Err(EvalError::new(
+                EvalErrorKind::NonBoolCond,
+                TextRange::new(6.into(), 7.into()),
+            )),
+        );
+    }
+
+    #[test]
+    fn break_outside_loop() {
+        let mut env = Env::new(Vec::new());
+
+        assert_eq!(
+            eval_root("1\nbreak", &mut env),
+            Err(EvalError::new(
+                EvalErrorKind::BreakOutsideLoop,
+                TextRange::new(2.into(), 7.into()),
+            )),
+        );
+    }
+
+    #[test]
+    fn reassign_mutable_binding_from_loop() {
+        let mut env = Env::new(Vec::new());
+
+        assert_eq!(
+            eval_root(
+                "let mut count = 0\nfor x in [1 2 3] { count = $count + $x }\n$count",
+                &mut env,
+            ),
+            Ok(Val::Number(6)),
+        );
+    }
+
+    #[test]
+    fn reassignment_updates_nearest_binding() {
+        let mut env = Env::new(Vec::new());
+
+        assert_eq!(
+            eval_root("let mut x = 1\n{ let mut x = 2; x = 3 }\n$x", &mut env,),
+            Ok(Val::Number(1)),
+        );
+    }
+
+    #[test]
+    fn while_loop_with_mutable_counter() {
+        let mut env = Env::new(Vec::new());
+
+        assert_eq!(
+            eval_root(
+                "let mut done = false\nlet mut n = 0\nwhile (if $done then { false } else { true }) { n = $n + 1; done = true }\n$n",
+                &mut env,
+            ),
+            Ok(Val::Number(1)),
+        );
+    }
+
+    #[test]
+    fn assign_to_undeclared_binding() {
+        let mut env = Env::new(Vec::new());
+
+        assert_eq!(
+            eval_root("x = 5", &mut env),
+            Err(EvalError::new(
+                EvalErrorKind::AssignToUndeclaredBinding,
+                TextRange::new(0.into(), 1.into()),
+            )),
+        );
+    }
+
+    #[test]
+    fn assign_to_immutable_binding() {
+        let mut env = Env::new(Vec::new());
+
+        assert_eq!(
+            eval_root("let x = 1\nx = 2", &mut env),
+            Err(EvalError::new(
+                EvalErrorKind::AssignToImmutableBinding,
+                TextRange::new(10.into(), 11.into()),
+            )),
+        );
+    }
+
+    #[test]
+    fn import_module_with_namespace() {
+        let (mut env, dir) = env_in_temp_dir("import");
+        std::fs::write(
+            dir.join("lib.fj"),
+            "let greeting = \"hi\"\nlet double = |x| $x + $x\nlet _secret = 1",
+        )
+        .unwrap();
+
+        assert_eq!(
+            eval_root("import \"lib.fj\"\n$lib.greeting", &mut env),
+            Ok(Val::Str("hi".to_string())),
+        );
+        assert_eq!(eval_root("lib.double 4", &mut env), Ok(Val::Number(8)));
+        assert_eq!(
+            eval_root("$lib._secret", &mut env),
+            Err(EvalError::new(
+                EvalErrorKind::BindingDoesNotExist,
+                TextRange::new(0.into(), 12.into()),
+            )),
+        );
+    }
+
+    #[test]
+    fn source_module_merges_bindings() {
+        let (mut env, dir) = env_in_temp_dir("source");
+        std::fs::write(dir.join("lib.fj"), "let greeting = \"hi\"").unwrap();
+
+        assert_eq!(
+            eval_root("source \"lib.fj\"\n$greeting", &mut env),
+            Ok(Val::Str("hi".to_string())),
+        );
+    }
+
+    #[test]
+    fn source_module_merges_aliases() {
+        let (mut env, dir) = env_in_temp_dir("source-alias");
+        std::fs::write(dir.join("lib.fj"), "alias upto = range 1").unwrap();
+
+        assert_eq!(
+            eval_root("source \"lib.fj\"\nupto 3", &mut env),
+            Ok(Val::List(vec![Val::Number(1), Val::Number(2)])),
+        );
+    }
+
+    #[test]
+    fn import_from_module_search_path() {
+        let (mut env, dir) = env_in_temp_dir("module-search-path");
+        std::fs::create_dir(dir.join("modules")).unwrap();
+        std::fs::write(dir.join("modules").join("lib.fj"), "let x = 5").unwrap();
+
+        env.set_module_search_path(vec![dir.join("modules")]);
+
+        assert_eq!(
+            eval_root("import \"lib.fj\"\n$lib.x", &mut env),
+            Ok(Val::Number(5)),
+        );
+    }
+
+    #[test]
+    fn imports_are_relative_to_importing_module() {
+        let (mut env, dir) = env_in_temp_dir("nested-import");
+        std::fs::create_dir(dir.join("sub")).unwrap();
+        std::fs::write(dir.join("sub").join("a.fj"), "source \"b.fj\"\nlet y = $x").unwrap();
+        std::fs::write(dir.join("sub").join("b.fj"), "let x = 5").unwrap();
+
+        assert_eq!(
+            eval_root("import \"sub/a.fj\"\n$a.y", &mut env),
+            Ok(Val::Number(5)),
+        );
+    }
+
+    #[test]
+    fn module_lambdas_can_use_rest_of_module() {
+        let (mut env, dir) = env_in_temp_dir("module-lambdas");
+        std::fs::write(
+            dir.join("lib.fj"),
+            r#"let sq = |x| $x * $x
+let quad = |x| sq (sq $x)
+let _k = 10
+let addk = |x| $x + $_k
+let mut count = 0
+let next = || { count = $count + 1; $count }"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            eval_root(
+                "import \"lib.fj\"\n[(lib.quad 2) (lib.addk 1) (lib.next) (lib.next)]",
+                &mut env,
+            ),
+            Ok(Val::List(vec![
+                Val::Number(16),
+                Val::Number(11),
+                Val::Number(1),
+                Val::Number(2),
+            ])),
+        );
+        assert_eq!(
+            eval_root("source \"lib.fj\"\naddk 1", &mut env),
+            Ok(Val::Number(11)),
+        );
+    }
+
+    #[test]
+    fn errors_in_module_lambdas_name_the_module() {
+        let (mut env, dir) = env_in_temp_dir("module-lambda-error");
+        std::fs::write(dir.join("lib.fj"), "let fail = |x| $missing").unwrap();
+
+        let lib = std::fs::canonicalize(dir.join("lib.fj")).unwrap();
+
+        assert_eq!(
+            eval_root("import \"lib.fj\"\nlib.fail 1", &mut env),
+            Err(EvalError::new(
+                EvalErrorKind::ErrorInModule {
+                    path: lib,
+                    error: Box::new(EvalError::new(
+                        EvalErrorKind::BindingDoesNotExist,
+                        TextRange::new(15.into(), 23.into()),
+                    )),
+                },
+                TextRange::new(16.into(), 26.into()),
+            )),
+        );
+    }
+
+    #[test]
+    fn import_non_existent_module() {
+        let (mut env, _dir) = env_in_temp_dir("missing-module");
+
+        assert_eq!(
+            eval_root("import \"missing.fj\"", &mut env),
+            Err(EvalError::new(
+                EvalErrorKind::ModuleDoesNotExist,
+                TextRange::new(7.into(), 19.into()),
+            )),
+        );
+    }
+
+    #[test]
+    fn detect_import_cycle() {
+        let (mut env, dir) = env_in_temp_dir("import-cycle");
+        std::fs::write(dir.join("a.fj"), "import \"b.fj\"").unwrap();
+        std::fs::write(dir.join("b.fj"), "import \"a.fj\"").unwrap();
+
+        let a = std::fs::canonicalize(dir.join("a.fj")).unwrap();
+        let b = std::fs::canonicalize(dir.join("b.fj")).unwrap();
+        let path_range = TextRange::new(7.into(), 13.into());
+
+        assert_eq!(
+            eval_root("import \"a.fj\"", &mut env),
+            Err(EvalError::new(
+                EvalErrorKind::ErrorInModule {
+                    path: a.clone(),
+                    error: Box::new(EvalError::new(
+                        EvalErrorKind::ErrorInModule {
+                            path: b,
+                            error: Box::new(EvalError::new(
+                                EvalErrorKind::ImportCycle { path: a },
+                                path_range,
+                            )),
+                        },
+                        path_range,
+                    )),
+                },
+                path_range,
+            )),
+        );
+    }
+
+    #[test]
+    fn syntax_errors_in_imported_module_name_the_module() {
+        let (mut env, dir) = env_in_temp_dir("import-syntax-error");
+        std::fs::write(dir.join("lib.fj"), "let = 5").unwrap();
+
+        let error = eval_root("import \"lib.fj\"", &mut env).unwrap_err();
+
+        match error.kind() {
+            EvalErrorKind::ImportSyntaxErrors { path, errors } => {
+                assert_eq!(path, &std::fs::canonicalize(dir.join("lib.fj")).unwrap());
+                assert!(!errors.is_empty());
+            }
+            kind => panic!("unexpected error kind {:?}", kind),
+        }
+    }
 }
@@ -0,0 +1,185 @@
+use super::{exit_code, EvalError, EvalErrorKind};
+use crate::env::Env;
+use crate::val::Val;
+use smol_str::SmolStr;
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use text_size::TextRange;
+
+/// Functions that are part of the interpreter itself, rather than being external commands or
+/// lambdas defined in Fjord code.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub(crate) enum Builtin {
+    /// Lists all background jobs as records of their number, command and status.
+    Jobs,
+    /// Waits for the given background jobs to finish, or all of them if none are given.
+    Wait,
+    /// Brings a background job into the foreground, waiting for it to finish.
+    Fg,
+    /// Resumes a stopped background job, leaving it in the background.
+    Bg,
+    /// Returns a list of the numbers from the first parameter (or zero if there is only one)
+    /// up to but not including the last.
+    Range,
+}
+
+impl Builtin {
+    pub(crate) const ALL: [Self; 5] = [Self::Jobs, Self::Wait, Self::Fg, Self::Bg, Self::Range];
+
+    pub(crate) fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|builtin| builtin.name() == name)
+    }
+
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Self::Jobs => "jobs",
+            Self::Wait => "wait",
+            Self::Fg => "fg",
+            Self::Bg => "bg",
+            Self::Range => "range",
+        }
+    }
+
+    pub(crate) fn eval(
+        self,
+        params: Vec<Val>,
+        params_range: TextRange,
+        env: &Env<'_>,
+    ) -> Result<Val, EvalError> {
+        let error = |kind| EvalError::new(kind, params_range);
+
+        match self {
+            Self::Jobs => {
+                if !params.is_empty() {
+                    return Err(error(EvalErrorKind::TooManyParams));
+                }
+
+                let jobs = env
+                    .list_jobs()
+                    .into_iter()
+                    .map(|(id, command, exit_status)| {
+                        // Jobs that have finished have their exit status in place of a status.
+                        let status = match exit_status {
+                            Some(exit_status) => Val::ExitStatus(exit_code(exit_status)),
+                            None => Val::Str("running".to_string()),
+                        };
+
+                        let mut fields = BTreeMap::new();
+                        fields.insert(SmolStr::new("job"), Val::Job(id));
+                        fields.insert(SmolStr::new("command"), Val::Str(command));
+                        fields.insert(SmolStr::new("status"), status);
+
+                        Val::Record(fields)
+                    })
+                    .collect();
+
+                Ok(Val::List(jobs))
+            }
+            Self::Wait => {
+                let mut last_status = Val::ExitStatus(0);
+
+                if params.is_empty() {
+                    while let Some(id) = env.most_recent_job() {
+                        last_status = wait(id, env).map_err(error)?;
+                    }
+                }
+
+                for param in &params {
+                    let id = job_id(param).ok_or_else(|| error(EvalErrorKind::NoSuchJob))?;
+                    last_status = wait(id, env).map_err(error)?;
+                }
+
+                Ok(last_status)
+            }
+            Self::Fg => {
+                let id = single_job_id(&params, env).map_err(error)?;
+
+                #[cfg(unix)]
+                resume(id, env);
+
+                wait(id, env).map_err(error)
+            }
+            Self::Bg => {
+                let id = single_job_id(&params, env).map_err(error)?;
+
+                if env.job_pid(id).is_none() {
+                    return Err(error(EvalErrorKind::NoSuchJob));
+                }
+
+                #[cfg(unix)]
+                resume(id, env);
+
+                Ok(Val::Job(id))
+            }
+            Self::Range => {
+                let (start, end) = match params.as_slice() {
+                    [] => return Err(error(EvalErrorKind::TooFewParams)),
+                    [end] => (&Val::Number(0), end),
+                    [start, end] => (start, end),
+                    _ => return Err(error(EvalErrorKind::TooManyParams)),
+                };
+
+                match (start, end) {
+                    (Val::Number(start), Val::Number(end)) => {
+                        Ok(Val::List((*start..*end).map(Val::Number).collect()))
+                    }
+                    (Val::Number(_), other) | (other, _) => {
+                        Err(error(EvalErrorKind::NonNumberRangeBound { ty: other.ty() }))
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Jobs can be referred to by the value running them in the background returns, by their number,
+// or with the `%1` notation used by most shells (and used when displaying jobs).
+fn job_id(val: &Val) -> Option<u32> {
+    match val {
+        Val::Job(id) => Some(*id),
+        Val::Number(n) => u32::try_from(*n).ok(),
+        Val::Str(s) => s.strip_prefix('%').unwrap_or(s).parse().ok(),
+        _ => None,
+    }
+}
+
+fn single_job_id(params: &[Val], env: &Env<'_>) -> Result<u32, EvalErrorKind> {
+    match params {
+        [] => env.most_recent_job().ok_or(EvalErrorKind::NoSuchJob),
+        [param] => job_id(param).ok_or(EvalErrorKind::NoSuchJob),
+        _ => Err(EvalErrorKind::TooManyParams),
+    }
+}
+
+fn wait(id: u32, env: &Env<'_>) -> Result<Val, EvalErrorKind> {
+    // Whichever job we’re waiting for is effectively running in the foreground, so it should be
+    // interrupted along with evaluation.
+    let _foreground = env.job_pid(id).map(|pid| env.run_in_foreground(pid));
+
+    let exit_status = env.wait_for_job(id);
+
+    if env.is_cancelled() {
+        return Err(EvalErrorKind::Interrupted);
+    }
+
+    match exit_status {
+        Some(Ok(exit_status)) => Ok(Val::ExitStatus(exit_code(exit_status))),
+        Some(Err(_)) => Err(EvalErrorKind::FailedRunningCommand),
+        None => Err(EvalErrorKind::NoSuchJob),
+    }
+}
+
+// Sends SIGCONT to a job, which resumes it if it has been stopped (for example with `kill -STOP`),
+// and does nothing otherwise.
+#[cfg(unix)]
+fn resume(id: u32, env: &Env<'_>) {
+    if let Some(pid) = env.job_pid(id) {
+        // SAFETY: kill has no memory safety requirements.
+        unsafe {
+            libc::kill(pid as libc::pid_t, libc::SIGCONT);
+        }
+    }
+}
@@ -1,4 +1,6 @@
+use crate::parser::SyntaxError;
 use crate::val::Ty;
+use std::path::PathBuf;
 use text_size::TextRange;
 
 /// A struct representing all the possible ways evaluation can fail. This includes both the kind of
@@ -13,12 +15,40 @@ impl EvalError {
     pub(super) fn new(kind: EvalErrorKind, range: TextRange) -> Self {
         Self { kind, range }
     }
+
+    pub(super) fn kind(&self) -> &EvalErrorKind {
+        &self.kind
+    }
+
+    pub(crate) fn is_interrupted(&self) -> bool {
+        self.kind == EvalErrorKind::Interrupted
+    }
+
+    /// `break` and `continue` propagate as errors up to the innermost loop, which catches them. If
+    /// one gets as far as the edge of a lambda (or of the code being evaluated) without being
+    /// caught, there is no loop for it to act on.
+    pub(super) fn outside_loop(self) -> Self {
+        let kind = match self.kind {
+            EvalErrorKind::Break => EvalErrorKind::BreakOutsideLoop,
+            EvalErrorKind::Continue => EvalErrorKind::ContinueOutsideLoop,
+            kind => kind,
+        };
+
+        Self { kind, ..self }
+    }
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
 pub(crate) enum EvalErrorKind {
+    /// when an expression or pattern that could not be lowered is evaluated, such as a number
+    /// literal too large to fit into 64 bits
+    InvalidCode,
     /// when a binding is used that has not been defined
     BindingDoesNotExist,
+    /// when a binding is assigned to that has not been defined
+    AssignToUndeclaredBinding,
+    /// when a binding that was not defined with `let mut` is assigned to
+    AssignToImmutableBinding,
     /// when too many parameters are supplied to a function
     TooManyParams,
     /// when too few parameters are supplied to a function
@@ -27,12 +57,63 @@ pub(crate) enum EvalErrorKind {
     CallNonLambda { ty: Ty },
     /// when a function or command that does not exist is called
     FuncOrCommandDoesNotExist,
+    /// when a command is invoked by its path, but the file at that path is not executable
+    NotExecutable,
     /// when something that cannot be displayed is passed as an argument into a command
     UndisplayableCommandArg,
+    /// when a glob pattern passed to a command matches no files and this is configured to be an
+    /// error
+    NoGlobMatches,
+    /// when something other than a command is run in the background
+    BackgroundNonCommand,
+    /// when the bounds passed to `range` are not numbers
+    NonNumberRangeBound { ty: Ty },
+    /// when a job that does not exist (or has already finished) is referred to
+    NoSuchJob,
     /// when running a command fails
     FailedRunningCommand,
     /// when a binary operation is applied to two types that are not numbers
     BinOpOnNonNumbers { lhs_ty: Ty, rhs_ty: Ty },
-    /// when a non-boolean condition is used in an if-expression
+    /// when a number is divided by zero
+    DivisionByZero,
+    /// when the left-hand side of `&&` or `||` is neither a boolean nor an exit status
+    LogicalOpOnNonBool { ty: Ty },
+    /// when a non-boolean condition is used in an if-expression or while loop
     NonBoolCond,
+    /// when something other than a list or command is iterated over in a for loop
+    NonIterable { ty: Ty },
+    /// when `break` is evaluated, which stops the innermost loop and is only ever seen outside of
+    /// evaluation as `BreakOutsideLoop`
+    Break,
+    /// when `continue` is evaluated, which moves the innermost loop on to its next iteration and is
+    /// only ever seen outside of evaluation as `ContinueOutsideLoop`
+    Continue,
+    /// when `break` is used outside of a loop
+    BreakOutsideLoop,
+    /// when `continue` is used outside of a loop
+    ContinueOutsideLoop,
+    /// when none of the arms of a match expression match the value being matched on
+    NonExhaustiveMatch,
+    /// when a type pattern refers to a type that does not exist
+    TyDoesNotExist,
+    /// when a module that is imported cannot be found
+    ModuleDoesNotExist,
+    /// when reading a module that is imported fails
+    FailedReadingModule { path: PathBuf },
+    /// when a module is imported while it is already being imported, either directly or by one of
+    /// the modules it imports
+    ImportCycle { path: PathBuf },
+    /// when a module that is imported contains syntax errors
+    ImportSyntaxErrors {
+        path: PathBuf,
+        errors: Vec<SyntaxError>,
+    },
+    /// when evaluating a module that is imported fails, in which case the inner error’s range is
+    /// in that module
+    ErrorInModule {
+        path: PathBuf,
+        error: Box<EvalError>,
+    },
+    /// when evaluation is cancelled, e.g. because the user pressed Ctrl-C
+    Interrupted,
 }
@@ -0,0 +1,158 @@
+use super::EvalErrorKind;
+use crate::env::{Env, GlobNoMatch};
+use crate::val::Val;
+use glob::{MatchOptions, Pattern};
+use smol_str::SmolStr;
+use std::path::Path;
+
+/// Expands a leading `~` (the current user’s home directory, taken from `$HOME`) or `~user`
+/// (`user`’s home directory). Words that can’t be expanded are returned unchanged.
+pub(crate) fn expand_tilde(word: &str, env: &Env<'_>) -> String {
+    let (home, rest) = split_tilde(word, env);
+    format!("{}{}", home, rest)
+}
+
+/// Splits a word into the home directory its leading tilde expands to and the rest of the word.
+/// The home directory is empty if the word can’t be expanded, in which case the rest is all of it.
+pub(super) fn split_tilde<'a>(word: &'a str, env: &Env<'_>) -> (String, &'a str) {
+    let without_tilde = match word.strip_prefix('~') {
+        Some(without_tilde) => without_tilde,
+        None => return (String::new(), word),
+    };
+
+    let (user, rest) = match without_tilde.find(std::path::is_separator) {
+        Some(idx) => without_tilde.split_at(idx),
+        None => (without_tilde, ""),
+    };
+
+    let home = if user.is_empty() {
+        match env.get_binding(&SmolStr::new("HOME")) {
+            Some(Val::Str(home)) => Some(home),
+            _ => None,
+        }
+    } else {
+        home_dir_of(user)
+    };
+
+    match home {
+        Some(home) => (home, rest),
+        None => (String::new(), word),
+    }
+}
+
+#[cfg(unix)]
+fn home_dir_of(user: &str) -> Option<String> {
+    use std::ffi::{CStr, CString};
+    use std::mem::MaybeUninit;
+    use std::ptr;
+
+    // Entries this large don’t exist in practice, so needing a bigger buffer means something is
+    // wrong, and we’d rather give up than keep allocating.
+    const MAX_BUF_LEN: usize = 1024 * 1024;
+
+    let user = CString::new(user).ok()?;
+    let mut buf: Vec<libc::c_char> = vec![0; 1024];
+
+    loop {
+        let mut passwd = MaybeUninit::<libc::passwd>::uninit();
+        let mut result = ptr::null_mut();
+
+        // SAFETY: all pointers are valid for the duration of the call, and `buf.len()` is the
+        // actual length of the buffer.
+        let status = unsafe {
+            libc::getpwnam_r(
+                user.as_ptr(),
+                passwd.as_mut_ptr(),
+                buf.as_mut_ptr(),
+                buf.len(),
+                &mut result,
+            )
+        };
+
+        if status == libc::ERANGE && buf.len() < MAX_BUF_LEN {
+            let new_len = buf.len() * 2;
+            buf.resize(new_len, 0);
+            continue;
+        }
+
+        if status != 0 || result.is_null() {
+            return None;
+        }
+
+        // SAFETY: getpwnam_r succeeded, so `result` points to `passwd`, whose `pw_dir` is a valid
+        // nul-terminated string stored in `buf`.
+        let dir = unsafe { CStr::from_ptr((*result).pw_dir) };
+
+        return Some(dir.to_string_lossy().into_owned());
+    }
+}
+
+#[cfg(not(unix))]
+fn home_dir_of(_user: &str) -> Option<String> {
+    None
+}
+
+pub(super) fn is_glob(s: &str) -> bool {
+    s.contains(['*', '?', '['])
+}
+
+/// Expands `pattern`, which comes after `prefix` in a word. The prefix (such as the home directory a
+/// tilde expanded to) is matched literally, even if it contains characters that have special
+/// meaning in globs.
+pub(super) fn expand_glob(
+    prefix: &str,
+    pattern: &str,
+    env: &Env<'_>,
+) -> Result<Vec<String>, EvalErrorKind> {
+    let working_dir = env.working_dir();
+    let word = format!("{}{}", prefix, pattern);
+    let pattern = format!("{}{}", Pattern::escape(prefix), pattern);
+
+    // Relative patterns are matched against the environment’s working directory rather than that
+    // of the process. The working directory itself could contain characters that have special
+    // meaning in globs, so we have to escape it.
+    let is_relative = Path::new(&pattern).is_relative();
+    let full_pattern = if is_relative {
+        Path::new(&Pattern::escape(&working_dir.to_string_lossy())).join(&pattern)
+    } else {
+        Path::new(&pattern).to_path_buf()
+    };
+
+    // Like in most shells, wildcards do not match hidden files unless explicitly asked to.
+    let options = MatchOptions {
+        require_literal_leading_dot: true,
+        ..MatchOptions::default()
+    };
+
+    let paths = match glob::glob_with(&full_pattern.to_string_lossy(), options) {
+        Ok(paths) => paths,
+
+        // Anything that isn’t a valid pattern (such as an unclosed ‘[’) is passed through as-is.
+        Err(_) => return Ok(vec![word]),
+    };
+
+    let matches: Vec<_> = paths
+        .filter_map(Result::ok)
+        .map(|path| {
+            let path = if is_relative {
+                path.strip_prefix(working_dir)
+                    .unwrap_or(&path)
+                    .to_path_buf()
+            } else {
+                path
+            };
+
+            path.to_string_lossy().into_owned()
+        })
+        .collect();
+
+    if !matches.is_empty() {
+        return Ok(matches);
+    }
+
+    match env.glob_no_match() {
+        GlobNoMatch::PassThrough => Ok(vec![word]),
+        GlobNoMatch::Remove => Ok(Vec::new()),
+        GlobNoMatch::Error => Err(EvalErrorKind::NoGlobMatches),
+    }
+}
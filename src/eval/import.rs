@@ -0,0 +1,96 @@
+use super::{EvalError, EvalErrorKind};
+use crate::env::Env;
+use crate::hir::Hir;
+use crate::parser::Parser;
+use crate::val::Val;
+use smol_str::SmolStr;
+use std::fs;
+use std::path::Path;
+use std::rc::Rc;
+use text_size::TextRange;
+
+/// Imports the module at `module_path`, evaluating it with `eval_module`. This takes care of
+/// everything apart from evaluation itself, so that both the tree-walker and the bytecode VM can
+/// share it.
+pub(super) fn import(
+    module_path: &str,
+    range: TextRange,
+    is_source: bool,
+    env: &mut Env<'_>,
+    eval_module: impl FnOnce(&Rc<Hir>, &mut Env<'static>) -> Result<Val, EvalError>,
+) -> Result<(), EvalError> {
+    let error = |kind| EvalError::new(kind, range);
+
+    let path = env
+        .resolve_module(module_path)
+        .ok_or_else(|| error(EvalErrorKind::ModuleDoesNotExist))?;
+
+    // Canonicalising the path means that a module is recognised as part of a cycle no matter
+    // how it was referred to.
+    let path = fs::canonicalize(&path).unwrap_or(path);
+
+    let _importing = env.start_importing(path.clone()).map_err(error)?;
+
+    let source = fs::read_to_string(&path)
+        .map_err(|_| error(EvalErrorKind::FailedReadingModule { path: path.clone() }))?;
+
+    let parse_output = Parser::new(&source).parse();
+
+    if !parse_output.errors().is_empty() {
+        return Err(error(EvalErrorKind::ImportSyntaxErrors {
+            path,
+            errors: parse_output.errors().to_vec(),
+        }));
+    }
+
+    let hir = parse_output.into_no_errors().unwrap().lower();
+    let mut module_env = env.create_module_env();
+
+    eval_module(&hir, &mut module_env).map_err(|module_error| {
+        error(EvalErrorKind::ErrorInModule {
+            path: path.clone(),
+            error: Box::new(module_error),
+        })
+    })?;
+
+    // `import "lib.fj"` makes the module’s bindings (and aliases) available as `lib.name`, while
+    // `source` merges them directly into the current environment.
+    let namespace = if is_source {
+        None
+    } else {
+        Path::new(module_path)
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+    };
+
+    // Names that start with an underscore are private to the module.
+    let exported_name = |name: SmolStr| -> Option<SmolStr> {
+        if name.starts_with('_') {
+            return None;
+        }
+
+        match &namespace {
+            Some(namespace) => Some(format!("{}.{}", namespace, name).into()),
+            None => Some(name),
+        }
+    };
+
+    for (name, alias) in module_env.aliases() {
+        if let Some(name) = exported_name(name) {
+            env.store_alias(name, alias);
+        }
+    }
+
+    for (name, val) in module_env.bindings() {
+        if let Some(name) = exported_name(name) {
+            env.store_binding(name, val);
+        }
+    }
+
+    // Lambdas are evaluated in the environment they are called from, so this is what lets a
+    // lambda exported from the module use the module’s other bindings (even those that aren’t
+    // exported, or are exported under a namespace).
+    env.add_module(path, hir, module_env);
+
+    Ok(())
+}
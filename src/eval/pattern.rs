@@ -0,0 +1,95 @@
+use super::{EvalError, EvalErrorKind};
+use crate::hir::{Hir, Pattern, PatternIdx};
+use crate::val::{Ty, Val};
+use smol_str::SmolStr;
+
+impl Hir {
+    /// Checks whether the pattern matches the given value, collecting the bindings it introduces
+    /// along the way. If the pattern does not match, some bindings may still have been collected,
+    /// so they should be discarded.
+    pub(super) fn bind_pattern(
+        &self,
+        pattern: PatternIdx,
+        val: &Val,
+        bindings: &mut Vec<(SmolStr, Val)>,
+    ) -> Result<bool, EvalError> {
+        let matches = match &self.patterns[pattern] {
+            Pattern::Wildcard => true,
+            Pattern::Ty(name) => {
+                let ty = Ty::from_name(&self.names[*name]).ok_or_else(|| {
+                    EvalError::new(EvalErrorKind::TyDoesNotExist, self.source_map[pattern])
+                })?;
+
+                val.ty() == ty
+            }
+            Pattern::Binding(name) => {
+                bindings.push((self.names[*name].clone(), val.clone()));
+                true
+            }
+            Pattern::Number(n) => *val == Val::Number(*n),
+            Pattern::Str(s) => matches!(val, Val::Str(val) if val == s),
+            Pattern::Bool(b) => *val == Val::Bool(*b),
+            Pattern::List {
+                elements: patterns,
+                rest,
+            } => {
+                let elements = match val {
+                    Val::List(elements) => elements,
+                    _ => return Ok(false),
+                };
+
+                // Without a rest pattern the list has to be exactly as long as the pattern.
+                let length_matches = if rest.is_some() {
+                    elements.len() >= patterns.len()
+                } else {
+                    elements.len() == patterns.len()
+                };
+
+                if !length_matches {
+                    return Ok(false);
+                }
+
+                for (pattern, element) in patterns.iter().zip(elements) {
+                    if !self.bind_pattern(*pattern, element, bindings)? {
+                        return Ok(false);
+                    }
+                }
+
+                if let Some(Some(name)) = rest {
+                    let rest = elements[patterns.len()..].to_vec();
+                    bindings.push((self.names[*name].clone(), Val::List(rest)));
+                }
+
+                true
+            }
+            Pattern::Record(field_patterns) => {
+                let fields = match val {
+                    Val::Record(fields) => fields,
+                    _ => return Ok(false),
+                };
+
+                // Records may have more fields than the pattern mentions.
+                for (name, pattern) in field_patterns {
+                    let field = match fields.get(&self.names[*name]) {
+                        Some(field) => field,
+                        None => return Ok(false),
+                    };
+
+                    if !self.bind_pattern(*pattern, field, bindings)? {
+                        return Ok(false);
+                    }
+                }
+
+                true
+            }
+            Pattern::Invalid => {
+                return Err(EvalError::new(
+                    EvalErrorKind::InvalidCode,
+                    self.source_map[pattern],
+                ))
+            }
+        };
+
+        Ok(matches)
+    }
+}
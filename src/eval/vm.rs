@@ -0,0 +1,1055 @@
+//! A bytecode compiler and stack-based virtual machine, which evaluate Fjord code with the same
+//! semantics as the tree-walking interpreter without re-examining the HIR as they go.
+//!
+//! The compiler turns HIR into flat sequences of instructions. Constructs that create
+//! a new environment (blocks, loop bodies, lambda bodies and the like) are compiled into separate
+//! instruction sequences, which the VM runs recursively, giving each its own environment. This
+//! mirrors how the tree-walker nests environments, so the two always agree on scoping.
+
+mod compile;
+
+use super::{
+    apply, arithmetic, bind_params, check_interrupted, command, exit_code, import, leave_lambda,
+    loop_should_continue, output_lines, push_alias_args, push_val_arg, push_word_arg, run_command,
+    spawn_background, Application, EvalError, EvalErrorKind,
+};
+use crate::env::Env;
+use crate::hir::{Hir, Lambda, PatternIdx};
+use crate::val::{Alias, AliasArg, Callee, FuncOrCommand, Val};
+use crate::Op;
+use compile::Compiler;
+use smol_str::SmolStr;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::process::Stdio;
+use std::rc::Rc;
+use text_size::TextRange;
+
+/// Fjord code compiled to bytecode, which can be run any number of times.
+#[derive(Debug)]
+pub struct Program {
+    code: Code,
+    // Lambdas are compiled ahead of time where possible, but lambdas that come from elsewhere
+    // (such as those bound in the environment by the tree-walker) are compiled the first time
+    // they are called.
+    lambdas: RefCell<HashMap<Lambda, Rc<CompiledLambda>>>,
+}
+
+type Code = Rc<[Instr]>;
+
+#[derive(Debug)]
+struct CompiledLambda {
+    params: Vec<(SmolStr, Option<Code>)>,
+    rest_param: Option<Option<SmolStr>>,
+    body: Code,
+}
+
+#[derive(Debug)]
+enum Instr {
+    Push(Val),
+    Pop,
+    CheckInterrupted(TextRange),
+    LoadBinding {
+        name: SmolStr,
+        range: TextRange,
+    },
+    StoreBinding {
+        name: SmolStr,
+        is_mutable: bool,
+    },
+    Assign {
+        name: SmolStr,
+        range: TextRange,
+    },
+    Import {
+        module_path: String,
+        range: TextRange,
+        is_source: bool,
+    },
+    /// Defines an alias expanding to `call`, whose arguments are evaluated straight away.
+    StoreAlias {
+        name: SmolStr,
+        call: Rc<Call>,
+    },
+    Arithmetic {
+        op: Op,
+        range: TextRange,
+    },
+    /// Jumps to `target`, leaving the left-hand side of `&&` or `||` on the stack as the result,
+    /// if the right-hand side should not be evaluated. Otherwise, pops the left-hand side.
+    ShortCircuit {
+        op: Op,
+        lhs_range: TextRange,
+        target: usize,
+    },
+    /// Pops a condition, jumping to `target` if it is false.
+    JumpUnlessTrue {
+        condition_range: TextRange,
+        target: usize,
+    },
+    Jump(usize),
+    Block(Code),
+    Lambda(Lambda),
+    Call {
+        call: Rc<Call>,
+        capture_output: bool,
+    },
+    Background {
+        call: Rc<Call>,
+        command: String,
+    },
+    Fail(EvalError),
+    MakeList(usize),
+    MakeRecord(Vec<SmolStr>),
+    /// Matches against the patterns of the arms in `hir`, in order, running the body of the first
+    /// one that matches.
+    Match {
+        hir: Rc<Hir>,
+        arms: Vec<(PatternIdx, Code)>,
+        scrutinee_range: TextRange,
+    },
+    For {
+        binding_name: SmolStr,
+        body: Code,
+        iterable_range: TextRange,
+        range: TextRange,
+    },
+    While {
+        condition: Code,
+        body: Code,
+        condition_range: TextRange,
+        range: TextRange,
+    },
+}
+
+#[derive(Debug)]
+struct Call {
+    name: SmolStr,
+    name_range: TextRange,
+    params: Vec<Param>,
+    params_range: TextRange,
+    range: TextRange,
+}
+
+#[derive(Debug)]
+struct Param {
+    // Unquoted words are kept around so that they can be expanded when passed to commands.
+    word: Option<String>,
+    code: Code,
+    range: TextRange,
+}
+
+impl Program {
+    pub(crate) fn compile(hir: &Rc<Hir>) -> Self {
+        let mut compiler = Compiler::new(hir);
+        let code = compiler.compile_items(&hir.root_items);
+
+        Self {
+            code,
+            lambdas: RefCell::new(compiler.into_lambdas()),
+        }
+    }
+
+    /// Runs the program in the given environment, producing the same result as evaluating the
+    /// code it was compiled from would have.
+    pub fn run(&self, env: &mut Env<'_>) -> Result<Val, EvalError> {
+        self.run_code(&self.code, env)
+            .map_err(EvalError::outside_loop)
+    }
+
+    fn run_code(&self, code: &[Instr], env: &mut Env<'_>) -> Result<Val, EvalError> {
+        let mut stack = Vec::new();
+        let mut pc = 0;
+
+        while let Some(instr) = code.get(pc) {
+            pc += 1;
+
+            match instr {
+                Instr::Push(val) => stack.push(val.clone()),
+                Instr::Pop => {
+                    stack.pop();
+                }
+                Instr::CheckInterrupted(range) => check_interrupted(env, *range)?,
+                Instr::LoadBinding { name, range } => {
+                    let val = env.get_binding(name).ok_or_else(|| {
+                        EvalError::new(EvalErrorKind::BindingDoesNotExist, *range)
+                    })?;
+
+                    stack.push(val);
+                }
+                Instr::StoreBinding { name, is_mutable } => {
+                    let val = stack.pop().unwrap();
+
+                    if *is_mutable {
+                        env.store_mutable_binding(name.clone(), val);
+                    } else {
+                        env.store_binding(name.clone(), val);
+                    }
+                }
+                Instr::Assign { name, range } => {
+                    let val = stack.pop().unwrap();
+
+                    env.assign_binding(name, val)
+                        .map_err(|kind| EvalError::new(kind, *range))?;
+                }
+                Instr::Import {
+                    module_path,
+                    range,
+                    is_source,
+                } => {
+                    import::import(module_path, *range, *is_source, env, |hir, module_env| {
+                        Program::compile(hir).run(module_env)
+                    })?;
+
+                    stack.push(Val::Nil);
+                }
+                Instr::StoreAlias { name, call } => {
+                    let alias = Alias {
+                        name: call.name.clone(),
+                        args: self.alias_args(call, env)?,
+                    };
+
+                    env.store_alias(name.clone(), alias);
+                }
+                Instr::Arithmetic { op, range } => {
+                    let rhs = stack.pop().unwrap();
+                    let lhs = stack.pop().unwrap();
+
+                    stack.push(arithmetic(*op, lhs, rhs, *range)?);
+                }
+                Instr::ShortCircuit {
+                    op,
+                    lhs_range,
+                    target,
+                } => {
+                    let lhs_succeeded = match stack.last().unwrap() {
+                        Val::Bool(b) => *b,
+                        Val::ExitStatus(code) => *code == 0,
+                        lhs => {
+                            return Err(EvalError::new(
+                                EvalErrorKind::LogicalOpOnNonBool { ty: lhs.ty() },
+                                *lhs_range,
+                            ));
+                        }
+                    };
+
+                    if lhs_succeeded == (*op == Op::And) {
+                        stack.pop();
+                    } else {
+                        pc = *target;
+                    }
+                }
+                Instr::JumpUnlessTrue {
+                    condition_range,
+                    target,
+                } => match stack.pop().unwrap() {
+                    Val::Bool(true) => {}
+                    Val::Bool(false) => pc = *target,
+                    _ => {
+                        return Err(EvalError::new(EvalErrorKind::NonBoolCond, *condition_range));
+                    }
+                },
+                Instr::Jump(target) => pc = *target,
+                Instr::Block(code) => {
+                    let mut child_env = env.create_child();
+                    stack.push(self.run_code(code, &mut child_env)?);
+                }
+                Instr::Lambda(lambda) => stack.push(Val::Lambda(lambda.clone())),
+                Instr::Call {
+                    call,
+                    capture_output,
+                } => stack.push(self.call(call, *capture_output, env)?),
+                Instr::Background { call, command } => stack.push(self.spawn(call, command, env)?),
+                Instr::Fail(error) => return Err(error.clone()),
+                Instr::MakeList(len) => {
+                    let elements = stack.split_off(stack.len() - len);
+                    stack.push(Val::List(elements));
+                }
+                Instr::MakeRecord(names) => {
+                    let vals = stack.split_off(stack.len() - names.len());
+                    let fields: BTreeMap<_, _> = names.iter().cloned().zip(vals).collect();
+
+                    stack.push(Val::Record(fields));
+                }
+                Instr::Match {
+                    hir,
+                    arms,
+                    scrutinee_range,
+                } => {
+                    let val = stack.pop().unwrap();
+                    stack.push(self.run_match(hir, &val, arms, *scrutinee_range, env)?);
+                }
+                Instr::For {
+                    binding_name,
+                    body,
+                    iterable_range,
+                    range,
+                } => {
+                    let elements = match stack.pop().unwrap() {
+                        Val::List(elements) => elements,
+                        val => {
+                            return Err(EvalError::new(
+                                EvalErrorKind::NonIterable { ty: val.ty() },
+                                *iterable_range,
+                            ));
+                        }
+                    };
+
+                    for element in elements {
+                        check_interrupted(env, *range)?;
+
+                        let mut iteration_env = env.create_child();
+                        iteration_env.store_binding(binding_name.clone(), element);
+
+                        if !loop_should_continue(self.run_code(body, &mut iteration_env))? {
+                            break;
+                        }
+                    }
+
+                    stack.push(Val::Nil);
+                }
+                Instr::While {
+                    condition,
+                    body,
+                    condition_range,
+                    range,
+                } => {
+                    loop {
+                        check_interrupted(env, *range)?;
+
+                        let keep_going = match self.run_code(condition, env)? {
+                            Val::Bool(b) => b,
+                            Val::ExitStatus(code) => code == 0,
+                            _ => {
+                                return Err(EvalError::new(
+                                    EvalErrorKind::NonBoolCond,
+                                    *condition_range,
+                                ));
+                            }
+                        };
+
+                        if !keep_going
+                            || !loop_should_continue(self.run_code(body, &mut env.create_child()))?
+                        {
+                            break;
+                        }
+                    }
+
+                    stack.push(Val::Nil);
+                }
+            }
+        }
+
+        // Every sequence of instructions leaves exactly one value on the stack.
+        Ok(stack.pop().unwrap())
+    }
+
+    fn call(&self, call: &Call, capture_output: bool, env: &mut Env<'_>) -> Result<Val, EvalError> {
+        // Iterating over a command goes over the lines it outputs, rather than its exit status.
+        if capture_output {
+            let Callee {
+                func_or_command,
+                alias_args,
+            } = self.resolve(call, env)?;
+
+            if let FuncOrCommand::Command(path) = func_or_command {
+                let command = command(path, self.command_args(call, &alias_args, env)?, env);
+                let (_, output) =
+                    run_command(command, Stdio::piped(), call.name_range, call.range, env)?;
+
+                return Ok(Val::List(output_lines(&output)));
+            }
+        }
+
+        // Checking here means that even an infinitely recursive lambda can be interrupted.
+        check_interrupted(env, call.name_range)?;
+
+        let Callee {
+            func_or_command,
+            alias_args,
+        } = self.resolve(call, env)?;
+
+        match func_or_command {
+            FuncOrCommand::Func(lambda) => {
+                let params = self.eval_params(call, &alias_args, env)?;
+                let params = match apply(&lambda, params.into_iter(), env) {
+                    Application::Call(params) => params,
+                    Application::Partial(lambda) => return Ok(Val::Lambda(lambda)),
+                };
+
+                let mut new_env = env.create_lambda_env(&lambda);
+                let compiled = self.compiled_lambda(&lambda);
+
+                bind_params(
+                    &compiled.params,
+                    compiled.rest_param.clone(),
+                    params,
+                    call.params_range,
+                    &mut new_env,
+                    |default, env| leave_lambda(self.run_code(default, env), env, call.range),
+                )?;
+
+                let result = self.run_code(&compiled.body, &mut new_env);
+                leave_lambda(result, &new_env, call.range)
+            }
+            FuncOrCommand::Builtin(builtin) => {
+                let params = self.eval_params(call, &alias_args, env)?;
+                builtin.eval(params, call.params_range, env)
+            }
+            FuncOrCommand::Command(path) => {
+                let command = command(path, self.command_args(call, &alias_args, env)?, env);
+                let (status, _) =
+                    run_command(command, Stdio::inherit(), call.name_range, call.range, env)?;
+
+                Ok(Val::ExitStatus(exit_code(status)))
+            }
+        }
+    }
+
+    fn spawn(&self, call: &Call, job_command: &str, env: &mut Env<'_>) -> Result<Val, EvalError> {
+        let Callee {
+            func_or_command,
+            alias_args,
+        } = self.resolve(call, env)?;
+
+        let path = match func_or_command {
+            FuncOrCommand::Command(path) => path,
+            FuncOrCommand::Func(_) | FuncOrCommand::Builtin(_) => {
+                return Err(EvalError::new(
+                    EvalErrorKind::BackgroundNonCommand,
+                    call.range,
+                ));
+            }
+        };
+
+        let command = command(path, self.command_args(call, &alias_args, env)?, env);
+
+        spawn_background(command, job_command.to_string(), call.name_range, env)
+    }
+
+    fn resolve(&self, call: &Call, env: &Env<'_>) -> Result<Callee, EvalError> {
+        env.get_func_or_command(&call.name)
+            .map_err(|kind| EvalError::new(kind, call.name_range))
+    }
+
+    fn eval_params(
+        &self,
+        call: &Call,
+        alias_args: &[AliasArg],
+        env: &mut Env<'_>,
+    ) -> Result<Vec<Val>, EvalError> {
+        let mut params: Vec<_> = alias_args.iter().map(AliasArg::to_val).collect();
+
+        for param in &call.params {
+            params.push(self.run_code(&param.code, env)?);
+        }
+
+        Ok(params)
+    }
+
+    fn command_args(
+        &self,
+        call: &Call,
+        alias_args: &[AliasArg],
+        env: &mut Env<'_>,
+    ) -> Result<Vec<String>, EvalError> {
+        let mut args = Vec::new();
+        push_alias_args(alias_args, call.name_range, env, &mut args)?;
+
+        for param in &call.params {
+            match &param.word {
+                Some(word) => push_word_arg(word, param.range, env, &mut args)?,
+                None => {
+                    let val = self.run_code(&param.code, env)?;
+                    push_val_arg(val, param.range, &mut args)?;
+                }
+            }
+        }
+
+        Ok(args)
+    }
+
+    // Just like in the tree-walker, unquoted words are kept as they are so that they can be
+    // expanded each time the alias is used.
+    fn alias_args(&self, call: &Call, env: &mut Env<'_>) -> Result<Vec<AliasArg>, EvalError> {
+        call.params
+            .iter()
+            .map(|param| match &param.word {
+                Some(word) => Ok(AliasArg::Word(word.clone())),
+                None => Ok(AliasArg::Val(self.run_code(&param.code, env)?)),
+            })
+            .collect()
+    }
+
+    fn compiled_lambda(&self, lambda: &Lambda) -> Rc<CompiledLambda> {
+        // Partially applying a lambda doesn’t change its code, so compiled lambdas are shared
+        // between all the ways a lambda has been applied.
+        let lambda = &lambda.unapplied();
+
+        if let Some(compiled) = self.lambdas.borrow().get(lambda) {
+            return Rc::clone(compiled);
+        }
+
+        let mut compiler = Compiler::new(lambda.hir());
+        let compiled = compiler.compile_lambda(lambda);
+        self.lambdas.borrow_mut().extend(compiler.into_lambdas());
+
+        compiled
+    }
+
+    fn run_match(
+        &self,
+        hir: &Hir,
+        val: &Val,
+        arms: &[(PatternIdx, Code)],
+        scrutinee_range: TextRange,
+        env: &Env<'_>,
+    ) -> Result<Val, EvalError> {
+        for (pattern, body) in arms {
+            let mut bindings = Vec::new();
+
+            if hir.bind_pattern(*pattern, val, &mut bindings)? {
+                let mut arm_env = env.create_child();
+
+                for (name, val) in bindings {
+                    arm_env.store_binding(name, val);
+                }
+
+                return self.run_code(body, &mut arm_env);
+            }
+        }
+
+        Err(EvalError::new(
+            EvalErrorKind::NonExhaustiveMatch,
+            scrutinee_range,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+    use crate::test_support::TempDir;
+    use crate::val::Ty;
+
+    // Runs the input with both the tree-walking interpreter and the VM, checking that they agree
+    // on both the result and the environment left behind.
+    fn check_with_env(input: &str, make_env: impl Fn() -> Env<'static>) -> Result<Val, EvalError> {
+        let parse_output = Parser::new(input).parse().into_no_errors().unwrap();
+
+        // Both have to run the same lowered code, since lambdas from different lowerings are
+        // never equal.
+        let hir = parse_output.lower();
+
+        let mut tree_walker_env = make_env();
+        let tree_walker_result = hir.eval(&mut tree_walker_env);
+
+        let mut vm_env = make_env();
+        let vm_result = Program::compile(&hir).run(&mut vm_env);
+
+        assert_eq!(vm_result, tree_walker_result);
+        assert_eq!(vm_env, tree_walker_env);
+
+        vm_result
+    }
+
+    fn check(input: &str) -> Result<Val, EvalError> {
+        check_with_env(input, || Env::new(Vec::new()))
+    }
+
+    #[test]
+    fn empty_input() {
+        assert_eq!(check(""), Ok(Val::Nil));
+    }
+
+    #[test]
+    fn arithmetic() {
+        assert_eq!(check("1 + 2 * 3 - 8 / 4"), Ok(Val::Number(5)));
+    }
+
+    #[test]
+    fn arithmetic_on_non_numbers() {
+        assert_eq!(
+            check("1 + \"a\""),
+            Err(EvalError::new(
+                EvalErrorKind::BinOpOnNonNumbers {
+                    lhs_ty: Ty::Number,
+                    rhs_ty: Ty::Str,
+                },
+                TextRange::new(0.into(), 7.into()),
+            )),
+        );
+    }
+
+    #[test]
+    fn division_by_zero() {
+        assert_eq!(
+            check("let x = 0\n5 / $x"),
+            Err(EvalError::new(
+                EvalErrorKind::DivisionByZero,
+                TextRange::new(10.into(), 16.into()),
+            )),
+        );
+    }
+
+    #[test]
+    fn invalid_code() {
+        assert_eq!(
+            check("99999999999999999999"),
+            Err(EvalError::new(
+                EvalErrorKind::InvalidCode,
+                TextRange::new(0.into(), 20.into()),
+            )),
+        );
+        assert_eq!(
+            check("let q = (1)+2"),
+            Err(EvalError::new(
+                EvalErrorKind::InvalidCode,
+                TextRange::new(8.into(), 13.into()),
+            )),
+        );
+        assert_eq!(
+            check("let f = |x y| $x+$y\nf 1 2"),
+            Err(EvalError::new(
+                EvalErrorKind::InvalidCode,
+                TextRange::new(14.into(), 19.into()),
+            )),
+        );
+        assert_eq!(
+            check("match 1 { 99999999999999999999 => 1 }"),
+            Err(EvalError::new(
+                EvalErrorKind::InvalidCode,
+                TextRange::new(10.into(), 30.into()),
+            )),
+        );
+    }
+
+    #[test]
+    fn logical_ops_short_circuit() {
+        assert_eq!(check("false && $nope"), Ok(Val::Bool(false)));
+        assert_eq!(check("true || $nope"), Ok(Val::Bool(true)));
+        assert_eq!(check("true && false || 5"), Ok(Val::Number(5)));
+        assert_eq!(
+            check("1 && true"),
+            Err(EvalError::new(
+                EvalErrorKind::LogicalOpOnNonBool { ty: Ty::Number },
+                TextRange::new(0.into(), 1.into()),
+            )),
+        );
+    }
+
+    #[test]
+    fn if_else_if_chains() {
+        let input = "let x = 2\nif false then { 1 } else if true then { $x } else { 3 }";
+        assert_eq!(check(input), Ok(Val::Number(2)));
+        assert_eq!(check("if false then { 1 }"), Ok(Val::Nil));
+        assert_eq!(
+            check("if 1 then { 1 }"),
+            Err(EvalError::new(
+                EvalErrorKind::NonBoolCond,
+                TextRange::new(3.into(), 4.into()),
+            )),
+        );
+    }
+
+    #[test]
+    fn bindings_and_blocks() {
+        assert_eq!(
+            check("let a = 1\nlet b = { let a = 2\n$a + 1 }\n$a + $b"),
+            Ok(Val::Number(4))
+        );
+        assert_eq!(
+            check("{ let inner = 1 }\n$inner"),
+            Err(EvalError::new(
+                EvalErrorKind::BindingDoesNotExist,
+                TextRange::new(18.into(), 24.into()),
+            )),
+        );
+    }
+
+    #[test]
+    fn mutable_bindings() {
+        assert_eq!(
+            check("let mut x = 1\n{ x = $x + 1 }\nx = $x * 10\n$x"),
+            Ok(Val::Number(20)),
+        );
+        assert_eq!(
+            check("let x = 1\nx = 2"),
+            Err(EvalError::new(
+                EvalErrorKind::AssignToImmutableBinding,
+                TextRange::new(10.into(), 11.into()),
+            )),
+        );
+        assert_eq!(
+            check("y = 2"),
+            Err(EvalError::new(
+                EvalErrorKind::AssignToUndeclaredBinding,
+                TextRange::new(0.into(), 1.into()),
+            )),
+        );
+    }
+
+    #[test]
+    fn lambdas() {
+        assert_eq!(
+            check("let add = |a b| $a + $b\nlet twice = |f x| f (f $x)\nlet inc = |x| add $x 1\ntwice $inc 5"),
+            Ok(Val::Number(7)),
+        );
+        assert_eq!(
+            check("let greet = |name| $name\ngreet world"),
+            Ok(Val::Str("world".to_string()))
+        );
+    }
+
+    #[test]
+    fn lambda_param_count_errors() {
+        assert_eq!(
+            check("let f = |a b| $a\nf 1"),
+            Err(EvalError::new(
+                EvalErrorKind::TooFewParams,
+                TextRange::new(19.into(), 20.into()),
+            )),
+        );
+        assert_eq!(
+            check("let f = |a| $a\nf 1 2"),
+            Err(EvalError::new(
+                EvalErrorKind::TooManyParams,
+                TextRange::new(17.into(), 20.into()),
+            )),
+        );
+    }
+
+    #[test]
+    fn recursive_lambda() {
+        let input = "let fact = |n| match $n { 0 => 1; _ => $n * (fact ($n - 1)) }\nfact 5";
+        assert_eq!(check(input), Ok(Val::Number(120)));
+    }
+
+    #[test]
+    fn calling_non_lambda() {
+        assert_eq!(
+            check("let x = 1\nx"),
+            Err(EvalError::new(
+                EvalErrorKind::CallNonLambda { ty: Ty::Number },
+                TextRange::new(10.into(), 11.into()),
+            )),
+        );
+        assert_eq!(
+            check("does-not-exist"),
+            Err(EvalError::new(
+                EvalErrorKind::FuncOrCommandDoesNotExist,
+                TextRange::new(0.into(), 14.into()),
+            )),
+        );
+    }
+
+    #[test]
+    fn lists_and_records() {
+        assert_eq!(
+            check("[1 (1 + 1) \"three\"]"),
+            Ok(Val::List(vec![
+                Val::Number(1),
+                Val::Number(2),
+                Val::Str("three".to_string()),
+            ])),
+        );
+        assert_eq!(
+            check("[a = 1 b = [2 3]]"),
+            Ok(Val::Record(BTreeMap::from([
+                (SmolStr::from("a"), Val::Number(1)),
+                (
+                    SmolStr::from("b"),
+                    Val::List(vec![Val::Number(2), Val::Number(3)]),
+                ),
+            ]))),
+        );
+    }
+
+    #[test]
+    fn match_expressions() {
+        let input = r#"
+let describe = |x| match $x {
+    0 => "zero"
+    "s" => "string"
+    [first ...rest] => $rest
+    [name = n] => $n
+    Bool => "bool"
+    _ => "other"
+}
+[(describe 0) (describe "s") (describe [1 2 3]) (describe [name = "n"]) (describe true) (describe 5)]"#;
+
+        assert_eq!(
+            check(input),
+            Ok(Val::List(vec![
+                Val::Str("zero".to_string()),
+                Val::Str("string".to_string()),
+                Val::List(vec![Val::Number(2), Val::Number(3)]),
+                Val::Str("n".to_string()),
+                Val::Str("bool".to_string()),
+                Val::Str("other".to_string()),
+            ])),
+        );
+    }
+
+    #[test]
+    fn non_exhaustive_match() {
+        assert_eq!(
+            check("match 5 { 1 => 1 }"),
+            Err(EvalError::new(
+                EvalErrorKind::NonExhaustiveMatch,
+                TextRange::new(6.into(), 7.into()),
+            )),
+        );
+        assert_eq!(
+            check("match 5 { Nonsense => 1 }"),
+            Err(EvalError::new(
+                EvalErrorKind::TyDoesNotExist,
+                TextRange::new(10.into(), 18.into()),
+            )),
+        );
+    }
+
+    #[test]
+    fn loops() {
+        let input = r#"
+let mut total = 0
+for n in (range 10) {
+    match $n {
+        7 => break
+        2 => continue
+        _ => { total = $total + $n }
+    }
+}
+let mut i = 0
+let mut going = true
+while $going {
+    i = $i + 1
+    going = match $i { 3 => false; _ => true }
+}
+[$total $i]"#;
+
+        assert_eq!(
+            check(input),
+            Ok(Val::List(vec![Val::Number(19), Val::Number(3)])),
+        );
+        assert_eq!(
+            check("for x in 5 { $x }"),
+            Err(EvalError::new(
+                EvalErrorKind::NonIterable { ty: Ty::Number },
+                TextRange::new(9.into(), 10.into()),
+            )),
+        );
+        assert_eq!(
+            check("while 5 { 1 }"),
+            Err(EvalError::new(
+                EvalErrorKind::NonBoolCond,
+                TextRange::new(6.into(), 7.into()),
+            )),
+        );
+        assert_eq!(
+            check("break"),
+            Err(EvalError::new(
+                EvalErrorKind::BreakOutsideLoop,
+                TextRange::new(0.into(), 5.into()),
+            )),
+        );
+        assert_eq!(
+            check("{ continue }"),
+            Err(EvalError::new(
+                EvalErrorKind::ContinueOutsideLoop,
+                TextRange::new(2.into(), 10.into()),
+            )),
+        );
+    }
+
+    #[test]
+    fn loop_control_does_not_escape_lambdas() {
+        assert_eq!(
+            check("let mut n = 0\nlet b = || { break }\nfor x in [1 2 3] { b; n = $n + 1 }"),
+            Err(EvalError::new(
+                EvalErrorKind::BreakOutsideLoop,
+                TextRange::new(27.into(), 32.into()),
+            )),
+        );
+        assert_eq!(
+            check("let c = || { continue }\nwhile true { c }"),
+            Err(EvalError::new(
+                EvalErrorKind::ContinueOutsideLoop,
+                TextRange::new(13.into(), 21.into()),
+            )),
+        );
+        assert_eq!(
+            check("let f = || { for x in [1 2] { break }; 5 }\n[(f) (f)]"),
+            Ok(Val::List(vec![Val::Number(5), Val::Number(5)])),
+        );
+    }
+
+    #[test]
+    fn rest_params() {
+        assert_eq!(
+            check("let f = |first ...rest| [$first $rest]\n[(f 1 2 3) (f 1)]"),
+            Ok(Val::List(vec![
+                Val::List(vec![
+                    Val::Number(1),
+                    Val::List(vec![Val::Number(2), Val::Number(3)]),
+                ]),
+                Val::List(vec![Val::Number(1), Val::List(Vec::new())]),
+            ])),
+        );
+        assert_eq!(
+            check("let f = |first ...rest| $rest\nf"),
+            Err(EvalError::new(
+                EvalErrorKind::TooFewParams,
+                TextRange::new(31.into(), 31.into()),
+            )),
+        );
+    }
+
+    #[test]
+    fn default_params() {
+        assert_eq!(
+            check("let f = |x y=($x * 2)| $x + $y\n[(f 1) (f 1 1)]"),
+            Ok(Val::List(vec![Val::Number(3), Val::Number(2)])),
+        );
+        assert_eq!(
+            check("let f = |x y=1| $x\nf"),
+            Err(EvalError::new(
+                EvalErrorKind::TooFewParams,
+                TextRange::new(20.into(), 20.into()),
+            )),
+        );
+    }
+
+    #[test]
+    fn partial_application() {
+        let input = "let add = |x y z=0| $x + $y + $z\nlet inc = add 1\n[(inc 5) (inc 5 10)]";
+
+        let make_env = || {
+            let mut env = Env::new(Vec::new());
+            env.set_partial_application(true);
+            env
+        };
+
+        assert_eq!(
+            check_with_env(input, make_env),
+            Ok(Val::List(vec![Val::Number(6), Val::Number(16)])),
+        );
+        assert_eq!(
+            check(input),
+            Err(EvalError::new(
+                EvalErrorKind::TooFewParams,
+                TextRange::new(47.into(), 48.into()),
+            )),
+        );
+    }
+
+    #[test]
+    fn aliases() {
+        let input = r#"
+let add = |x y| $x + $y
+alias inc = add 1
+alias upto = range 1
+[(inc 5) (upto 3)]"#;
+
+        assert_eq!(
+            check(input),
+            Ok(Val::List(vec![
+                Val::Number(6),
+                Val::List(vec![Val::Number(1), Val::Number(2)]),
+            ])),
+        );
+        assert_eq!(
+            check("{ alias r = range 1 }\nr 3"),
+            Err(EvalError::new(
+                EvalErrorKind::FuncOrCommandDoesNotExist,
+                TextRange::new(22.into(), 23.into()),
+            )),
+        );
+    }
+
+    #[test]
+    fn background_non_command() {
+        assert_eq!(
+            check("let f = || 5\nf &"),
+            Err(EvalError::new(
+                EvalErrorKind::BackgroundNonCommand,
+                TextRange::new(13.into(), 14.into()),
+            )),
+        );
+    }
+
+    #[test]
+    fn interrupted() {
+        let make_env = || {
+            let env = Env::new(Vec::new());
+            env.cancellation_token().cancel();
+            env
+        };
+
+        assert_eq!(
+            check_with_env("1", make_env),
+            Err(EvalError::new(
+                EvalErrorKind::Interrupted,
+                TextRange::new(0.into(), 1.into()),
+            )),
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn commands() {
+        assert_eq!(check("/bin/sh -c \"exit 3\""), Ok(Val::ExitStatus(3)));
+        assert_eq!(
+            check(
+                r#"let mut lines = []
+for line in (/usr/bin/printf "a\nb\n") { lines = [$line] }
+$lines"#
+            ),
+            Ok(Val::List(vec![Val::Str("b".to_string())])),
+        );
+    }
+
+    #[test]
+    fn import_and_source() {
+        let dir = TempDir::new("vm-import");
+        std::fs::write(dir.join("lib.fj"), "let greeting = \"hi\"\nlet _secret = 1").unwrap();
+
+        let make_env = || {
+            let mut env = Env::new(Vec::new());
+            env.set_working_dir(dir.to_path_buf());
+            env
+        };
+
+        assert_eq!(
+            check_with_env(
+                "import \"lib.fj\"\nsource \"lib.fj\"\n[$lib.greeting $greeting]",
+                make_env
+            ),
+            Ok(Val::List(vec![
+                Val::Str("hi".to_string()),
+                Val::Str("hi".to_string()),
+            ])),
+        );
+        assert_eq!(
+            check_with_env("import \"missing.fj\"", make_env),
+            Err(EvalError::new(
+                EvalErrorKind::ModuleDoesNotExist,
+                TextRange::new(7.into(), 19.into()),
+            )),
+        );
+
+        std::fs::write(
+            dir.join("lambdas.fj"),
+            "let sq = |x| $x * $x\nlet _k = 10\nlet quad_plus_k = |x| (sq (sq $x)) + $_k",
+        )
+        .unwrap();
+
+        // The import happens in a block so that the lambdas it binds, which are lowered separately
+        // by each evaluator and so never equal, don’t end up in the environments being compared.
+        assert_eq!(
+            check_with_env("{ import \"lambdas.fj\"; lambdas.quad_plus_k 2 }", make_env),
+            Ok(Val::Number(26)),
+        );
+    }
+}
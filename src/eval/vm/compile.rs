@@ -0,0 +1,335 @@
+use super::{Call, Code, CompiledLambda, Instr, Param};
+use crate::eval::{EvalError, EvalErrorKind};
+use crate::hir::{Expr, ExprIdx, Hir, Item, ItemIdx, Lambda};
+use crate::val::Val;
+use crate::Op;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// Lowers HIR to bytecode, compiling every lambda it comes across along the way.
+pub(super) struct Compiler<'hir> {
+    hir: &'hir Rc<Hir>,
+    lambdas: HashMap<Lambda, Rc<CompiledLambda>>,
+}
+
+impl<'hir> Compiler<'hir> {
+    pub(super) fn new(hir: &'hir Rc<Hir>) -> Self {
+        Self {
+            hir,
+            lambdas: HashMap::new(),
+        }
+    }
+
+    pub(super) fn into_lambdas(self) -> HashMap<Lambda, Rc<CompiledLambda>> {
+        self.lambdas
+    }
+
+    pub(super) fn compile_lambda(&mut self, lambda: &Lambda) -> Rc<CompiledLambda> {
+        debug_assert!(Rc::ptr_eq(lambda.hir(), self.hir));
+
+        if let Some(compiled) = self.lambdas.get(lambda) {
+            return Rc::clone(compiled);
+        }
+
+        let compiled = Rc::new(CompiledLambda {
+            params: lambda
+                .params()
+                .iter()
+                .map(|param| {
+                    let default = param.default.map(|default| self.compile_expr_code(default));
+                    (self.hir.names[param.name].clone(), default)
+                })
+                .collect(),
+            rest_param: lambda
+                .rest_param()
+                .map(|rest_param| rest_param.map(|name| self.hir.names[name].clone())),
+            body: self.compile_expr_code(lambda.body()),
+        });
+
+        self.lambdas.insert(lambda.clone(), Rc::clone(&compiled));
+
+        compiled
+    }
+
+    pub(super) fn compile_items(&mut self, items: &[ItemIdx]) -> Code {
+        let mut code = Vec::new();
+
+        for item in items {
+            // Only the value of the last item is kept.
+            if !code.is_empty() {
+                code.push(Instr::Pop);
+            }
+
+            code.push(Instr::CheckInterrupted(self.hir.source_map[*item]));
+            self.compile_item(*item, &mut code);
+        }
+
+        if code.is_empty() {
+            code.push(Instr::Push(Val::Nil));
+        }
+
+        code.into()
+    }
+
+    fn compile_item(&mut self, idx: ItemIdx, code: &mut Vec<Instr>) {
+        let hir = self.hir;
+
+        match &hir.items[idx] {
+            Item::BindingDef {
+                name,
+                is_mutable,
+                expr,
+            } => {
+                self.compile_expr(*expr, code);
+                code.push(Instr::StoreBinding {
+                    name: hir.names[*name].clone(),
+                    is_mutable: *is_mutable,
+                });
+                code.push(Instr::Push(Val::Nil));
+            }
+            Item::Assignment { name, expr } => {
+                self.compile_expr(*expr, code);
+                code.push(Instr::Assign {
+                    name: hir.names[*name].clone(),
+                    range: hir.source_map.assignment_name(idx),
+                });
+                code.push(Instr::Push(Val::Nil));
+            }
+            Item::Import { path, is_source } => code.push(Instr::Import {
+                module_path: path.clone(),
+                range: hir.source_map.import_path(idx),
+                is_source: *is_source,
+            }),
+            Item::Alias { name, call } => {
+                let call = self.compile_call(*call);
+                code.push(Instr::StoreAlias {
+                    name: hir.names[*name].clone(),
+                    call,
+                });
+                code.push(Instr::Push(Val::Nil));
+            }
+            Item::Expr(expr) => self.compile_expr(*expr, code),
+        }
+    }
+
+    // Compiles an expression that is run on its own, such as a lambda body or match arm.
+    fn compile_expr_code(&mut self, expr: ExprIdx) -> Code {
+        let mut code = Vec::new();
+        self.compile_expr(expr, &mut code);
+
+        code.into()
+    }
+
+    fn compile_expr(&mut self, idx: ExprIdx, code: &mut Vec<Instr>) {
+        let hir = self.hir;
+        let range = hir.source_map[idx];
+
+        match &hir.exprs[idx] {
+            Expr::BinOp { op, lhs, rhs } => self.compile_bin_op(idx, *op, *lhs, *rhs, code),
+            Expr::If {
+                condition,
+                true_branch,
+                false_branch,
+            } => self.compile_if(*condition, *true_branch, *false_branch, code),
+            Expr::FunctionCall { .. } => code.push(Instr::Call {
+                call: self.compile_call(idx),
+                capture_output: false,
+            }),
+            Expr::Lambda { .. } => {
+                let lambda = Lambda::new(Rc::clone(hir), idx);
+                self.compile_lambda(&lambda);
+                code.push(Instr::Lambda(lambda));
+            }
+            Expr::BindingUsage(name) => code.push(Instr::LoadBinding {
+                name: hir.names[*name].clone(),
+                range,
+            }),
+            Expr::Block(items) => code.push(Instr::Block(self.compile_items(items))),
+            Expr::Glob(pattern) => code.push(Instr::Push(Val::Str(pattern.clone()))),
+            Expr::Background { expr, command } => code.push(match hir.exprs[*expr] {
+                Expr::FunctionCall { .. } => Instr::Background {
+                    call: self.compile_call(*expr),
+                    command: command.clone(),
+                },
+                _ => Instr::Fail(EvalError::new(
+                    EvalErrorKind::BackgroundNonCommand,
+                    hir.source_map[*expr],
+                )),
+            }),
+            Expr::List(elements) => {
+                for element in elements {
+                    self.compile_expr(*element, code);
+                }
+
+                code.push(Instr::MakeList(elements.len()));
+            }
+            Expr::Record(fields) => {
+                for (_, value) in fields {
+                    self.compile_expr(*value, code);
+                }
+
+                code.push(Instr::MakeRecord(
+                    fields
+                        .iter()
+                        .map(|(name, _)| hir.names[*name].clone())
+                        .collect(),
+                ));
+            }
+            Expr::Match { scrutinee, arms } => {
+                self.compile_expr(*scrutinee, code);
+
+                let arms = arms
+                    .iter()
+                    .map(|(pattern, body)| (*pattern, self.compile_expr_code(*body)))
+                    .collect();
+
+                code.push(Instr::Match {
+                    hir: Rc::clone(hir),
+                    arms,
+                    scrutinee_range: hir.source_map[*scrutinee],
+                });
+            }
+            Expr::For {
+                binding_name,
+                iterable,
+                body,
+            } => {
+                // Iterating over a command goes over the lines it outputs, rather than its exit
+                // status, so the call needs to know to capture them.
+                match hir.exprs[*iterable] {
+                    Expr::FunctionCall { .. } => code.push(Instr::Call {
+                        call: self.compile_call(*iterable),
+                        capture_output: true,
+                    }),
+                    _ => self.compile_expr(*iterable, code),
+                }
+
+                code.push(Instr::For {
+                    binding_name: hir.names[*binding_name].clone(),
+                    body: self.compile_expr_code(*body),
+                    iterable_range: hir.source_map[*iterable],
+                    range,
+                });
+            }
+            Expr::While { condition, body } => code.push(Instr::While {
+                condition: self.compile_expr_code(*condition),
+                body: self.compile_expr_code(*body),
+                condition_range: hir.source_map[*condition],
+                range,
+            }),
+            Expr::Break => code.push(Instr::Fail(EvalError::new(EvalErrorKind::Break, range))),
+            Expr::Continue => {
+                code.push(Instr::Fail(EvalError::new(EvalErrorKind::Continue, range)))
+            }
+            Expr::Atom(text) => code.push(Instr::Push(Val::Str(text.to_string()))),
+            Expr::Number(n) => code.push(Instr::Push(Val::Number(*n))),
+            Expr::Str(s) => code.push(Instr::Push(Val::Str(s.clone()))),
+            Expr::Bool(b) => code.push(Instr::Push(Val::Bool(*b))),
+            Expr::Invalid => code.push(Instr::Fail(EvalError::new(
+                EvalErrorKind::InvalidCode,
+                range,
+            ))),
+        }
+    }
+
+    fn compile_bin_op(
+        &mut self,
+        idx: ExprIdx,
+        op: Op,
+        lhs: ExprIdx,
+        rhs: ExprIdx,
+        code: &mut Vec<Instr>,
+    ) {
+        self.compile_expr(lhs, code);
+
+        match op {
+            Op::And | Op::Or => {
+                let short_circuit = code.len();
+                code.push(Instr::Pop);
+
+                self.compile_expr(rhs, code);
+
+                code[short_circuit] = Instr::ShortCircuit {
+                    op,
+                    lhs_range: self.hir.source_map[lhs],
+                    target: code.len(),
+                };
+            }
+            Op::Add | Op::Sub | Op::Mul | Op::Div => {
+                self.compile_expr(rhs, code);
+                code.push(Instr::Arithmetic {
+                    op,
+                    range: self.hir.source_map[idx],
+                });
+            }
+        }
+    }
+
+    fn compile_if(
+        &mut self,
+        condition: ExprIdx,
+        true_branch: ExprIdx,
+        false_branch: Option<ExprIdx>,
+        code: &mut Vec<Instr>,
+    ) {
+        self.compile_expr(condition, code);
+
+        // Jump targets aren’t known until the branches have been compiled, so placeholders are
+        // patched afterwards.
+        let jump_unless_true = code.len();
+        code.push(Instr::Pop);
+
+        self.compile_expr(true_branch, code);
+
+        let jump_to_end = code.len();
+        code.push(Instr::Pop);
+
+        code[jump_unless_true] = Instr::JumpUnlessTrue {
+            condition_range: self.hir.source_map[condition],
+            target: code.len(),
+        };
+
+        match false_branch {
+            Some(false_branch) => self.compile_expr(false_branch, code),
+            None => code.push(Instr::Push(Val::Nil)),
+        }
+
+        code[jump_to_end] = Instr::Jump(code.len());
+    }
+
+    fn compile_call(&mut self, idx: ExprIdx) -> Rc<Call> {
+        let hir = self.hir;
+
+        let (name, params) = match &hir.exprs[idx] {
+            Expr::FunctionCall { name, params } => (name, params),
+            _ => unreachable!(),
+        };
+
+        let params = params
+            .iter()
+            .map(|param| {
+                // Only unquoted arguments are expanded, so string literals are never treated as
+                // globs and never have their tildes replaced.
+                let word = match &hir.exprs[*param] {
+                    Expr::Glob(pattern) => Some(pattern.clone()),
+                    Expr::Atom(text) => Some(text.to_string()),
+                    _ => None,
+                };
+
+                Param {
+                    word,
+                    code: self.compile_expr_code(*param),
+                    range: hir.source_map[*param],
+                }
+            })
+            .collect();
+
+        Rc::new(Call {
+            name: hir.names[*name].clone(),
+            name_range: hir.source_map.call_name(idx),
+            params,
+            params_range: hir.source_map.call_params(idx),
+            range: hir.source_map[idx],
+        })
+    }
+}
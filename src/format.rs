@@ -0,0 +1,429 @@
+//! Formatting Fjord code in a canonical style.
+//!
+//! Since the syntax tree keeps all whitespace and line breaks, the formatter can work through its
+//! tokens in order, deciding what should go between each pair of neighbouring tokens. Line breaks
+//! from the original code are kept (apart from blank lines being collapsed), whereas spaces are
+//! normalised. Spaces are only ever added between tokens that were written next to each other if
+//! that can’t change how the code lexes or parses.
+
+use crate::lexer::SyntaxKind;
+use crate::parser::{NoErrors, ParseOutput};
+use crate::{SyntaxNode, SyntaxToken};
+
+const INDENT: &str = "    ";
+
+impl ParseOutput<NoErrors> {
+    /// Formats the parsed code in a canonical style, returning the result. Formatting the result
+    /// again gives the same output.
+    ///
+    /// Items and match arms stay on the lines they were written on, but are indented according to
+    /// how deeply they are nested. Blocks spanning several lines are laid out with their braces on
+    /// lines of their own, and runs of blank lines are collapsed into a single blank line.
+    pub fn format(&self) -> String {
+        let mut formatter = Formatter::default();
+
+        for token in self
+            .syntax()
+            .descendants_with_tokens()
+            .filter_map(|element| element.into_token())
+        {
+            formatter.token(token);
+        }
+
+        formatter.finish()
+    }
+}
+
+#[derive(Default)]
+struct Formatter {
+    output: String,
+    prev: Option<SyntaxToken>,
+    // The whitespace and line breaks since the previous token.
+    trivia: Vec<SyntaxToken>,
+    // The indentation of the line each unclosed delimiter was opened on.
+    open_delimiters: Vec<usize>,
+    line_indent: usize,
+}
+
+impl Formatter {
+    fn token(&mut self, token: SyntaxToken) {
+        if matches!(token.kind(), SyntaxKind::Whitespace | SyntaxKind::Eol) {
+            self.trivia.push(token);
+            return;
+        }
+
+        if let Some(prev) = self.prev.take() {
+            self.separate(&prev, &token);
+        }
+
+        self.output.push_str(token.text());
+
+        if is_closing_delimiter(token.kind()) {
+            self.open_delimiters.pop();
+        } else if is_opening_delimiter(token.kind()) {
+            self.open_delimiters.push(self.line_indent);
+        }
+
+        self.trivia.clear();
+        self.prev = Some(token);
+    }
+
+    fn separate(&mut self, prev: &SyntaxToken, token: &SyntaxToken) {
+        let line_breaks = match (opens_multi_line_block(prev), closes_multi_line_block(token)) {
+            (true, _) | (_, true) => 1,
+            (false, false) => self.line_breaks().min(2),
+        };
+
+        if line_breaks > 0 {
+            for _ in 0..line_breaks {
+                self.output.push('\n');
+            }
+
+            // A line starting with a closing delimiter is indented like the line that delimiter
+            // was opened on, whereas everything else is indented one level further.
+            self.line_indent = match self.open_delimiters.last() {
+                Some(indent) if is_closing_delimiter(token.kind()) => *indent,
+                Some(indent) => indent + 1,
+                None => 0,
+            };
+
+            for _ in 0..self.line_indent {
+                self.output.push_str(INDENT);
+            }
+        } else if self.space_between(prev, token) {
+            self.output.push(' ');
+        }
+    }
+
+    fn line_breaks(&self) -> usize {
+        self.trivia
+            .iter()
+            .filter(|token| token.kind() == SyntaxKind::Eol)
+            .map(|token| token.text().replace("\r\n", "\n").len())
+            .sum()
+    }
+
+    fn space_between(&self, prev: &SyntaxToken, token: &SyntaxToken) -> bool {
+        if never_spaced(prev, token) {
+            return false;
+        }
+
+        if !self.trivia.is_empty() {
+            return true;
+        }
+
+        // Tokens inside globs are always written next to each other, since whitespace would end
+        // the glob.
+        if token.parent().kind() == SyntaxKind::Glob {
+            return false;
+        }
+
+        always_spaced(prev, token)
+    }
+
+    fn finish(mut self) -> String {
+        if !self.output.is_empty() {
+            self.output.push('\n');
+        }
+
+        self.output
+    }
+}
+
+fn never_spaced(prev: &SyntaxToken, token: &SyntaxToken) -> bool {
+    matches!(
+        prev.kind(),
+        SyntaxKind::LParen | SyntaxKind::LBracket | SyntaxKind::Dollar
+    ) || matches!(
+        token.kind(),
+        SyntaxKind::RParen | SyntaxKind::RBracket | SyntaxKind::Semicolon
+    ) || (prev.kind() == SyntaxKind::LBrace && token.kind() == SyntaxKind::RBrace)
+        || is_opening_pipe(prev)
+        || is_closing_pipe(token)
+}
+
+fn always_spaced(prev: &SyntaxToken, token: &SyntaxToken) -> bool {
+    is_spaced_operator(prev)
+        || is_spaced_operator(token)
+        || is_keyword(prev.kind())
+        || is_keyword(token.kind())
+        || prev.kind() == SyntaxKind::Semicolon
+        || (prev.kind() == SyntaxKind::LBrace && is_brace_of_block_or_match(prev))
+        || (token.kind() == SyntaxKind::RBrace && is_brace_of_block_or_match(token))
+        || (token.kind() == SyntaxKind::Ampersand
+            && token.parent().kind() == SyntaxKind::Background)
+        || is_last_token_of_lambda_params(prev)
+}
+
+fn is_spaced_operator(token: &SyntaxToken) -> bool {
+    match token.kind() {
+        SyntaxKind::Equals => matches!(
+            token.parent().kind(),
+            SyntaxKind::BindingDef
+                | SyntaxKind::Assignment
+                | SyntaxKind::Alias
+                | SyntaxKind::RecordField
+                | SyntaxKind::RecordFieldPattern
+        ),
+        SyntaxKind::FatArrow => token.parent().kind() == SyntaxKind::MatchArm,
+        SyntaxKind::Plus
+        | SyntaxKind::Minus
+        | SyntaxKind::Star
+        | SyntaxKind::Slash
+        | SyntaxKind::DoubleAmpersand
+        | SyntaxKind::DoublePipe => token.parent().kind() == SyntaxKind::BinOp,
+        _ => false,
+    }
+}
+
+fn is_keyword(kind: SyntaxKind) -> bool {
+    matches!(
+        kind,
+        SyntaxKind::LetKw
+            | SyntaxKind::MutKw
+            | SyntaxKind::ImportKw
+            | SyntaxKind::SourceKw
+            | SyntaxKind::AliasKw
+            | SyntaxKind::IfKw
+            | SyntaxKind::ThenKw
+            | SyntaxKind::ElseKw
+            | SyntaxKind::MatchKw
+            | SyntaxKind::ForKw
+            | SyntaxKind::InKw
+            | SyntaxKind::WhileKw
+            | SyntaxKind::BreakKw
+            | SyntaxKind::ContinueKw
+    )
+}
+
+fn is_opening_delimiter(kind: SyntaxKind) -> bool {
+    matches!(
+        kind,
+        SyntaxKind::LBrace | SyntaxKind::LParen | SyntaxKind::LBracket
+    )
+}
+
+fn is_closing_delimiter(kind: SyntaxKind) -> bool {
+    matches!(
+        kind,
+        SyntaxKind::RBrace | SyntaxKind::RParen | SyntaxKind::RBracket
+    )
+}
+
+fn is_brace_of_block_or_match(token: &SyntaxToken) -> bool {
+    matches!(token.parent().kind(), SyntaxKind::Block | SyntaxKind::Match)
+}
+
+fn opens_multi_line_block(token: &SyntaxToken) -> bool {
+    token.kind() == SyntaxKind::LBrace && is_multi_line_block(&token.parent())
+}
+
+fn closes_multi_line_block(token: &SyntaxToken) -> bool {
+    token.kind() == SyntaxKind::RBrace && is_multi_line_block(&token.parent())
+}
+
+// A block (or match) is laid out over several lines if its contents were split over several lines
+// in the first place.
+fn is_multi_line_block(node: &SyntaxNode) -> bool {
+    matches!(node.kind(), SyntaxKind::Block | SyntaxKind::Match)
+        && node
+            .children_with_tokens()
+            .any(|child| child.kind() == SyntaxKind::Eol)
+}
+
+fn is_opening_pipe(token: &SyntaxToken) -> bool {
+    token.kind() == SyntaxKind::Pipe
+        && token.parent().kind() == SyntaxKind::LambdaParams
+        && token.prev_sibling_or_token().is_none()
+}
+
+fn is_closing_pipe(token: &SyntaxToken) -> bool {
+    token.kind() == SyntaxKind::Pipe
+        && token.parent().kind() == SyntaxKind::LambdaParams
+        && token.prev_sibling_or_token().is_some()
+}
+
+fn is_last_token_of_lambda_params(token: &SyntaxToken) -> bool {
+    token.parent().kind() == SyntaxKind::LambdaParams && token.next_sibling_or_token().is_none()
+}
+
+#[cfg(test)]
+pub(crate) fn check_idempotent(input: &str) {
+    use crate::parser::Parser;
+
+    let parse_output = match Parser::new(input).parse().into_no_errors() {
+        Some(parse_output) => parse_output,
+        None => return,
+    };
+
+    let formatted = parse_output.format();
+    let reparsed = Parser::new(&formatted)
+        .parse()
+        .into_no_errors()
+        .unwrap_or_else(|| panic!("formatting {:?} gave invalid code {:?}", input, formatted));
+
+    assert_eq!(reparsed.format(), formatted, "formatting {:?}", input);
+    assert_eq!(
+        skeleton(&reparsed.syntax()),
+        skeleton(&parse_output.syntax()),
+        "formatting {:?} changed its meaning",
+        input,
+    );
+}
+
+// The syntax tree without any whitespace or line breaks, which formatting shouldn’t change.
+#[cfg(test)]
+fn skeleton(node: &SyntaxNode) -> String {
+    node.descendants_with_tokens()
+        .filter(|element| !matches!(element.kind(), SyntaxKind::Whitespace | SyntaxKind::Eol))
+        .map(|element| match element {
+            rowan::NodeOrToken::Node(node) => {
+                format!("{:?}@{}\n", node.kind(), node.ancestors().count())
+            }
+            rowan::NodeOrToken::Token(token) => format!("{:?} {:?}\n", token.kind(), token.text()),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+    use expect_test::{expect, Expect};
+
+    fn check(input: &str, expected_output: Expect) {
+        let formatted = Parser::new(input)
+            .parse()
+            .into_no_errors()
+            .unwrap()
+            .format();
+        expected_output.assert_eq(&formatted);
+
+        check_idempotent(input);
+    }
+
+    #[test]
+    fn format_nothing() {
+        check("", expect![[""]]);
+    }
+
+    #[test]
+    fn format_spacing_around_equals_sign() {
+        check(
+            "let  a=5\nlet mut b   =  $a\nb=10",
+            expect![[r#"
+                let a = 5
+                let mut b = $a
+                b = 10
+            "#]],
+        );
+    }
+
+    #[test]
+    fn format_spacing_around_operators() {
+        check(
+            "(1   +  2)*3 ||  false",
+            expect![[r#"
+                (1 + 2) * 3 || false
+            "#]],
+        );
+    }
+
+    #[test]
+    fn format_lambda() {
+        check(
+            "let f = | a   b |$a\nlet g = ||   5",
+            expect![[r#"
+                let f = |a b| $a
+                let g = || 5
+            "#]],
+        );
+    }
+
+    #[test]
+    fn format_single_line_block() {
+        check(
+            "{echo a ;  ls}\n{   }",
+            expect![[r#"
+                { echo a; ls }
+                {}
+            "#]],
+        );
+    }
+
+    #[test]
+    fn format_multi_line_block() {
+        check(
+            "let a = { echo a\n        1 + 2 }\nfor x in [1 2] {\nwhile true {   break\n}\n}",
+            expect![[r#"
+                let a = {
+                    echo a
+                    1 + 2
+                }
+                for x in [1 2] {
+                    while true {
+                        break
+                    }
+                }
+            "#]],
+        );
+    }
+
+    #[test]
+    fn format_match() {
+        check(
+            "match $a {\n1=>\"one\"\n  [x ...rest]   => $x\n_ => { $b }}",
+            expect![[r#"
+                match $a {
+                    1 => "one"
+                    [x ...rest] => $x
+                    _ => { $b }
+                }
+            "#]],
+        );
+    }
+
+    #[test]
+    fn collapse_blank_lines() {
+        check(
+            "\n\n\necho a\n\n\n\necho b\r\n\r\n\r\necho c\n\n",
+            expect![[r#"
+                echo a
+
+                echo b
+
+                echo c
+            "#]],
+        );
+    }
+
+    #[test]
+    fn keep_globs_and_binding_usages_together() {
+        check(
+            "ls   *.rs  src/*  $dir   &",
+            expect![[r#"
+                ls *.rs src/* $dir &
+            "#]],
+        );
+    }
+
+    #[test]
+    fn format_records_and_lists() {
+        check(
+            "[ a=1   b = [ 1 2 ] ]",
+            expect![[r#"
+                [a = 1 b = [1 2]]
+            "#]],
+        );
+    }
+
+    #[test]
+    fn format_if() {
+        check(
+            "if $x then{ 1 }else{2}",
+            expect![[r#"
+                if $x then { 1 } else { 2 }
+            "#]],
+        );
+    }
+}
@@ -0,0 +1,277 @@
+//! Classifying the tokens of Fjord code for syntax highlighting.
+
+use crate::env::Env;
+use crate::lexer::SyntaxKind;
+use crate::parser::{ParseOutput, ParseOutputState};
+use crate::SyntaxToken;
+use text_size::TextRange;
+
+/// A span of code that should be highlighted in a particular way.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct HighlightSpan {
+    range: TextRange,
+    kind: HighlightKind,
+}
+
+impl HighlightSpan {
+    /// Returns the range of code the span covers.
+    pub fn range(&self) -> TextRange {
+        self.range
+    }
+
+    /// Returns how the span should be highlighted.
+    pub fn kind(&self) -> HighlightKind {
+        self.kind
+    }
+}
+
+/// The different ways in which code can be highlighted.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum HighlightKind {
+    /// A keyword, including `true` and `false`.
+    Keyword,
+    /// A string literal.
+    String,
+    /// A number literal.
+    Number,
+    /// A usage of a binding, including its dollar sign.
+    BindingUsage,
+    /// The name of a function call that refers to something which can be called: a command, a
+    /// builtin or a lambda.
+    CommandName,
+    /// An operator, such as `+`, `=` or the pipes around the parameters of a lambda.
+    Operator,
+    /// Code that could not be parsed.
+    Error,
+}
+
+impl<State: ParseOutputState> ParseOutput<State> {
+    /// Classifies the tokens of the parsed code for highlighting, returning spans in the order in
+    /// which they appear in the code. Tokens that need no special highlighting, such as bare words
+    /// and punctuation, are not included.
+    ///
+    /// The names of function calls are looked up in `env`, so that calls to commands which exist
+    /// can be highlighted differently from those which do not.
+    pub fn highlight(&self, env: &Env<'_>) -> Vec<HighlightSpan> {
+        self.syntax()
+            .descendants_with_tokens()
+            .filter_map(|element| element.into_token())
+            .filter_map(|token| {
+                let kind = classify(&token, env)?;
+
+                // Binding usages are highlighted as a whole, rather than as a dollar sign and a
+                // name.
+                let range = if kind == HighlightKind::BindingUsage {
+                    token.parent().text_range()
+                } else {
+                    token.text_range()
+                };
+
+                Some(HighlightSpan { range, kind })
+            })
+            .collect()
+    }
+}
+
+fn classify(token: &SyntaxToken, env: &Env<'_>) -> Option<HighlightKind> {
+    let parent = token.parent().kind();
+
+    let kind = match token.kind() {
+        SyntaxKind::LetKw
+        | SyntaxKind::MutKw
+        | SyntaxKind::ImportKw
+        | SyntaxKind::SourceKw
+        | SyntaxKind::AliasKw
+        | SyntaxKind::IfKw
+        | SyntaxKind::ThenKw
+        | SyntaxKind::ElseKw
+        | SyntaxKind::MatchKw
+        | SyntaxKind::ForKw
+        | SyntaxKind::InKw
+        | SyntaxKind::WhileKw
+        | SyntaxKind::BreakKw
+        | SyntaxKind::ContinueKw
+        | SyntaxKind::True
+        | SyntaxKind::False => HighlightKind::Keyword,
+
+        SyntaxKind::StringLiteral => HighlightKind::String,
+        SyntaxKind::Digits => HighlightKind::Number,
+
+        SyntaxKind::Dollar if parent == SyntaxKind::BindingUsage => HighlightKind::BindingUsage,
+
+        // Only the name of a function call is looked up; atoms anywhere else are bare words, even
+        // if a command with the same name happens to exist.
+        SyntaxKind::Atom if parent == SyntaxKind::FunctionCall => {
+            env.get_func_or_command(token.text()).ok()?;
+
+            HighlightKind::CommandName
+        }
+
+        SyntaxKind::Plus
+        | SyntaxKind::Minus
+        | SyntaxKind::Star
+        | SyntaxKind::Slash
+        | SyntaxKind::DoubleAmpersand
+        | SyntaxKind::DoublePipe
+            if parent == SyntaxKind::BinOp =>
+        {
+            HighlightKind::Operator
+        }
+        SyntaxKind::Pipe | SyntaxKind::DoublePipe if parent == SyntaxKind::LambdaParams => {
+            HighlightKind::Operator
+        }
+        SyntaxKind::Ampersand if parent == SyntaxKind::Background => HighlightKind::Operator,
+        SyntaxKind::Equals | SyntaxKind::FatArrow => HighlightKind::Operator,
+
+        SyntaxKind::Error => HighlightKind::Error,
+
+        _ => return None,
+    };
+
+    Some(kind)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+    use expect_test::{expect, Expect};
+
+    fn check_with_env(input: &str, env: &Env<'_>, expected_output: Expect) {
+        let parse_output = Parser::new(input).parse();
+
+        let spans: String = parse_output
+            .highlight(env)
+            .into_iter()
+            .map(|span| {
+                format!(
+                    "{:?}@{:?} {:?}\n",
+                    span.kind(),
+                    span.range(),
+                    &input[span.range()],
+                )
+            })
+            .collect();
+
+        expected_output.assert_eq(&spans);
+    }
+
+    fn check(input: &str, expected_output: Expect) {
+        check_with_env(input, &Env::new(Vec::new()), expected_output);
+    }
+
+    #[test]
+    fn highlight_binding_def() {
+        check(
+            "let mut a = \"hello\"",
+            expect![[r#"
+                Keyword@0..3 "let"
+                Keyword@4..7 "mut"
+                Operator@10..11 "="
+                String@12..19 "\"hello\""
+            "#]],
+        );
+    }
+
+    #[test]
+    fn highlight_binding_usages_as_a_whole() {
+        check(
+            "$a * (10 - $bc)",
+            expect![[r#"
+                BindingUsage@0..2 "$a"
+                Operator@3..4 "*"
+                Number@6..8 "10"
+                Operator@9..10 "-"
+                BindingUsage@11..14 "$bc"
+            "#]],
+        );
+    }
+
+    #[test]
+    fn highlight_lambda_and_match() {
+        check(
+            "|x| match $x { true => 1; _ => 2 }",
+            expect![[r#"
+                Operator@0..1 "|"
+                Operator@2..3 "|"
+                Keyword@4..9 "match"
+                BindingUsage@10..12 "$x"
+                Keyword@15..19 "true"
+                Operator@20..22 "=>"
+                Number@23..24 "1"
+                Operator@28..30 "=>"
+                Number@31..32 "2"
+            "#]],
+        );
+    }
+
+    #[test]
+    fn do_not_highlight_globs_as_operators() {
+        check(
+            "if false then { ls *.rs & }",
+            expect![[r#"
+                Keyword@0..2 "if"
+                Keyword@3..8 "false"
+                Keyword@9..13 "then"
+                Operator@24..25 "&"
+            "#]],
+        );
+    }
+
+    #[test]
+    fn highlight_errors() {
+        check(
+            "let = 5",
+            expect![[r#"
+                Keyword@0..3 "let"
+                Error@4..5 "="
+                Error@6..7 "5"
+            "#]],
+        );
+    }
+
+    #[test]
+    fn highlight_builtins_and_lambdas_as_command_names() {
+        let mut env = Env::new(Vec::new());
+        env.set_binding(
+            "f",
+            Parser::new("|x| $x")
+                .parse()
+                .into_no_errors()
+                .unwrap()
+                .eval(&mut Env::new(Vec::new()))
+                .unwrap(),
+        );
+
+        check_with_env(
+            "jobs\nf 1\ng 2",
+            &env,
+            expect![[r#"
+                CommandName@0..4 "jobs"
+                CommandName@5..6 "f"
+                Number@7..8 "1"
+                Number@11..12 "2"
+            "#]],
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn distinguish_commands_from_bare_words() {
+        use crate::test_support::TempDir;
+
+        let dir = TempDir::new("highlight");
+
+        for name in &["ls", "which"] {
+            dir.create_executable(name);
+        }
+
+        check_with_env(
+            "which ls\nlsd -l",
+            &Env::new(vec![dir.to_path_buf()]),
+            expect![[r#"
+                CommandName@0..5 "which"
+            "#]],
+        );
+    }
+}
@@ -0,0 +1,416 @@
+//! A lowered representation of Fjord code, which is what the interpreter actually evaluates.
+//!
+//! The syntax tree is lossless, so every accessor on it is fallible: the parser always produces a
+//! tree, but parts of it may be missing when the input contains errors. Lowering does the work of
+//! checking that everything is in place once, producing a tree of owned, typed nodes that cannot
+//! be malformed. Nodes are stored in arenas and refer to each other by index, names are interned,
+//! and the text ranges needed for error reporting are kept to one side in a `SourceMap`.
+
+mod lower;
+
+use crate::val::Val;
+use crate::Op;
+use smol_str::SmolStr;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+use std::ops::Index;
+use std::rc::Rc;
+use text_size::TextRange;
+
+/// Fjord code that has been lowered from a syntax tree.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub(crate) struct Hir {
+    pub(crate) root_items: Vec<ItemIdx>,
+    pub(crate) items: Arena<Item>,
+    pub(crate) exprs: Arena<Expr>,
+    pub(crate) patterns: Arena<Pattern>,
+    pub(crate) names: Interner,
+    pub(crate) source_map: SourceMap,
+}
+
+pub(crate) type ItemIdx = Idx<Item>;
+pub(crate) type ExprIdx = Idx<Expr>;
+pub(crate) type PatternIdx = Idx<Pattern>;
+
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum Item {
+    BindingDef {
+        name: Name,
+        is_mutable: bool,
+        expr: ExprIdx,
+    },
+    Assignment {
+        name: Name,
+        expr: ExprIdx,
+    },
+    Import {
+        path: String,
+        is_source: bool,
+    },
+    Alias {
+        name: Name,
+        // Always a `FunctionCall`.
+        call: ExprIdx,
+    },
+    Expr(ExprIdx),
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum Expr {
+    BinOp {
+        op: Op,
+        lhs: ExprIdx,
+        rhs: ExprIdx,
+    },
+    If {
+        condition: ExprIdx,
+        true_branch: ExprIdx,
+        false_branch: Option<ExprIdx>,
+    },
+    FunctionCall {
+        name: Name,
+        params: Vec<ExprIdx>,
+    },
+    Lambda {
+        params: Vec<LambdaParam>,
+        /// `None` if there is no rest parameter, and `Some(None)` if extra arguments are discarded
+        /// (as in `|first ...|`).
+        rest_param: Option<Option<Name>>,
+        body: ExprIdx,
+    },
+    BindingUsage(Name),
+    Block(Vec<ItemIdx>),
+    Glob(String),
+    Background {
+        expr: ExprIdx,
+        // The command as it was written, which is what is shown when listing jobs.
+        command: String,
+    },
+    List(Vec<ExprIdx>),
+    Record(Vec<(Name, ExprIdx)>),
+    Match {
+        scrutinee: ExprIdx,
+        arms: Vec<(PatternIdx, ExprIdx)>,
+    },
+    For {
+        binding_name: Name,
+        iterable: ExprIdx,
+        body: ExprIdx,
+    },
+    While {
+        condition: ExprIdx,
+        body: ExprIdx,
+    },
+    Break,
+    Continue,
+    Atom(SmolStr),
+    Number(i64),
+    Str(String),
+    Bool(bool),
+    /// Stands in for an expression that is present in the syntax tree but could not be lowered,
+    /// such as a number literal too large to fit into an `i64`. Evaluating it is an error.
+    Invalid,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct LambdaParam {
+    pub(crate) name: Name,
+    /// Evaluated each time the lambda is called without an argument for this parameter.
+    pub(crate) default: Option<ExprIdx>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum Pattern {
+    Wildcard,
+    Ty(Name),
+    Binding(Name),
+    Number(i64),
+    Str(String),
+    Bool(bool),
+    List {
+        elements: Vec<PatternIdx>,
+        /// `None` if there is no rest pattern, and `Some(None)` if the rest of the list is
+        /// discarded (as in `[first ...]`).
+        rest: Option<Option<Name>>,
+    },
+    Record(Vec<(Name, PatternIdx)>),
+    /// Like `Expr::Invalid`, matching against it is an error.
+    Invalid,
+}
+
+/// A lambda value, which keeps the code it was defined in alive so that it can be called after
+/// the code that created it has finished evaluating. Partially applied lambdas also hold on to
+/// the arguments they have been given so far.
+///
+/// Two lambdas are only equal if they come from the same place in the same piece of lowered code,
+/// and have the same arguments bound to them.
+#[derive(Clone)]
+pub struct Lambda {
+    hir: Rc<Hir>,
+    expr: ExprIdx,
+    bound_args: Rc<[Val]>,
+}
+
+impl Lambda {
+    pub(crate) fn new(hir: Rc<Hir>, expr: ExprIdx) -> Self {
+        debug_assert!(matches!(hir.exprs[expr], Expr::Lambda { .. }));
+
+        Self {
+            hir,
+            expr,
+            bound_args: Rc::new([]),
+        }
+    }
+
+    pub(crate) fn hir(&self) -> &Rc<Hir> {
+        &self.hir
+    }
+
+    /// Returns the same lambda with `args` bound to it in place of any arguments it already had.
+    pub(crate) fn with_bound_args(&self, args: Vec<Val>) -> Self {
+        Self {
+            hir: Rc::clone(&self.hir),
+            expr: self.expr,
+            bound_args: args.into(),
+        }
+    }
+
+    /// Returns the same lambda without any arguments bound to it.
+    pub(crate) fn unapplied(&self) -> Self {
+        Self::new(Rc::clone(&self.hir), self.expr)
+    }
+
+    pub(crate) fn bound_args(&self) -> &[Val] {
+        &self.bound_args
+    }
+
+    pub(crate) fn params(&self) -> &[LambdaParam] {
+        match &self.hir.exprs[self.expr] {
+            Expr::Lambda { params, .. } => params,
+            _ => unreachable!(),
+        }
+    }
+
+    /// Returns how many arguments the lambda has to be called with, which is every parameter up to
+    /// and including the last one without a default value.
+    pub(crate) fn required_param_count(&self) -> usize {
+        self.params()
+            .iter()
+            .rposition(|param| param.default.is_none())
+            .map_or(0, |idx| idx + 1)
+    }
+
+    pub(crate) fn rest_param(&self) -> Option<Option<Name>> {
+        match &self.hir.exprs[self.expr] {
+            Expr::Lambda { rest_param, .. } => *rest_param,
+            _ => unreachable!(),
+        }
+    }
+
+    pub(crate) fn body(&self) -> ExprIdx {
+        match &self.hir.exprs[self.expr] {
+            Expr::Lambda { body, .. } => *body,
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl PartialEq for Lambda {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.hir, &other.hir)
+            && self.expr == other.expr
+            && self.bound_args == other.bound_args
+    }
+}
+
+impl Eq for Lambda {}
+
+impl Hash for Lambda {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        Rc::as_ptr(&self.hir).hash(state);
+        self.expr.hash(state);
+        self.bound_args.hash(state);
+    }
+}
+
+impl fmt::Debug for Lambda {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut tuple = f.debug_tuple("Lambda");
+        tuple.field(&self.hir.source_map[self.expr]);
+
+        if !self.bound_args.is_empty() {
+            tuple.field(&self.bound_args);
+        }
+
+        tuple.finish()
+    }
+}
+
+/// Where in the source code each part of the lowered code came from.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub(crate) struct SourceMap {
+    items: Vec<TextRange>,
+    exprs: Vec<TextRange>,
+    patterns: Vec<TextRange>,
+    // The name an assignment assigns to.
+    assignment_names: HashMap<ItemIdx, TextRange>,
+    // The string literal holding the path of an import.
+    import_paths: HashMap<ItemIdx, TextRange>,
+    // The name of the function or command being called, and the parameters passed to it.
+    call_names: HashMap<ExprIdx, TextRange>,
+    call_params: HashMap<ExprIdx, TextRange>,
+}
+
+impl SourceMap {
+    pub(crate) fn assignment_name(&self, item: ItemIdx) -> TextRange {
+        self.assignment_names[&item]
+    }
+
+    pub(crate) fn import_path(&self, item: ItemIdx) -> TextRange {
+        self.import_paths[&item]
+    }
+
+    pub(crate) fn call_name(&self, expr: ExprIdx) -> TextRange {
+        self.call_names[&expr]
+    }
+
+    pub(crate) fn call_params(&self, expr: ExprIdx) -> TextRange {
+        self.call_params[&expr]
+    }
+}
+
+impl Index<ItemIdx> for SourceMap {
+    type Output = TextRange;
+
+    fn index(&self, idx: ItemIdx) -> &Self::Output {
+        &self.items[idx.to_usize()]
+    }
+}
+
+impl Index<ExprIdx> for SourceMap {
+    type Output = TextRange;
+
+    fn index(&self, idx: ExprIdx) -> &Self::Output {
+        &self.exprs[idx.to_usize()]
+    }
+}
+
+impl Index<PatternIdx> for SourceMap {
+    type Output = TextRange;
+
+    fn index(&self, idx: PatternIdx) -> &Self::Output {
+        &self.patterns[idx.to_usize()]
+    }
+}
+
+/// An interned name, which can be looked up in the `Interner` of the code it came from.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub(crate) struct Name(u32);
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub(crate) struct Interner {
+    names: Vec<SmolStr>,
+    ids: HashMap<SmolStr, Name>,
+}
+
+impl Interner {
+    pub(crate) fn intern(&mut self, name: &SmolStr) -> Name {
+        if let Some(id) = self.ids.get(name) {
+            return *id;
+        }
+
+        let id = Name(self.names.len() as u32);
+        self.names.push(name.clone());
+        self.ids.insert(name.clone(), id);
+
+        id
+    }
+}
+
+impl Index<Name> for Interner {
+    type Output = SmolStr;
+
+    fn index(&self, name: Name) -> &Self::Output {
+        &self.names[name.0 as usize]
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct Arena<T> {
+    data: Vec<T>,
+}
+
+impl<T> Default for Arena<T> {
+    fn default() -> Self {
+        Self { data: Vec::new() }
+    }
+}
+
+impl<T> Arena<T> {
+    fn alloc(&mut self, value: T) -> Idx<T> {
+        let idx = Idx::new(self.data.len());
+        self.data.push(value);
+
+        idx
+    }
+}
+
+impl<T> Index<Idx<T>> for Arena<T> {
+    type Output = T;
+
+    fn index(&self, idx: Idx<T>) -> &Self::Output {
+        &self.data[idx.to_usize()]
+    }
+}
+
+/// An index into an `Arena`. The type parameter is only there to stop indices into one arena
+/// being used with another.
+pub(crate) struct Idx<T> {
+    raw: u32,
+    _ty: PhantomData<fn() -> T>,
+}
+
+impl<T> Idx<T> {
+    fn new(raw: usize) -> Self {
+        Self {
+            raw: raw as u32,
+            _ty: PhantomData,
+        }
+    }
+
+    fn to_usize(self) -> usize {
+        self.raw as usize
+    }
+}
+
+// These are implemented by hand because deriving them would require `T` to implement them too.
+
+impl<T> Clone for Idx<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Idx<T> {}
+
+impl<T> PartialEq for Idx<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.raw == other.raw
+    }
+}
+
+impl<T> Eq for Idx<T> {}
+
+impl<T> Hash for Idx<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.raw.hash(state);
+    }
+}
+
+impl<T> fmt::Debug for Idx<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Idx({})", self.raw)
+    }
+}
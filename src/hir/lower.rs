@@ -0,0 +1,406 @@
+use super::{Expr, ExprIdx, Hir, Item, ItemIdx, LambdaParam, Pattern, PatternIdx};
+use crate::ast::{self, ExprKind, ItemKind, PatternKind};
+use text_size::TextRange;
+
+impl Hir {
+    /// Lowers a syntax tree. Items are left out if any part of them is missing, which can only
+    /// happen if the tree contains syntax errors. Expressions and patterns that can’t be lowered
+    /// for any other reason, such as number literals too large to fit into an `i64`, are lowered to
+    /// `Invalid` instead, so that evaluating them is an error rather than them silently vanishing.
+    pub(crate) fn lower(root: &ast::Root) -> Self {
+        let mut hir = Self::default();
+        hir.root_items = root
+            .items()
+            .filter_map(|item| hir.lower_item(item))
+            .collect();
+
+        hir
+    }
+
+    /// Lowers a single item without adding it to the root items, returning `None` if any part of
+    /// it is missing.
+    pub(crate) fn lower_item(&mut self, item: ast::Item) -> Option<ItemIdx> {
+        let range = item.text_range();
+
+        match item.kind() {
+            ItemKind::BindingDef(binding_def) => {
+                let name = self.names.intern(&binding_def.binding_name()?);
+                let expr = self.lower_expr(binding_def.expr()?);
+
+                Some(self.alloc_item(
+                    Item::BindingDef {
+                        name,
+                        is_mutable: binding_def.is_mutable(),
+                        expr,
+                    },
+                    range,
+                ))
+            }
+            ItemKind::Assignment(assignment) => {
+                let name_atom = assignment.binding_name()?;
+                let name = self.names.intern(name_atom.text());
+                let expr = self.lower_expr(assignment.expr()?);
+
+                let idx = self.alloc_item(Item::Assignment { name, expr }, range);
+                self.source_map
+                    .assignment_names
+                    .insert(idx, name_atom.text_range());
+
+                Some(idx)
+            }
+            ItemKind::Import(import) => {
+                let path = import.path()?;
+
+                let idx = self.alloc_item(
+                    Item::Import {
+                        path: path.contents().to_string(),
+                        is_source: import.is_source(),
+                    },
+                    range,
+                );
+                self.source_map.import_paths.insert(idx, path.text_range());
+
+                Some(idx)
+            }
+            ItemKind::Alias(alias) => {
+                let name = self.names.intern(alias.name()?.text());
+                let call = self.lower_expr(alias.call()?);
+
+                Some(self.alloc_item(Item::Alias { name, call }, range))
+            }
+            ItemKind::Expr(expr) => {
+                let expr = self.lower_expr(expr);
+                Some(self.alloc_item(Item::Expr(expr), range))
+            }
+        }
+    }
+
+    pub(crate) fn lower_expr(&mut self, expr: ast::Expr) -> ExprIdx {
+        let range = expr.text_range();
+
+        match self.try_lower_expr(expr) {
+            Some(idx) => idx,
+            None => self.alloc_expr(Expr::Invalid, range),
+        }
+    }
+
+    fn try_lower_expr(&mut self, expr: ast::Expr) -> Option<ExprIdx> {
+        let range = expr.text_range();
+
+        let lowered = match expr.kind() {
+            ExprKind::BinOp(bin_op) => Expr::BinOp {
+                op: bin_op.op()?.as_op()?,
+                lhs: self.lower_expr(bin_op.lhs()?),
+                rhs: self.lower_expr(bin_op.rhs()?),
+            },
+            ExprKind::If(if_) => Expr::If {
+                condition: self.lower_expr(if_.condition()?),
+                true_branch: self.lower_expr(if_.true_branch()?),
+                false_branch: if_
+                    .false_branch()
+                    .map(|false_branch| self.lower_expr(false_branch)),
+            },
+            ExprKind::FunctionCall(function_call) => {
+                let name_atom = function_call.name()?;
+                let name = self.names.intern(name_atom.text());
+                let params_range = function_call.params()?.text_range();
+
+                let params = function_call
+                    .param_exprs()?
+                    .map(|param| self.lower_expr(param))
+                    .collect();
+
+                let idx = self.alloc_expr(Expr::FunctionCall { name, params }, range);
+                self.source_map
+                    .call_names
+                    .insert(idx, name_atom.text_range());
+                self.source_map.call_params.insert(idx, params_range);
+
+                return Some(idx);
+            }
+            ExprKind::Lambda(lambda) => Expr::Lambda {
+                params: lambda
+                    .params()?
+                    .map(|(name, default_param)| {
+                        let default = match default_param {
+                            Some(default_param) => Some(self.lower_expr(default_param.value()?)),
+                            None => None,
+                        };
+
+                        Some(LambdaParam {
+                            name: self.names.intern(name.text()),
+                            default,
+                        })
+                    })
+                    .collect::<Option<_>>()?,
+                rest_param: lambda.rest_param().map(|rest_param| {
+                    rest_param
+                        .binding_name()
+                        .map(|name| self.names.intern(&name))
+                }),
+                body: self.lower_expr(lambda.body()?),
+            },
+            ExprKind::BindingUsage(binding_usage) => {
+                Expr::BindingUsage(self.names.intern(&binding_usage.binding_name()?))
+            }
+            ExprKind::Block(block) => Expr::Block(
+                block
+                    .items()
+                    .map(|item| self.lower_item(item))
+                    .collect::<Option<_>>()?,
+            ),
+            ExprKind::Glob(glob) => Expr::Glob(glob.pattern()),
+            ExprKind::Background(background) => {
+                let expr = background.expr()?;
+
+                Expr::Background {
+                    command: expr.text().trim().to_string(),
+                    expr: self.lower_expr(expr),
+                }
+            }
+            ExprKind::List(list) => Expr::List(
+                list.elements()
+                    .map(|element| self.lower_expr(element))
+                    .collect(),
+            ),
+            ExprKind::Record(record) => Expr::Record(
+                record
+                    .fields()
+                    .map(|field| {
+                        let name = self.names.intern(&field.name()?);
+                        Some((name, self.lower_expr(field.value()?)))
+                    })
+                    .collect::<Option<_>>()?,
+            ),
+            ExprKind::Match(match_) => Expr::Match {
+                scrutinee: self.lower_expr(match_.scrutinee()?),
+                arms: match_
+                    .arms()
+                    .map(|arm| {
+                        let pattern = self.lower_pattern(arm.pattern()?);
+                        Some((pattern, self.lower_expr(arm.body()?)))
+                    })
+                    .collect::<Option<_>>()?,
+            },
+            ExprKind::For(for_) => Expr::For {
+                binding_name: self.names.intern(&for_.binding_name()?),
+                iterable: self.lower_expr(for_.iterable()?),
+                body: self.lower_expr(for_.body()?),
+            },
+            ExprKind::While(while_) => Expr::While {
+                condition: self.lower_expr(while_.condition()?),
+                body: self.lower_expr(while_.body()?),
+            },
+            ExprKind::Break => Expr::Break,
+            ExprKind::Continue => Expr::Continue,
+            ExprKind::Atom(atom) => Expr::Atom(atom.text().clone()),
+            // Number literals that don’t fit into an `i64` end up `Invalid`.
+            ExprKind::NumberLiteral(digits) => Expr::Number(digits.text().parse().ok()?),
+            ExprKind::StringLiteral(string_literal) => {
+                Expr::Str(string_literal.contents().to_string())
+            }
+            ExprKind::True => Expr::Bool(true),
+            ExprKind::False => Expr::Bool(false),
+        };
+
+        Some(self.alloc_expr(lowered, range))
+    }
+
+    fn lower_pattern(&mut self, pattern: ast::Pattern) -> PatternIdx {
+        let range = pattern.text_range();
+
+        match self.try_lower_pattern(pattern) {
+            Some(idx) => idx,
+            None => self.alloc_pattern(Pattern::Invalid, range),
+        }
+    }
+
+    fn try_lower_pattern(&mut self, pattern: ast::Pattern) -> Option<PatternIdx> {
+        let range = pattern.text_range();
+
+        let lowered = match pattern.kind() {
+            PatternKind::Wildcard => Pattern::Wildcard,
+            PatternKind::Ty(name) => Pattern::Ty(self.names.intern(name.text())),
+            PatternKind::Binding(name) => Pattern::Binding(self.names.intern(name.text())),
+            PatternKind::NumberLiteral(digits) => Pattern::Number(digits.text().parse().ok()?),
+            PatternKind::StringLiteral(string_literal) => {
+                Pattern::Str(string_literal.contents().to_string())
+            }
+            PatternKind::True => Pattern::Bool(true),
+            PatternKind::False => Pattern::Bool(false),
+            PatternKind::List(list_pattern) => Pattern::List {
+                elements: list_pattern
+                    .elements()
+                    .map(|element| self.lower_pattern(element))
+                    .collect(),
+                rest: list_pattern.rest().map(|rest| {
+                    rest.binding_name()
+                        .map(|binding_name| self.names.intern(&binding_name))
+                }),
+            },
+            PatternKind::Record(record_pattern) => Pattern::Record(
+                record_pattern
+                    .fields()
+                    .map(|field| {
+                        let name = self.names.intern(&field.name()?);
+                        Some((name, self.lower_pattern(field.pattern()?)))
+                    })
+                    .collect::<Option<_>>()?,
+            ),
+        };
+
+        Some(self.alloc_pattern(lowered, range))
+    }
+
+    fn alloc_item(&mut self, item: Item, range: TextRange) -> ItemIdx {
+        self.source_map.items.push(range);
+        self.items.alloc(item)
+    }
+
+    fn alloc_expr(&mut self, expr: Expr, range: TextRange) -> ExprIdx {
+        self.source_map.exprs.push(range);
+        self.exprs.alloc(expr)
+    }
+
+    fn alloc_pattern(&mut self, pattern: Pattern, range: TextRange) -> PatternIdx {
+        self.source_map.patterns.push(range);
+        self.patterns.alloc(pattern)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    fn lower(input: &str) -> Hir {
+        let root = ast::Root::cast(Parser::new(input).parse().syntax()).unwrap();
+        Hir::lower(&root)
+    }
+
+    #[test]
+    fn lower_binding_def() {
+        let hir = lower("let mut a = 5");
+
+        assert_eq!(hir.root_items.len(), 1);
+
+        let (name, expr) = match hir.items[hir.root_items[0]] {
+            Item::BindingDef {
+                name,
+                is_mutable: true,
+                expr,
+            } => (name, expr),
+            _ => panic!(),
+        };
+
+        assert_eq!(hir.names[name], "a");
+        assert_eq!(hir.exprs[expr], Expr::Number(5));
+        assert_eq!(hir.source_map[expr], TextRange::new(12.into(), 13.into()));
+    }
+
+    #[test]
+    fn names_are_interned() {
+        let hir = lower("let x = 1\n$x + $x");
+
+        let (lhs, rhs) = match hir.items[hir.root_items[1]] {
+            Item::Expr(expr) => match hir.exprs[expr] {
+                Expr::BinOp { lhs, rhs, .. } => (lhs, rhs),
+                _ => panic!(),
+            },
+            _ => panic!(),
+        };
+
+        assert_eq!(hir.exprs[lhs], hir.exprs[rhs]);
+    }
+
+    #[test]
+    fn record_ranges_of_call_names_and_params() {
+        let hir = lower("echo a b");
+        let call = match hir.items[hir.root_items[0]] {
+            Item::Expr(expr) => expr,
+            _ => panic!(),
+        };
+
+        assert_eq!(
+            hir.source_map.call_name(call),
+            TextRange::new(0.into(), 4.into()),
+        );
+        assert_eq!(
+            hir.source_map.call_params(call),
+            TextRange::new(5.into(), 8.into()),
+        );
+    }
+
+    #[test]
+    fn lower_number_too_large_to_invalid() {
+        let hir = lower("99999999999999999999");
+
+        assert_eq!(hir.root_items.len(), 1);
+
+        let expr = match hir.items[hir.root_items[0]] {
+            Item::Expr(expr) => expr,
+            _ => panic!(),
+        };
+
+        assert_eq!(hir.exprs[expr], Expr::Invalid);
+        assert_eq!(hir.source_map[expr], TextRange::new(0.into(), 20.into()));
+    }
+
+    #[test]
+    fn lower_call_of_non_name_to_invalid() {
+        let hir = lower("let q = (1)+2");
+
+        assert_eq!(hir.root_items.len(), 1);
+
+        let expr = match hir.items[hir.root_items[0]] {
+            Item::BindingDef { expr, .. } => expr,
+            _ => panic!(),
+        };
+
+        assert_eq!(hir.exprs[expr], Expr::Invalid);
+        assert_eq!(hir.source_map[expr], TextRange::new(8.into(), 13.into()));
+    }
+
+    #[test]
+    fn lower_lambda_with_invalid_body() {
+        let hir = lower("let f = |x y| $x+$y");
+
+        assert_eq!(hir.root_items.len(), 1);
+
+        let lambda = match hir.items[hir.root_items[0]] {
+            Item::BindingDef { expr, .. } => expr,
+            _ => panic!(),
+        };
+
+        let body = match hir.exprs[lambda] {
+            Expr::Lambda { body, .. } => body,
+            _ => panic!(),
+        };
+
+        assert_eq!(hir.exprs[body], Expr::Invalid);
+        assert_eq!(hir.source_map[body], TextRange::new(14.into(), 19.into()));
+    }
+
+    #[test]
+    fn lower_number_pattern_too_large_to_invalid() {
+        let hir = lower("match 1 { 99999999999999999999 => 1 }");
+
+        let arms = match hir.items[hir.root_items[0]] {
+            Item::Expr(expr) => match &hir.exprs[expr] {
+                Expr::Match { arms, .. } => arms,
+                _ => panic!(),
+            },
+            _ => panic!(),
+        };
+
+        assert_eq!(hir.patterns[arms[0].0], Pattern::Invalid);
+    }
+
+    #[test]
+    fn leave_out_incomplete_items() {
+        let hir = lower("let = 5\n10");
+
+        assert_eq!(hir.root_items.len(), 1);
+        assert!(matches!(hir.items[hir.root_items[0]], Item::Expr(_)));
+    }
+}
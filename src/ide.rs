@@ -0,0 +1,902 @@
+//! Queries about Fjord code for editors, such as finding where a binding is defined.
+//!
+//! Code that is being edited is usually incomplete, so these all work on syntax trees that contain
+//! errors. Bindings are resolved lexically: a binding is visible to everything after it in the
+//! block it is defined in, and lambda parameters, loop variables and bindings in match patterns
+//! are visible in the body they belong to.
+
+use crate::ast::{self, ExprKind, ItemKind, PatternKind};
+use crate::env::Env;
+use crate::eval::{expand_tilde, Builtin};
+use crate::lexer::SyntaxKind;
+use crate::parser::{ParseOutput, ParseOutputState};
+use crate::val::Ty;
+use crate::{Op, SyntaxElement, SyntaxNode, SyntaxToken};
+use smol_str::SmolStr;
+use std::collections::HashSet;
+use std::fs;
+use text_size::{TextRange, TextSize};
+
+/// Information about the binding at some position in the code.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct Hover {
+    range: TextRange,
+    text: String,
+}
+
+impl Hover {
+    /// Returns the range of the name being hovered over.
+    pub fn range(&self) -> TextRange {
+        self.range
+    }
+
+    /// Returns a description of the binding, including the kind of value it holds if that is
+    /// known.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+}
+
+/// Something that could be inserted at some position in the code.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct Completion {
+    label: String,
+    kind: CompletionKind,
+    range: TextRange,
+}
+
+impl Completion {
+    /// Returns the text that would be inserted.
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+
+    /// Returns the range of code the completion would replace, which is the part of the name or
+    /// path before the position being completed.
+    pub fn range(&self) -> TextRange {
+        self.range
+    }
+
+    /// Returns what kind of thing the completion refers to.
+    pub fn kind(&self) -> CompletionKind {
+        self.kind
+    }
+}
+
+/// The kinds of things that can be completed.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum CompletionKind {
+    /// A binding visible at the position being completed.
+    Binding,
+    /// A builtin.
+    Builtin,
+    /// A command found in the search path.
+    Command,
+    /// A path to a file or directory.
+    Path,
+}
+
+impl<State: ParseOutputState> ParseOutput<State> {
+    /// Returns the range of the name that defines the binding used at `offset`, if there is a
+    /// binding usage there and its definition can be found.
+    pub fn goto_definition(&self, offset: TextSize) -> Option<TextRange> {
+        let (_, definition) = binding_usage_at(&self.syntax(), offset)?;
+        Some(definition.range)
+    }
+
+    /// Describes the binding used or defined at `offset`.
+    pub fn hover(&self, offset: TextSize) -> Option<Hover> {
+        let root = self.syntax();
+
+        if let Some((atom, definition)) = binding_usage_at(&root, offset) {
+            return Some(Hover {
+                range: atom.text_range(),
+                text: definition.describe(),
+            });
+        }
+
+        let definition = root
+            .token_at_offset(offset)
+            .find_map(|token| definition_named_by(&token))?;
+
+        Some(Hover {
+            range: definition.range,
+            text: definition.describe(),
+        })
+    }
+
+    /// Returns what could be inserted at `offset`.
+    ///
+    /// After a `$` these are the names of bindings, both those defined in the code and those
+    /// already in `env`. Where a command could be called, builtins and the commands in the search
+    /// path of `env` are offered as well. In the arguments of a function call, paths relative to
+    /// the working directory of `env` are offered instead.
+    pub fn completions(&self, offset: TextSize, env: &Env<'_>) -> Vec<Completion> {
+        let root = self.syntax();
+        let token = root.token_at_offset(offset).left_biased();
+
+        let (prefix, position) = match &token {
+            Some(token) if token.kind() == SyntaxKind::Dollar => ("", Position::BindingUsage),
+            Some(token) if token.kind() == SyntaxKind::Atom => {
+                let prefix = &token.text()[..usize::from(offset - token.text_range().start())];
+
+                match token.parent().kind() {
+                    SyntaxKind::BindingUsage => (prefix, Position::BindingUsage),
+                    SyntaxKind::FunctionCall => (prefix, Position::Command),
+                    SyntaxKind::FunctionCallParams => (prefix, Position::Argument),
+                    _ => return Vec::new(),
+                }
+            }
+            Some(token) if is_in_command_position(&root, token) => ("", Position::Command),
+            Some(token) if is_in_argument_position(&root, token) => ("", Position::Argument),
+            Some(_) => return Vec::new(),
+            None => ("", Position::Command),
+        };
+
+        let range = TextRange::new(offset - TextSize::of(prefix), offset);
+
+        if position == Position::Argument {
+            return path_completions(prefix, range, env);
+        }
+
+        let start = token
+            .clone()
+            .map_or_else(|| root.clone().into(), SyntaxElement::Token);
+        let mut seen = HashSet::new();
+
+        // Bindings defined in the code shadow those already in the environment, so they come
+        // first.
+        let binding_names = visible_definitions(start)
+            .into_iter()
+            .map(|definition| definition.name)
+            .chain(env.binding_names());
+
+        let mut completions: Vec<_> = binding_names
+            .filter(|name| name.starts_with(prefix))
+            .filter(|name| seen.insert(name.clone()))
+            .map(|name| Completion {
+                label: name.to_string(),
+                kind: CompletionKind::Binding,
+                range,
+            })
+            .collect();
+
+        if position == Position::Command {
+            let mut builtin_names: Vec<_> = Builtin::ALL
+                .iter()
+                .map(|builtin| builtin.name())
+                .filter(|name| name.starts_with(prefix))
+                .collect();
+            builtin_names.sort_unstable();
+
+            completions.extend(builtin_names.into_iter().map(|name| Completion {
+                label: name.to_string(),
+                kind: CompletionKind::Builtin,
+                range,
+            }));
+
+            completions.extend(
+                env.command_names()
+                    .into_iter()
+                    .filter(|name| name.starts_with(prefix))
+                    .map(|label| Completion {
+                        label,
+                        kind: CompletionKind::Command,
+                        range,
+                    }),
+            );
+        }
+
+        completions
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Position {
+    BindingUsage,
+    Command,
+    Argument,
+}
+
+// Completes the path being typed, which is relative to the working directory unless it is
+// absolute or starts with a tilde. Directories are completed with a trailing slash so that their
+// contents can be completed next, and paths are escaped so that they are read back as one word.
+fn path_completions(prefix: &str, range: TextRange, env: &Env<'_>) -> Vec<Completion> {
+    let prefix = prefix.replace("\\ ", " ");
+
+    let (dir, file_prefix) = match prefix.rfind(std::path::is_separator) {
+        Some(idx) => prefix.split_at(idx + 1),
+        None => ("", prefix.as_str()),
+    };
+
+    let entries = match fs::read_dir(env.working_dir().join(expand_tilde(dir, env))) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+
+    let mut completions: Vec<_> = entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+
+            // Hidden files are only offered if they are asked for explicitly.
+            if !name.starts_with(file_prefix) || (name.starts_with('.') && file_prefix.is_empty()) {
+                return None;
+            }
+
+            let trailing_slash = if entry.path().is_dir() { "/" } else { "" };
+            let label = escape_path(&format!("{}{}{}", dir, name, trailing_slash))?;
+
+            Some(Completion {
+                label,
+                kind: CompletionKind::Path,
+                range,
+            })
+        })
+        .collect();
+
+    completions.sort_by(|a, b| a.label.cmp(&b.label));
+
+    completions
+}
+
+// Atoms can contain escaped spaces but none of the other characters that end them, so paths that
+// contain any of those are quoted instead. Paths that can be written neither way (because they
+// also contain a double quote, which string literals can’t) are left out.
+fn escape_path(path: &str) -> Option<String> {
+    const ENDS_ATOM: &[char] = &[
+        '\n', '\r', '=', '$', '|', '*', '(', ')', '{', '}', '[', ']', ';', '&',
+    ];
+
+    if path.contains(ENDS_ATOM) {
+        if path.contains('"') {
+            return None;
+        }
+
+        return Some(format!("\"{}\"", path));
+    }
+
+    // Double quotes are fine inside atoms, but not around the whole of one, since that would be
+    // read as a string literal.
+    if path.len() > 1
+        && path.starts_with('"')
+        && path.matches('"').count() == 2
+        && path.ends_with('"')
+    {
+        return None;
+    }
+
+    Some(path.replace(' ', "\\ "))
+}
+
+// Whether a command could be called at the end of the given token, such as at the start of a line.
+fn is_in_command_position(root: &SyntaxNode, token: &SyntaxToken) -> bool {
+    match skip_whitespace_backwards(root, token) {
+        Some(token) => matches!(
+            token.kind(),
+            SyntaxKind::Eol
+                | SyntaxKind::Semicolon
+                | SyntaxKind::LBrace
+                | SyntaxKind::LParen
+                | SyntaxKind::Equals
+                | SyntaxKind::FatArrow
+                | SyntaxKind::ThenKw
+                | SyntaxKind::ElseKw
+                | SyntaxKind::DoubleAmpersand
+                | SyntaxKind::DoublePipe
+        ),
+        None => true,
+    }
+}
+
+// Whether an argument to a function call could start at the end of the given token, which is the
+// case after whitespace following the name of the call or any of its arguments.
+fn is_in_argument_position(root: &SyntaxNode, token: &SyntaxToken) -> bool {
+    if token.kind() != SyntaxKind::Whitespace {
+        return false;
+    }
+
+    skip_whitespace_backwards(root, token).is_some_and(|token| {
+        token
+            .parent()
+            .ancestors()
+            .any(|node| node.kind() == SyntaxKind::FunctionCall)
+    })
+}
+
+// Finds the closest token at or before the given one that isn’t whitespace.
+fn skip_whitespace_backwards(root: &SyntaxNode, token: &SyntaxToken) -> Option<SyntaxToken> {
+    let mut token = token.clone();
+
+    // `SyntaxToken::prev_token` gives up at empty nodes (such as the parameters of a call with
+    // none), so the previous token is found by its offset instead.
+    while token.kind() == SyntaxKind::Whitespace {
+        let start = token.text_range().start();
+
+        if start == TextSize::from(0) {
+            return None;
+        }
+
+        token = root.token_at_offset(start).left_biased()?;
+    }
+
+    Some(token)
+}
+
+fn binding_usage_at(root: &SyntaxNode, offset: TextSize) -> Option<(ast::Atom, Definition)> {
+    let binding_usage = root
+        .token_at_offset(offset)
+        .find_map(|token| ast::BindingUsage::cast(token.parent()))?;
+
+    let atom = binding_usage.binding_name_atom()?;
+    let definition = visible_definitions(binding_usage.syntax().clone().into())
+        .into_iter()
+        .find(|definition| definition.name == *atom.text())?;
+
+    Some((atom, definition))
+}
+
+// Finds the definition a token is the name of, if it is the name of a binding definition.
+fn definition_named_by(token: &SyntaxToken) -> Option<Definition> {
+    let binding_def = ast::BindingDef::cast(token.parent())?;
+    let name = binding_def.binding_name_atom()?;
+
+    if name.text_range() == token.text_range() {
+        Some(Definition::new(name, DefinitionKind::Binding(binding_def)))
+    } else {
+        None
+    }
+}
+
+struct Definition {
+    name: SmolStr,
+    range: TextRange,
+    kind: DefinitionKind,
+}
+
+enum DefinitionKind {
+    Binding(ast::BindingDef),
+    LambdaParam,
+    LoopVariable,
+    PatternBinding,
+}
+
+impl Definition {
+    fn new(name: ast::Atom, kind: DefinitionKind) -> Self {
+        Self {
+            name: name.text().clone(),
+            range: name.text_range(),
+            kind,
+        }
+    }
+
+    // Rest patterns and parameters are a single atom, which includes the leading dots.
+    fn rest(name: Option<SmolStr>, range: TextRange, kind: DefinitionKind) -> Option<Self> {
+        let name = name?;
+        let start = range.start() + TextSize::of("...");
+
+        Some(Self {
+            range: TextRange::at(start, TextSize::of(name.as_str())),
+            name,
+            kind,
+        })
+    }
+
+    fn describe(&self) -> String {
+        match &self.kind {
+            DefinitionKind::Binding(binding_def) => {
+                let mutability = if binding_def.is_mutable() { "mut " } else { "" };
+                let ty = binding_def.expr().and_then(infer_ty);
+
+                match ty {
+                    Some(ty) => format!("let {}{}: {}", mutability, self.name, ty.name()),
+                    None => format!("let {}{}", mutability, self.name),
+                }
+            }
+            DefinitionKind::LambdaParam => format!("{} (lambda parameter)", self.name),
+            DefinitionKind::LoopVariable => format!("{} (loop variable)", self.name),
+            DefinitionKind::PatternBinding => format!("{} (bound by pattern)", self.name),
+        }
+    }
+}
+
+// Lists the definitions visible from an element, innermost first, so that a definition comes
+// before any it shadows.
+fn visible_definitions(element: SyntaxElement) -> Vec<Definition> {
+    let mut definitions = Vec::new();
+    let mut child = element;
+    let mut in_lambda = false;
+
+    while let Some(parent) = child.parent() {
+        match parent.kind() {
+            SyntaxKind::Lambda if child.kind() != SyntaxKind::LambdaParams => {
+                in_lambda = true;
+                let lambda = ast::Lambda::cast(parent.clone()).unwrap();
+
+                // The rest parameter comes after all the others, so it goes first.
+                if let Some(rest_param) = lambda.rest_param() {
+                    definitions.extend(Definition::rest(
+                        rest_param.binding_name(),
+                        rest_param.text_range(),
+                        DefinitionKind::LambdaParam,
+                    ));
+                }
+
+                if let Some(params) = lambda.param_atoms() {
+                    let params: Vec<_> = params.collect();
+                    definitions.extend(
+                        params
+                            .into_iter()
+                            .rev()
+                            .map(|param| Definition::new(param, DefinitionKind::LambdaParam)),
+                    );
+                }
+            }
+            SyntaxKind::For => {
+                let for_ = ast::For::cast(parent.clone()).unwrap();
+
+                if is_expr(for_.body(), &child) {
+                    definitions.extend(
+                        for_.binding_name_atom()
+                            .map(|name| Definition::new(name, DefinitionKind::LoopVariable)),
+                    );
+                }
+            }
+            SyntaxKind::MatchArm => {
+                let match_arm = ast::MatchArm::cast(parent.clone()).unwrap();
+
+                if is_expr(match_arm.body(), &child) {
+                    if let Some(pattern) = match_arm.pattern() {
+                        pattern_bindings(pattern, &mut definitions);
+                    }
+                }
+            }
+            SyntaxKind::Block | SyntaxKind::Root => {
+                // Lambdas are called after the binding they are stored in has been defined, so
+                // they can refer to it.
+                if in_lambda {
+                    if let Some(binding_def) =
+                        child.clone().into_node().and_then(ast::BindingDef::cast)
+                    {
+                        if let Some(name) = binding_def.binding_name_atom() {
+                            definitions
+                                .push(Definition::new(name, DefinitionKind::Binding(binding_def)));
+                        }
+                    }
+                }
+
+                let items: Vec<_> = match ast::Block::cast(parent.clone()) {
+                    Some(block) => block.items().collect(),
+                    None => ast::Root::cast(parent.clone()).unwrap().items().collect(),
+                };
+
+                for item in items.into_iter().rev() {
+                    if item.text_range().end() > child.text_range().start() {
+                        continue;
+                    }
+
+                    if let ItemKind::BindingDef(binding_def) = item.kind() {
+                        if let Some(name) = binding_def.binding_name_atom() {
+                            definitions
+                                .push(Definition::new(name, DefinitionKind::Binding(binding_def)));
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        child = parent.into();
+    }
+
+    definitions
+}
+
+fn is_expr(expr: Option<ast::Expr>, element: &SyntaxElement) -> bool {
+    expr.is_some_and(|expr| expr.text_range() == element.text_range())
+}
+
+fn pattern_bindings(pattern: ast::Pattern, definitions: &mut Vec<Definition>) {
+    match pattern.kind() {
+        PatternKind::Binding(name) => {
+            definitions.push(Definition::new(name, DefinitionKind::PatternBinding))
+        }
+        PatternKind::List(list_pattern) => {
+            for element in list_pattern.elements() {
+                pattern_bindings(element, definitions);
+            }
+
+            if let Some(rest) = list_pattern.rest() {
+                definitions.extend(Definition::rest(
+                    rest.binding_name(),
+                    rest.text_range(),
+                    DefinitionKind::PatternBinding,
+                ));
+            }
+        }
+        PatternKind::Record(record_pattern) => {
+            for field in record_pattern.fields() {
+                if let Some(pattern) = field.pattern() {
+                    pattern_bindings(pattern, definitions);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+// Works out what type of value an expression evaluates to without evaluating it, where that is
+// possible.
+fn infer_ty(expr: ast::Expr) -> Option<Ty> {
+    match expr.kind() {
+        ExprKind::NumberLiteral(_) => Some(Ty::Number),
+        ExprKind::StringLiteral(_) => Some(Ty::Str),
+        ExprKind::True | ExprKind::False => Some(Ty::Bool),
+        ExprKind::Lambda(_) => Some(Ty::Lambda),
+        ExprKind::List(_) => Some(Ty::List),
+        ExprKind::Record(_) => Some(Ty::Record),
+        ExprKind::Background(_) => Some(Ty::Job),
+        ExprKind::BinOp(bin_op) => match bin_op.op()?.as_op()? {
+            Op::And | Op::Or => Some(Ty::Bool),
+            Op::Add | Op::Sub | Op::Mul | Op::Div => Some(Ty::Number),
+        },
+        ExprKind::BindingUsage(binding_usage) => {
+            let name = binding_usage.binding_name()?;
+            let definition = visible_definitions(binding_usage.syntax().clone().into())
+                .into_iter()
+                .find(|definition| definition.name == name)?;
+
+            match definition.kind {
+                DefinitionKind::Binding(binding_def) => infer_ty(binding_def.expr()?),
+                _ => None,
+            }
+        }
+        ExprKind::Block(block) => match block.items().last() {
+            Some(item) => match item.kind() {
+                ItemKind::Expr(expr) => infer_ty(expr),
+                ItemKind::BindingDef(_) | ItemKind::Assignment(_) | ItemKind::Alias(_) => {
+                    Some(Ty::Nil)
+                }
+                ItemKind::Import(_) => None,
+            },
+            None => Some(Ty::Nil),
+        },
+        ExprKind::If(if_) => {
+            let true_branch_ty = infer_ty(if_.true_branch()?)?;
+            let false_branch_ty = match if_.false_branch() {
+                Some(false_branch) => infer_ty(false_branch)?,
+                None => Ty::Nil,
+            };
+
+            if true_branch_ty == false_branch_ty {
+                Some(true_branch_ty)
+            } else {
+                None
+            }
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+    use crate::test_support::TempDir;
+    use crate::val::Val;
+
+    // Removes a marker character from the input, returning where it was.
+    fn take_marker(input: &mut String, marker: char) -> Option<TextSize> {
+        let offset = input.find(marker)?;
+        input.remove(offset);
+
+        Some(TextSize::from(offset as u32))
+    }
+
+    // The cursor position is marked with a `^` in the input.
+    fn parse_with_cursor(input: &str) -> (ParseOutput<crate::parser::ContainsErrors>, TextSize) {
+        let mut input = input.to_string();
+        let offset = take_marker(&mut input, '^').unwrap();
+
+        (Parser::new(&input).parse(), offset)
+    }
+
+    // The name the cursor should go to is marked with an `@` before it.
+    fn check_goto_definition(input: &str) {
+        let mut input = input.to_string();
+        let expected = take_marker(&mut input, '@');
+        let (parse_output, offset) = parse_with_cursor(&input);
+
+        assert_eq!(
+            parse_output
+                .goto_definition(offset)
+                .map(|range| range.start()),
+            expected,
+        );
+    }
+
+    fn check_hover(input: &str, expected: &str) {
+        let (parse_output, offset) = parse_with_cursor(input);
+        assert_eq!(parse_output.hover(offset).unwrap().text(), expected);
+    }
+
+    fn completion_labels(input: &str, kind: CompletionKind) -> Vec<String> {
+        completion_labels_with_env(input, &Env::new(Vec::new()), kind)
+    }
+
+    fn completion_labels_with_env(input: &str, env: &Env<'_>, kind: CompletionKind) -> Vec<String> {
+        let (parse_output, offset) = parse_with_cursor(input);
+
+        parse_output
+            .completions(offset, env)
+            .into_iter()
+            .filter(|completion| completion.kind() == kind)
+            .map(|completion| completion.label().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn goto_binding_def() {
+        check_goto_definition("let @a = 5\necho $^a");
+    }
+
+    #[test]
+    fn goto_most_recent_binding_def() {
+        check_goto_definition("let a = 1\nlet b = 2\nlet @a = 3\n$a^ + $b");
+    }
+
+    #[test]
+    fn goto_lambda_param() {
+        check_goto_definition("let x = 1\nlet f = |@x y| $x^ + $y");
+    }
+
+    #[test]
+    fn do_not_goto_binding_defined_in_inner_block() {
+        check_goto_definition("{ let a = 5 }\n$a^");
+    }
+
+    #[test]
+    fn goto_binding_def_in_outer_block() {
+        check_goto_definition("let @a = 5\n{ let b = 1\n  $a^ }");
+    }
+
+    #[test]
+    fn do_not_goto_binding_defined_later() {
+        check_goto_definition("$a^\nlet a = 5");
+    }
+
+    #[test]
+    fn goto_binding_def_being_defined_from_lambda() {
+        check_goto_definition("let @f = |n| $f^");
+    }
+
+    #[test]
+    fn goto_loop_variable() {
+        check_goto_definition("for @x in [1 2] { echo $x^ }");
+    }
+
+    #[test]
+    fn goto_rest_param() {
+        check_goto_definition("|first ...@rest| echo $rest^");
+    }
+
+    #[test]
+    fn goto_pattern_binding() {
+        check_goto_definition("match [1 2] { [first ...@rest] => $rest^ }");
+    }
+
+    #[test]
+    fn goto_definition_with_syntax_errors() {
+        check_goto_definition("let @a = 5\nlet = )\necho $a^");
+    }
+
+    #[test]
+    fn hover_over_binding_usage() {
+        check_hover("let a = 5\n$a^", "let a: Number");
+        check_hover("let mut s = \"hi\"\n$s^", "let mut s: Str");
+        check_hover("let b = $x && true\n$b^", "let b: Bool");
+    }
+
+    #[test]
+    fn hover_over_binding_def() {
+        check_hover("let f^ = |x| $x", "let f: Lambda");
+    }
+
+    #[test]
+    fn hover_follows_binding_usages() {
+        check_hover("let a = [1 2]\nlet b = $a\n$b^", "let b: List");
+    }
+
+    #[test]
+    fn hover_over_binding_of_unknown_kind() {
+        check_hover("let a = ls\n$a^", "let a");
+    }
+
+    #[test]
+    fn hover_over_lambda_param() {
+        check_hover("|x| $x^", "x (lambda parameter)");
+    }
+
+    #[test]
+    fn complete_bindings_after_dollar() {
+        assert_eq!(
+            completion_labels(
+                "let abc = 1\nlet abd = 2\nlet x = 3\necho $ab^",
+                CompletionKind::Binding
+            ),
+            ["abd", "abc"],
+        );
+        assert_eq!(
+            completion_labels("let a = 1\n|x| $^", CompletionKind::Binding),
+            ["x", "a"],
+        );
+    }
+
+    #[test]
+    fn complete_shadowed_binding_once() {
+        assert_eq!(
+            completion_labels("let a = 1\nlet a = 2\n$^", CompletionKind::Binding),
+            ["a"],
+        );
+    }
+
+    #[test]
+    fn do_not_complete_in_arguments() {
+        assert!(completion_labels("let abc = 1\necho ab^", CompletionKind::Binding).is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn complete_commands() {
+        let dir = TempDir::new("ide-commands");
+
+        for name in &["grep", "git", "ls"] {
+            dir.create_executable(name);
+        }
+
+        let env = Env::new(vec![dir.to_path_buf()]);
+        let labels = |input| {
+            let (parse_output, offset) = parse_with_cursor(input);
+
+            parse_output
+                .completions(offset, &env)
+                .into_iter()
+                .map(|completion| (completion.label().to_string(), completion.kind()))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            labels("let gi = 1\ngi^"),
+            [
+                ("gi".to_string(), CompletionKind::Binding),
+                ("git".to_string(), CompletionKind::Command),
+            ],
+        );
+        assert_eq!(
+            labels("echo a\n^"),
+            [
+                ("bg".to_string(), CompletionKind::Builtin),
+                ("fg".to_string(), CompletionKind::Builtin),
+                ("jobs".to_string(), CompletionKind::Builtin),
+                ("range".to_string(), CompletionKind::Builtin),
+                ("wait".to_string(), CompletionKind::Builtin),
+                ("git".to_string(), CompletionKind::Command),
+                ("grep".to_string(), CompletionKind::Command),
+                ("ls".to_string(), CompletionKind::Command),
+            ],
+        );
+        assert!(labels("echo ^")
+            .iter()
+            .all(|(_, kind)| *kind == CompletionKind::Path));
+    }
+
+    #[test]
+    fn complete_builtins() {
+        assert_eq!(
+            completion_labels(
+                "let rad = 1
+ra^",
+                CompletionKind::Builtin
+            ),
+            ["range"],
+        );
+        assert!(completion_labels("echo $ra^", CompletionKind::Builtin).is_empty());
+    }
+
+    #[test]
+    fn complete_bindings_in_env() {
+        let mut env = Env::new(Vec::new());
+        env.set_binding("greeting", Val::Str("hi".to_string()));
+        env.set_binding("green", Val::Number(0));
+
+        let child = env.create_child();
+
+        assert_eq!(
+            completion_labels_with_env(
+                "let grey = 1
+echo $gre^",
+                &child,
+                CompletionKind::Binding
+            ),
+            ["grey", "green", "greeting"],
+        );
+    }
+
+    #[test]
+    fn complete_paths_in_arguments() {
+        use std::fs;
+
+        let dir = TempDir::new("ide-paths");
+        fs::create_dir(dir.join("src")).unwrap();
+
+        for name in &["notes.txt", "my file", ".hidden", "src/main.fj"] {
+            fs::write(dir.join(name), "").unwrap();
+        }
+
+        let mut env = Env::new(Vec::new());
+        env.set_working_dir(dir.to_path_buf());
+
+        let labels = |input| completion_labels_with_env(input, &env, CompletionKind::Path);
+
+        assert_eq!(labels("cat ^"), ["my\\ file", "notes.txt", "src/"]);
+        assert_eq!(labels("cat n^ a"), ["notes.txt"]);
+        assert_eq!(labels("cat a ^"), ["my\\ file", "notes.txt", "src/"]);
+        assert_eq!(labels("cat my\\ ^"), ["my\\ file"]);
+        assert_eq!(labels("cat src/^"), ["src/main.fj"]);
+        assert_eq!(labels("cat .^"), [".hidden"]);
+
+        let absolute = format!("cat {}/s^", dir.display());
+        assert_eq!(labels(&absolute), [format!("{}/src/", dir.display())]);
+
+        assert!(labels("ca^").is_empty());
+        assert!(labels("cat $^").is_empty());
+    }
+
+    #[test]
+    fn quote_completed_paths_that_would_not_be_one_atom() {
+        use std::fs;
+
+        let dir = TempDir::new("ide-quoting");
+
+        for name in &[
+            "a&b", "c;d", "e*f", "g(h)", "i$j", "k[l]", "m \"n", "\"o\"", "p\"q&r",
+        ] {
+            fs::write(dir.join(name), "").unwrap();
+        }
+
+        let mut env = Env::new(Vec::new());
+        env.set_working_dir(dir.to_path_buf());
+
+        assert_eq!(
+            completion_labels_with_env("cat ^", &env, CompletionKind::Path),
+            ["\"a&b\"", "\"c;d\"", "\"e*f\"", "\"g(h)\"", "\"i$j\"", "\"k[l]\"", "m\\ \"n",],
+        );
+    }
+
+    #[test]
+    fn complete_paths_in_home_dir() {
+        use std::fs;
+
+        let dir = TempDir::new("ide-home");
+        fs::create_dir(dir.join("Documents")).unwrap();
+
+        let mut env = Env::new(Vec::new());
+        env.set_binding("HOME", Val::Str(dir.display().to_string()));
+
+        assert_eq!(
+            completion_labels_with_env("cat ~/Doc^", &env, CompletionKind::Path),
+            ["~/Documents/"],
+        );
+    }
+
+    #[test]
+    fn completions_replace_text_before_cursor() {
+        let (parse_output, offset) = parse_with_cursor("let abc = 1\necho $ab^c");
+        let completions = parse_output.completions(offset, &Env::new(Vec::new()));
+
+        assert_eq!(
+            completions[0].range(),
+            TextRange::new(TextSize::from(18), TextSize::from(20)),
+        );
+    }
+}
@@ -7,6 +7,18 @@ pub(crate) enum SyntaxKind {
     #[token("let")]
     LetKw,
 
+    #[token("mut")]
+    MutKw,
+
+    #[token("import")]
+    ImportKw,
+
+    #[token("source")]
+    SourceKw,
+
+    #[token("alias")]
+    AliasKw,
+
     #[token("if")]
     IfKw,
 
@@ -16,7 +28,25 @@ pub(crate) enum SyntaxKind {
     #[token("else")]
     ElseKw,
 
-    #[regex(r"([^\n\r =$|*(){}]|\\ )+")]
+    #[token("match")]
+    MatchKw,
+
+    #[token("for")]
+    ForKw,
+
+    #[token("in")]
+    InKw,
+
+    #[token("while")]
+    WhileKw,
+
+    #[token("break")]
+    BreakKw,
+
+    #[token("continue")]
+    ContinueKw,
+
+    #[regex(r"([^\n\r =$|*(){}\[\];&]|\\ )+")]
     Atom,
 
     #[regex("[0-9]+", priority = 2)]
@@ -34,12 +64,27 @@ pub(crate) enum SyntaxKind {
     #[token("=")]
     Equals,
 
+    #[token("=>")]
+    FatArrow,
+
     #[token("$")]
     Dollar,
 
     #[token("|")]
     Pipe,
 
+    #[token("||")]
+    DoublePipe,
+
+    #[token("&&")]
+    DoubleAmpersand,
+
+    #[token("&")]
+    Ampersand,
+
+    #[token(";")]
+    Semicolon,
+
     #[token("+")]
     Plus,
 
@@ -64,6 +109,12 @@ pub(crate) enum SyntaxKind {
     #[token("}")]
     RBrace,
 
+    #[token("[")]
+    LBracket,
+
+    #[token("]")]
+    RBracket,
+
     #[regex(" +")]
     Whitespace,
 
@@ -76,14 +127,32 @@ pub(crate) enum SyntaxKind {
     // Compound variants
     Root,
     BindingDef,
+    Assignment,
+    Import,
+    Alias,
     BinOp,
     If,
     FunctionCall,
     FunctionCallParams,
     Lambda,
     LambdaParams,
+    DefaultParam,
+    RestParam,
     BindingUsage,
     Block,
+    Glob,
+    Background,
+    List,
+    Record,
+    RecordField,
+    Match,
+    MatchArm,
+    ListPattern,
+    RestPattern,
+    RecordPattern,
+    RecordFieldPattern,
+    For,
+    While,
 }
 
 impl From<SyntaxKind> for rowan::SyntaxKind {
@@ -92,6 +161,52 @@ impl From<SyntaxKind> for rowan::SyntaxKind {
     }
 }
 
+impl SyntaxKind {
+    pub(crate) fn can_start_expr(self) -> bool {
+        matches!(
+            self,
+            Self::IfKw
+                | Self::MatchKw
+                | Self::ForKw
+                | Self::WhileKw
+                | Self::BreakKw
+                | Self::ContinueKw
+                | Self::Atom
+                | Self::Digits
+                | Self::StringLiteral
+                | Self::True
+                | Self::False
+                | Self::Dollar
+                | Self::Pipe
+                | Self::LParen
+                | Self::LBrace
+                | Self::LBracket
+        )
+    }
+
+    /// Whether this is a keyword that is only treated as one where an item or expression starts.
+    /// When it is passed to a function or command (as in `echo in the source`) it is a bare word.
+    pub(crate) fn is_contextual_keyword(self) -> bool {
+        matches!(
+            self,
+            Self::MutKw
+                | Self::ImportKw
+                | Self::SourceKw
+                | Self::AliasKw
+                | Self::MatchKw
+                | Self::ForKw
+                | Self::InKw
+                | Self::WhileKw
+                | Self::BreakKw
+                | Self::ContinueKw
+        )
+    }
+
+    pub(crate) fn can_start_arg(self) -> bool {
+        self.can_start_expr() || self.is_contextual_keyword()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -137,6 +252,26 @@ mod tests {
         test_join_to_atom("let", SyntaxKind::LetKw);
     }
 
+    #[test]
+    fn lex_mut_keyword() {
+        test_join_to_atom("mut", SyntaxKind::MutKw);
+    }
+
+    #[test]
+    fn lex_import_keyword() {
+        test_join_to_atom("import", SyntaxKind::ImportKw);
+    }
+
+    #[test]
+    fn lex_source_keyword() {
+        test_join_to_atom("source", SyntaxKind::SourceKw);
+    }
+
+    #[test]
+    fn lex_alias_keyword() {
+        test_join_to_atom("alias", SyntaxKind::AliasKw);
+    }
+
     #[test]
     fn lex_if_keyword() {
         test_join_to_atom("if", SyntaxKind::IfKw);
@@ -152,6 +287,36 @@ mod tests {
         test_join_to_atom("else", SyntaxKind::ElseKw);
     }
 
+    #[test]
+    fn lex_match_keyword() {
+        test_join_to_atom("match", SyntaxKind::MatchKw);
+    }
+
+    #[test]
+    fn lex_for_keyword() {
+        test_join_to_atom("for", SyntaxKind::ForKw);
+    }
+
+    #[test]
+    fn lex_in_keyword() {
+        test_join_to_atom("in", SyntaxKind::InKw);
+    }
+
+    #[test]
+    fn lex_while_keyword() {
+        test_join_to_atom("while", SyntaxKind::WhileKw);
+    }
+
+    #[test]
+    fn lex_break_keyword() {
+        test_join_to_atom("break", SyntaxKind::BreakKw);
+    }
+
+    #[test]
+    fn lex_continue_keyword() {
+        test_join_to_atom("continue", SyntaxKind::ContinueKw);
+    }
+
     #[test]
     fn lex_atom() {
         test_join_to_atom("/bin/åbç123défg456", SyntaxKind::Atom);
@@ -187,6 +352,11 @@ mod tests {
         test_separate_from_atom("=", SyntaxKind::Equals);
     }
 
+    #[test]
+    fn lex_fat_arrow() {
+        test_separate_from_atom("=>", SyntaxKind::FatArrow);
+    }
+
     #[test]
     fn lex_dollar_sign() {
         test_separate_from_atom("$", SyntaxKind::Dollar);
@@ -197,6 +367,26 @@ mod tests {
         test_separate_from_atom("|", SyntaxKind::Pipe);
     }
 
+    #[test]
+    fn lex_double_pipe() {
+        test_separate_from_atom("||", SyntaxKind::DoublePipe);
+    }
+
+    #[test]
+    fn lex_double_ampersand() {
+        test_separate_from_atom("&&", SyntaxKind::DoubleAmpersand);
+    }
+
+    #[test]
+    fn lex_ampersand() {
+        test_separate_from_atom("&", SyntaxKind::Ampersand);
+    }
+
+    #[test]
+    fn lex_semicolon() {
+        test_separate_from_atom(";", SyntaxKind::Semicolon);
+    }
+
     #[test]
     fn lex_plus() {
         test_join_to_atom("+", SyntaxKind::Plus);
@@ -238,6 +428,16 @@ mod tests {
         test_separate_from_atom("}", SyntaxKind::RBrace);
     }
 
+    #[test]
+    fn lex_l_bracket() {
+        test_separate_from_atom("[", SyntaxKind::LBracket);
+    }
+
+    #[test]
+    fn lex_r_bracket() {
+        test_separate_from_atom("]", SyntaxKind::RBracket);
+    }
+
     #[test]
     fn lex_spaces() {
         test_separate_from_atom("  ", SyntaxKind::Whitespace);
@@ -253,21 +453,3 @@ mod tests {
         test_separate_from_atom("\r\r\n\r\n\n", SyntaxKind::Eol);
     }
 }
-
-impl SyntaxKind {
-    pub(crate) fn can_start_expr(self) -> bool {
-        match self {
-            Self::IfKw
-            | Self::Atom
-            | Self::Digits
-            | Self::StringLiteral
-            | Self::True
-            | Self::False
-            | Self::Dollar
-            | Self::Pipe
-            | Self::LParen
-            | Self::LBrace => true,
-            _ => false,
-        }
-    }
-}
@@ -8,11 +8,17 @@
 )]
 
 mod ast;
+mod format;
+mod hir;
 mod lang;
 mod lexer;
+#[cfg(test)]
+mod test_support;
 
 pub mod env;
 pub mod eval;
+pub mod highlight;
+pub mod ide;
 pub mod parser;
 pub mod val;
 
@@ -20,12 +26,14 @@ type SyntaxNode = rowan::SyntaxNode<lang::Lang>;
 type SyntaxToken = rowan::SyntaxToken<lang::Lang>;
 type SyntaxElement = rowan::NodeOrToken<SyntaxNode, SyntaxToken>;
 
-#[derive(Debug, Copy, Clone, PartialEq)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
 enum Op {
     Add,
     Sub,
     Mul,
     Div,
+    And,
+    Or,
 }
 
 mod private {
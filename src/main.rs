@@ -0,0 +1,367 @@
+use fjord::env::Env;
+use fjord::parser::{ItemOutcome, Parser};
+use fjord::val::Val;
+use rustyline::completion::{Completer, Pair};
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::validate::{ValidationContext, ValidationResult, Validator};
+use rustyline::{Config, Context, Editor};
+use std::convert::TryFrom;
+use std::ffi::OsString;
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use text_size::TextSize;
+
+// The binary doesn’t see the library’s test-only modules, so the fixtures are included directly.
+#[cfg(test)]
+#[allow(dead_code)]
+#[path = "test_support.rs"]
+mod test_support;
+
+fn main() -> io::Result<()> {
+    let args: Vec<_> = std::env::args_os().skip(1).collect();
+
+    match args.split_first() {
+        Some((command, paths)) if command == "fmt" => fmt(paths),
+        _ => repl(),
+    }
+}
+
+// Formats the given files in place, or standard input to standard output if no files are given.
+fn fmt(paths: &[OsString]) -> io::Result<()> {
+    if paths.is_empty() {
+        let mut input = String::new();
+        io::stdin().read_to_string(&mut input)?;
+
+        match format(&input, Path::new("<stdin>")) {
+            Some(formatted) => print!("{}", formatted),
+            None => std::process::exit(1),
+        }
+
+        return Ok(());
+    }
+
+    let mut all_formatted = true;
+
+    for path in paths {
+        let path = Path::new(path);
+        let input = fs::read_to_string(path)?;
+
+        match format(&input, path) {
+            Some(formatted) if formatted != input => fs::write(path, formatted)?,
+            Some(_) => {}
+            None => all_formatted = false,
+        }
+    }
+
+    if !all_formatted {
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
+
+// Code with syntax errors is left alone, since it isn’t clear what it should look like.
+fn format(input: &str, path: &Path) -> Option<String> {
+    let parse_output = Parser::new(input).parse();
+
+    for error in parse_output.errors() {
+        eprintln!("Syntax error in {}: {:?}", path.display(), error);
+    }
+
+    Some(parse_output.into_no_errors()?.format())
+}
+
+fn repl() -> io::Result<()> {
+    let search_path = std::env::var_os("PATH")
+        .map(|path| std::env::split_paths(&path).collect())
+        .unwrap_or_default();
+
+    let mut env = Env::new(search_path);
+
+    // Modules that aren’t found relative to the file importing them are looked for in the
+    // directories listed in FJORD_PATH.
+    if let Some(module_search_path) = std::env::var_os("FJORD_PATH") {
+        env.set_module_search_path(std::env::split_paths(&module_search_path).collect());
+    }
+
+    // Environment variables are made available as bindings, so that e.g. `$HOME` works as it
+    // would in other shells.
+    for (name, value) in std::env::vars() {
+        env.set_binding(&name, Val::Str(value));
+    }
+
+    // Ctrl-C should interrupt whatever is currently being evaluated, rather than exiting the
+    // shell. SIGTERM does exit it: straight away if it’s waiting for input, and otherwise once
+    // evaluation has stopped.
+    let cancellation_token = env.cancellation_token();
+    let at_prompt = Arc::new(AtomicBool::new(false));
+
+    #[cfg(unix)]
+    {
+        let at_prompt = Arc::clone(&at_prompt);
+
+        cancellation_token.install_signal_handlers(move |signal| {
+            if at_prompt.load(Ordering::SeqCst) {
+                std::process::exit(128 + signal);
+            }
+        })?;
+    }
+
+    // Anything the rc file binds (helper lambdas, a custom prompt and so on) is available from the
+    // first prompt onwards.
+    if let Some(rc_path) = rc_path() {
+        load_rc(&rc_path, &mut env);
+    }
+
+    let config = Config::builder().auto_add_history(true).build();
+    let mut editor = Editor::with_config(config).map_err(io::Error::other)?;
+    editor.set_helper(Some(Helper { env }));
+
+    let history_path = history_path();
+
+    // There is no history file the first time the shell is run.
+    if let Some(history_path) = &history_path {
+        let _ = editor.load_history(history_path);
+    }
+
+    loop {
+        for job in editor.helper_mut().unwrap().env.reap_finished_jobs() {
+            println!("{}", job);
+        }
+
+        let prompt = prompt(&mut editor.helper_mut().unwrap().env);
+
+        // The signal handler only exits by itself while we’re waiting for input, so a termination
+        // signal received before then is acted on here.
+        at_prompt.store(true, Ordering::SeqCst);
+
+        if let Some(signal) = cancellation_token.termination_signal() {
+            drop(editor);
+            std::process::exit(128 + signal);
+        }
+
+        let input = editor.readline(&prompt);
+        at_prompt.store(false, Ordering::SeqCst);
+
+        let input = match input {
+            Ok(input) => input,
+            // Ctrl-C discards whatever has been typed so far.
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => return Ok(()),
+            Err(error) => return Err(io::Error::other(error)),
+        };
+
+        // Failing to save history isn’t worth interrupting the user for.
+        if let Some(history_path) = &history_path {
+            let _ = editor.append_history(history_path);
+        }
+
+        match eval(&input, None, &mut editor.helper_mut().unwrap().env) {
+            // Commands print their own output, so there’s no need to show their exit status.
+            Some(Val::Nil) | Some(Val::ExitStatus(_)) | None => {}
+            Some(val) => match val.display_repr() {
+                Some(display_repr) => println!("{}", display_repr),
+                None => println!("{:?}", val),
+            },
+        }
+    }
+}
+
+// Errors are printed rather than returned, mentioning the file they come from if there is one.
+fn eval(input: &str, path: Option<&Path>, env: &mut Env<'static>) -> Option<Val> {
+    let location = path
+        .map(|path| format!(" in {}", path.display()))
+        .unwrap_or_default();
+
+    let parse_output = Parser::new(input).parse();
+
+    for error in parse_output.errors() {
+        eprintln!("Syntax error{}: {:?}", location, error);
+    }
+
+    let parse_output = parse_output.into_no_errors()?;
+
+    env.cancellation_token().reset();
+
+    match parse_output.compile().run(env) {
+        Ok(val) => Some(val),
+        Err(error) => {
+            eprintln!("Error{}: {:?}", location, error);
+            None
+        }
+    }
+}
+
+// Unlike input typed at the prompt, the rc file is evaluated even if parts of it contain syntax
+// errors, so that one typo doesn’t lose everything else it sets up. A missing rc file is ignored.
+fn load_rc(path: &Path, env: &mut Env<'static>) {
+    let rc = match fs::read_to_string(path) {
+        Ok(rc) => rc,
+        Err(_) => return,
+    };
+
+    env.cancellation_token().reset();
+
+    for outcome in Parser::new(&rc).parse().eval_skipping_errors(env) {
+        match outcome {
+            ItemOutcome::Evaluated(Ok(_)) => {}
+            ItemOutcome::Evaluated(Err(error)) => {
+                eprintln!("Error in {}: {:?}", path.display(), error)
+            }
+            ItemOutcome::Skipped(errors) => {
+                for error in errors {
+                    eprintln!("Syntax error in {}: {:?}", path.display(), error);
+                }
+            }
+        }
+    }
+}
+
+// The prompt is whatever the `prompt` lambda returns, if one has been defined (usually in the rc
+// file). Values without a textual representation, and errors, fall back to the default prompt.
+fn prompt(env: &mut Env<'static>) -> String {
+    const DEFAULT_PROMPT: &str = "→ ";
+
+    if !matches!(env.binding("prompt"), Some(Val::Lambda(_))) {
+        return DEFAULT_PROMPT.to_string();
+    }
+
+    eval("prompt", None, env)
+        .and_then(|val| val.display_repr())
+        .unwrap_or_else(|| DEFAULT_PROMPT.to_string())
+}
+
+// The rc file is kept in the user’s config directory, e.g. in ~/.config/fjord/rc.fj on Linux.
+fn rc_path() -> Option<PathBuf> {
+    Some(dirs::config_dir()?.join("fjord").join("rc.fj"))
+}
+
+// History is kept in the user’s data directory, e.g. in ~/.local/share/fjord/history on Linux.
+fn history_path() -> Option<PathBuf> {
+    let dir = dirs::data_dir()?.join("fjord");
+    fs::create_dir_all(&dir).ok()?;
+
+    Some(dir.join("history"))
+}
+
+// The line editor holds on to the environment so that it can complete the names of bindings
+// defined in earlier lines.
+struct Helper {
+    env: Env<'static>,
+}
+
+impl Completer for Helper {
+    type Candidate = Pair;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let offset = TextSize::try_from(pos).unwrap();
+        let completions = Parser::new(line).parse().completions(offset, &self.env);
+
+        let start = completions
+            .first()
+            .map_or(pos, |completion| completion.range().start().into());
+
+        let candidates = completions
+            .into_iter()
+            .map(|completion| Pair {
+                display: completion.label().to_string(),
+                replacement: completion.label().to_string(),
+            })
+            .collect();
+
+        Ok((start, candidates))
+    }
+}
+
+// Input is only evaluated once it is complete, so that pressing enter partway through a block
+// starts a new line instead.
+impl Validator for Helper {
+    fn validate(&self, ctx: &mut ValidationContext<'_>) -> rustyline::Result<ValidationResult> {
+        if Parser::new(ctx.input()).parse().is_incomplete() {
+            Ok(ValidationResult::Incomplete)
+        } else {
+            Ok(ValidationResult::Valid(None))
+        }
+    }
+}
+
+impl Hinter for Helper {
+    type Hint = String;
+}
+
+impl Highlighter for Helper {}
+
+impl rustyline::Helper for Helper {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+
+    // Loads an rc file written to a directory of its own.
+    fn env_with_rc(name: &str, rc: &str) -> Env<'static> {
+        let dir = TempDir::new(&format!("rc-{}", name));
+
+        let rc_path = dir.join("rc.fj");
+        fs::write(&rc_path, rc).unwrap();
+
+        let mut env = Env::new(Vec::new());
+        load_rc(&rc_path, &mut env);
+
+        env
+    }
+
+    #[test]
+    fn rc_defines_bindings_and_aliases() {
+        let mut env = env_with_rc(
+            "bindings",
+            "let greeting = \"hi\"\nlet add = |a b| $a + $b\nalias inc = add 1",
+        );
+
+        assert_eq!(env.binding("greeting"), Some(Val::Str("hi".to_string())));
+        assert_eq!(eval("inc 2", None, &mut env), Some(Val::Number(3)));
+    }
+
+    #[test]
+    fn rc_items_after_syntax_error_are_still_evaluated() {
+        let env = env_with_rc("syntax-error", "let a = 1\nlet = 2\nlet b = 3");
+
+        assert_eq!(env.binding("a"), Some(Val::Number(1)));
+        assert_eq!(env.binding("b"), Some(Val::Number(3)));
+    }
+
+    #[test]
+    fn prompt_renders_result_of_prompt_lambda() {
+        let mut env = env_with_rc("prompt", "let n = 3\nlet prompt = || [$n \">\"]");
+
+        assert_eq!(prompt(&mut env), "[3 >]");
+    }
+
+    #[test]
+    fn failing_prompt_falls_back_to_default() {
+        let mut env = env_with_rc("failing-prompt", "let prompt = || $does_not_exist");
+        assert_eq!(prompt(&mut env), "→ ");
+
+        let mut env = env_with_rc("lambda-prompt", "let prompt = || |x| $x");
+        assert_eq!(prompt(&mut env), "→ ");
+    }
+
+    #[test]
+    fn missing_rc_file_is_ignored() {
+        let mut env = Env::new(Vec::new());
+        load_rc(Path::new("/does/not/exist/rc.fj"), &mut env);
+
+        assert_eq!(env, Env::new(Vec::new()));
+        assert_eq!(prompt(&mut env), "→ ");
+    }
+}
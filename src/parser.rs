@@ -4,15 +4,20 @@ mod error;
 pub use error::SyntaxError;
 
 pub(crate) mod expr;
+mod incremental;
 pub(crate) mod item;
+pub(crate) mod pattern;
+pub use incremental::TextEdit;
 
 use crate::ast::Root;
 use crate::env::Env;
-use crate::eval::EvalError;
+use crate::eval::{EvalError, Program};
+use crate::hir::Hir;
 use crate::lexer::{Lexeme, Lexer, SyntaxKind};
 use crate::val::Val;
 use crate::SyntaxNode;
 use rowan::{GreenNode, GreenNodeBuilder};
+use std::rc::Rc;
 use text_size::TextRange;
 
 #[cfg(test)]
@@ -68,11 +73,22 @@ impl ParseOutput<NoErrors> {
     /// with syntax errors is likely to both lead to confusing errors, and because this adds a lot
     /// of complexity to the interpreter.
     pub fn eval(&self, env: &mut Env<'_>) -> Result<Val, EvalError> {
+        self.lower().eval(env)
+    }
+
+    /// Compiles the parsed syntax tree to bytecode, which can then be run with the same results
+    /// as evaluating it directly. This is also only implemented when no errors are present, for
+    /// the same reasons as `eval`.
+    pub fn compile(&self) -> Program {
+        Program::compile(&self.lower())
+    }
+
+    pub(crate) fn lower(&self) -> Rc<Hir> {
         // The parser always emits a syntax tree with a Root node at the top, so we can safely
         // unwrap.
         let root = Root::cast(self.syntax()).unwrap();
 
-        root.eval(env)
+        Rc::new(Hir::lower(&root))
     }
 }
 
@@ -95,6 +111,137 @@ impl ParseOutput<ContainsErrors> {
     pub fn errors(&self) -> &[SyntaxError] {
         &self.state.0
     }
+
+    /// Returns whether the code ends partway through a block, a match, a list or record,
+    /// parentheses or a string literal, in which case more input could finish it. An interactive
+    /// shell can use this to decide whether to ask for another line before evaluating anything.
+    pub fn is_incomplete(&self) -> bool {
+        let root = self.syntax();
+        let end = root.text_range().end();
+
+        // Blocks and matches are the only nodes the parser finishes without an error when it runs
+        // out of input, so they have to be checked for their closing brace.
+        let unclosed_brace = root.descendants().any(|node| {
+            let has_child = |kind| {
+                node.children_with_tokens()
+                    .any(|child| child.kind() == kind)
+            };
+
+            matches!(node.kind(), SyntaxKind::Block | SyntaxKind::Match)
+                && has_child(SyntaxKind::LBrace)
+                && !has_child(SyntaxKind::RBrace)
+        });
+
+        let tokens = || {
+            root.descendants_with_tokens()
+                .filter_map(|element| element.into_token())
+        };
+
+        // Missing closing parentheses and brackets are reported as errors. These only mean more
+        // input is needed if the parser ran out of input looking for them, rather than coming
+        // across something else (which it would have turned into an error token).
+        let ran_out_of_input = tokens()
+            .last()
+            .is_some_and(|token| token.kind() != SyntaxKind::Error);
+
+        let unclosed_paren_or_bracket = ran_out_of_input
+            && self.errors().iter().any(|error| {
+                error.range.end() == end
+                    && matches!(
+                        error.message,
+                        "expected right parenthesis" | "expected right bracket"
+                    )
+            });
+
+        // A quote that doesn’t start a string literal can only be one with no closing quote after
+        // it, since the lexer would otherwise have lexed everything up to that as a string.
+        let unterminated_string = tokens().any(|token| {
+            token.kind() != SyntaxKind::StringLiteral && token.text().starts_with('"')
+        });
+
+        unclosed_brace || unclosed_paren_or_bracket || unterminated_string
+    }
+
+    /// Evaluates every item that parsed without errors, skipping those that didn’t. This lets a
+    /// script with a typo in one place still run everything else.
+    ///
+    /// One outcome is returned per item, in order. An item is skipped if it contains any syntax
+    /// errors, and syntax errors between items count towards the item before them (since that is
+    /// what the parser was in the middle of). Any syntax errors before the first item get an
+    /// outcome of their own. Evaluation stops early if it is interrupted.
+    pub fn eval_skipping_errors(&self, env: &mut Env<'_>) -> Vec<ItemOutcome> {
+        // The parser always emits a syntax tree with a Root node at the top, so we can safely
+        // unwrap.
+        let root = Root::cast(self.syntax()).unwrap();
+        let items: Vec<_> = root.items().collect();
+
+        let mut item_errors = vec![Vec::new(); items.len()];
+        let mut leading_errors = Vec::new();
+
+        for error in self.errors() {
+            let item_idx = items
+                .iter()
+                .position(|item| item.text_range().intersect(error.range).is_some())
+                .or_else(|| {
+                    items
+                        .iter()
+                        .rposition(|item| item.text_range().start() <= error.range.start())
+                });
+
+            match item_idx {
+                Some(idx) => item_errors[idx].push(*error),
+                None => leading_errors.push(*error),
+            }
+        }
+
+        let mut hir = Hir::default();
+
+        let lowered: Vec<_> = items
+            .into_iter()
+            .zip(item_errors)
+            .map(|(item, errors)| {
+                if errors.is_empty() && !item.contains_error_tokens() {
+                    hir.lower_item(item).ok_or(errors)
+                } else {
+                    Err(errors)
+                }
+            })
+            .collect();
+
+        let hir = Rc::new(hir);
+        let mut outcomes = Vec::new();
+
+        if !leading_errors.is_empty() {
+            outcomes.push(ItemOutcome::Skipped(leading_errors));
+        }
+
+        for item in lowered {
+            match item {
+                Ok(item) => {
+                    let result = hir.eval_root_item(item, env);
+                    let interrupted = matches!(&result, Err(error) if error.is_interrupted());
+
+                    outcomes.push(ItemOutcome::Evaluated(result));
+
+                    if interrupted {
+                        break;
+                    }
+                }
+                Err(errors) => outcomes.push(ItemOutcome::Skipped(errors)),
+            }
+        }
+
+        outcomes
+    }
+}
+
+/// What happened to an item when evaluating a `ParseOutput` that contains errors.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum ItemOutcome {
+    /// The item was evaluated, with the given result.
+    Evaluated(Result<Val, EvalError>),
+    /// The item was not evaluated because of the given syntax errors.
+    Skipped(Vec<SyntaxError>),
 }
 
 /// Parses Fjord code.
@@ -104,6 +251,7 @@ pub struct Parser {
     builder: GreenNodeBuilder<'static>,
     errors: Vec<SyntaxError>,
     last_lexeme_range: TextRange,
+    last_lexeme_kind: Option<SyntaxKind>,
 }
 
 impl Parser {
@@ -117,6 +265,7 @@ impl Parser {
             builder: GreenNodeBuilder::new(),
             errors: Vec::new(),
             last_lexeme_range: TextRange::default(),
+            last_lexeme_kind: None,
         }
     }
 
@@ -140,6 +289,17 @@ impl Parser {
         self.builder.token(lexeme.kind.into(), lexeme.text);
 
         self.last_lexeme_range = lexeme.range;
+        self.last_lexeme_kind = Some(lexeme.kind);
+    }
+
+    // Contextual keywords are bare words when they are passed to a function or command, so they go
+    // into the tree as atoms.
+    fn bump_as_atom(&mut self) {
+        let lexeme = self.lexemes.pop().unwrap();
+        self.builder.token(SyntaxKind::Atom.into(), lexeme.text);
+
+        self.last_lexeme_range = lexeme.range;
+        self.last_lexeme_kind = Some(SyntaxKind::Atom);
     }
 
     fn skip(&mut self, kinds: &'static [SyntaxKind]) {
@@ -164,6 +324,23 @@ impl Parser {
         self.skip(&[SyntaxKind::Whitespace, SyntaxKind::Eol]);
     }
 
+    // Used to distinguish assignments from other items, records (and record patterns) from lists,
+    // and parameters with default values from those without, all of which start with a name
+    // followed by an equals sign.
+    fn at_name_followed_by_equals(&self) -> bool {
+        if self.peek() != Some(SyntaxKind::Atom) {
+            return false;
+        }
+
+        let mut idx = 1;
+
+        while self.lookahead(idx) == Some(SyntaxKind::Whitespace) {
+            idx += 1;
+        }
+
+        self.lookahead(idx) == Some(SyntaxKind::Equals)
+    }
+
     fn error(&mut self, message: &'static str) {
         match self.peek() {
             Some(SyntaxKind::Eol) | None => {}
@@ -172,6 +349,7 @@ impl Parser {
                 self.builder.token(SyntaxKind::Error.into(), lexeme.text);
 
                 self.last_lexeme_range = lexeme.range;
+                self.last_lexeme_kind = Some(SyntaxKind::Error);
             }
         }
 
@@ -203,6 +381,10 @@ impl Parser {
 
             match self.peek() {
                 Some(SyntaxKind::Eol) => self.bump(),
+                Some(SyntaxKind::Semicolon) => {
+                    self.bump();
+                    self.skip_ws_and_eol();
+                }
                 None => break,
                 _ => self.error("expected end of line"),
             }
@@ -230,6 +412,7 @@ impl Parser {
         };
 
         expected_output.assert_eq(&parse_output.debug_tree());
+        crate::format::check_idempotent(input);
     }
 }
 
@@ -243,6 +426,52 @@ mod tests {
         let parse_output = parser.parse();
 
         expected_output.assert_eq(&parse_output.debug_tree());
+        crate::format::check_idempotent(input);
+    }
+
+    fn check_incomplete(input: &str, is_incomplete: bool) {
+        assert_eq!(
+            Parser::new(input).parse().is_incomplete(),
+            is_incomplete,
+            "{:?}",
+            input,
+        );
+    }
+
+    #[test]
+    fn detect_incomplete_input() {
+        for input in &[
+            "{ ls",
+            "let a = {\n  echo a\n",
+            "if $x then { 1 } else {",
+            "match $x {\n1 => 2",
+            "[1 2",
+            "[a = 1\n",
+            "echo (ls",
+            "(1 +\n",
+            "echo \"hello",
+            "echo \"hello\nworld",
+            "{ |a\n",
+        ] {
+            check_incomplete(input, true);
+        }
+    }
+
+    #[test]
+    fn detect_complete_input() {
+        for input in &[
+            "",
+            "ls",
+            "{ ls }\n",
+            "let a = [1 2] ; (3)",
+            "echo \"hello\"",
+            "echo hello\"",
+            "}",
+            "(1\n2",
+            "let = 5",
+        ] {
+            check_incomplete(input, false);
+        }
     }
 
     #[test]
@@ -289,4 +518,91 @@ ls $b"#,
                         Atom@30..31 "b""#]],
         );
     }
+
+    #[test]
+    fn parse_items_separated_by_semicolons() {
+        test(
+            "cd dir; ls;\nls",
+            expect![[r#"
+                Root@0..14
+                  FunctionCall@0..6
+                    Atom@0..2 "cd"
+                    Whitespace@2..3 " "
+                    FunctionCallParams@3..6
+                      Atom@3..6 "dir"
+                  Semicolon@6..7 ";"
+                  Whitespace@7..8 " "
+                  FunctionCall@8..10
+                    Atom@8..10 "ls"
+                    FunctionCallParams@10..10
+                  Semicolon@10..11 ";"
+                  Eol@11..12 "\n"
+                  FunctionCall@12..14
+                    Atom@12..14 "ls"
+                    FunctionCallParams@14..14"#]],
+        );
+    }
+
+    fn eval_skipping_errors(input: &str) -> (Vec<ItemOutcome>, Env<'static>) {
+        let mut env = Env::new(Vec::new());
+        let outcomes = Parser::new(input).parse().eval_skipping_errors(&mut env);
+
+        (outcomes, env)
+    }
+
+    #[test]
+    fn eval_skipping_errors_without_errors() {
+        let (outcomes, _) = eval_skipping_errors("let a = 1\n$a + 1");
+
+        assert_eq!(
+            outcomes,
+            [
+                ItemOutcome::Evaluated(Ok(Val::Nil)),
+                ItemOutcome::Evaluated(Ok(Val::Number(2))),
+            ],
+        );
+    }
+
+    #[test]
+    fn eval_skipping_errors_skips_broken_items() {
+        let parse_output = Parser::new("let a = 1\nlet = 5\nlet b = $a + 1").parse();
+        assert_eq!(parse_output.errors().len(), 3);
+
+        let mut env = Env::new(Vec::new());
+
+        assert_eq!(
+            parse_output.eval_skipping_errors(&mut env),
+            [
+                ItemOutcome::Evaluated(Ok(Val::Nil)),
+                ItemOutcome::Skipped(parse_output.errors().to_vec()),
+                ItemOutcome::Evaluated(Ok(Val::Nil)),
+            ],
+        );
+        assert_eq!(env.get_binding(&"b".into()), Some(Val::Number(2)));
+    }
+
+    #[test]
+    fn eval_skipping_errors_keeps_going_after_eval_errors() {
+        let (outcomes, _) = eval_skipping_errors("$nope\n5");
+
+        assert!(matches!(outcomes[0], ItemOutcome::Evaluated(Err(_))));
+        assert_eq!(outcomes[1], ItemOutcome::Evaluated(Ok(Val::Number(5))));
+    }
+
+    #[test]
+    fn eval_skipping_errors_attributes_errors_between_items_to_previous_item() {
+        let (outcomes, _) = eval_skipping_errors("1 ) 2\n3");
+
+        assert_eq!(
+            outcomes,
+            [
+                ItemOutcome::Skipped(vec![SyntaxError {
+                    message: "expected end of line",
+                    range: TextRange::new(2.into(), 3.into()),
+                }]),
+                ItemOutcome::Evaluated(Ok(Val::Number(2))),
+                ItemOutcome::Evaluated(Ok(Val::Number(3))),
+            ],
+        );
+    }
 }
@@ -6,3 +6,15 @@ pub struct SyntaxError {
     pub(super) message: &'static str,
     pub(super) range: TextRange,
 }
+
+impl SyntaxError {
+    /// Returns a description of what went wrong.
+    pub fn message(&self) -> &'static str {
+        self.message
+    }
+
+    /// Returns the range of the input the error is located at.
+    pub fn range(&self) -> TextRange {
+        self.range
+    }
+}
@@ -16,6 +16,11 @@ fn parse_expr_bp(p: &mut Parser, min_bp: u8, in_func_call_params: bool) {
     p.skip_ws();
 
     let checkpoint = p.builder.checkpoint();
+
+    // Only commands can be run in the background, so we keep track of whether what we’ve parsed so
+    // far is a function call. An atom outside of a call’s parameters is the name of one.
+    let mut is_call = !in_func_call_params && p.peek() == Some(SyntaxKind::Atom);
+
     parse_one_expr(p, in_func_call_params);
 
     p.skip_ws();
@@ -23,16 +28,63 @@ fn parse_expr_bp(p: &mut Parser, min_bp: u8, in_func_call_params: bool) {
     loop {
         let op = loop {
             match p.peek() {
-                Some(kind) if kind.can_start_expr() => break VirtualOp::Application,
+                Some(kind) if kind.can_start_arg() => break VirtualOp::Application,
+                Some(SyntaxKind::DoublePipe)
+                    if (is_call || in_func_call_params) && at_double_pipe_lambda_arg(p, 0) =>
+                {
+                    break VirtualOp::Application
+                }
+                Some(SyntaxKind::DoubleAmpersand) => break VirtualOp::Op(Op::And),
+                Some(SyntaxKind::DoublePipe) => break VirtualOp::Op(Op::Or),
+                // A star separated from the preceding expression by whitespace that is either
+                // directly followed by more of a glob (as in `ls *.rs`) or by nothing that could be
+                // multiplied (as in `ls *`) starts a glob parameter rather than a multiplication.
+                Some(SyntaxKind::Star)
+                    if p.last_lexeme_kind == Some(SyntaxKind::Whitespace)
+                        && glob_len(p, 0).is_some() =>
+                {
+                    break VirtualOp::Application
+                }
+                // A trailing `&` runs the command before it in the background, so it’s only
+                // handled by the outermost call. Nothing can come after it apart from the end of
+                // the item.
+                Some(SyntaxKind::Ampersand) => {
+                    if min_bp > 0 {
+                        return;
+                    }
+
+                    if is_call {
+                        p.builder
+                            .start_node_at(checkpoint, SyntaxKind::Background.into());
+                        p.bump();
+                        p.builder.finish_node();
+                    } else {
+                        p.error("expected command before ampersand");
+                    }
+
+                    p.skip_ws();
+
+                    match p.peek() {
+                        Some(SyntaxKind::Eol)
+                        | Some(SyntaxKind::Semicolon)
+                        | Some(SyntaxKind::RBrace)
+                        | None => {}
+                        _ => p.error("expected end of item after ampersand"),
+                    }
+
+                    return;
+                }
                 Some(SyntaxKind::Plus) => break VirtualOp::Op(Op::Add),
                 Some(SyntaxKind::Minus) => break VirtualOp::Op(Op::Sub),
                 Some(SyntaxKind::Star) => break VirtualOp::Op(Op::Mul),
                 Some(SyntaxKind::Slash) => break VirtualOp::Op(Op::Div),
                 Some(SyntaxKind::RParen)
                 | Some(SyntaxKind::RBrace)
+                | Some(SyntaxKind::RBracket)
                 | Some(SyntaxKind::ThenKw)
                 | Some(SyntaxKind::ElseKw)
                 | Some(SyntaxKind::Eol)
+                | Some(SyntaxKind::Semicolon)
                 | None => return,
                 Some(_) => p.error("expected operator"),
             }
@@ -55,6 +107,8 @@ fn parse_expr_bp(p: &mut Parser, min_bp: u8, in_func_call_params: bool) {
             parse_expr_bp(p, right_bp, true);
             p.skip_ws();
 
+            is_call = true;
+
             if !in_func_call_params {
                 p.builder.finish_node();
                 p.builder.finish_node();
@@ -69,23 +123,46 @@ fn parse_expr_bp(p: &mut Parser, min_bp: u8, in_func_call_params: bool) {
             parse_expr_bp(p, right_bp, in_func_call_params);
 
             p.builder.finish_node();
+
+            is_call = false;
         }
     }
 }
 
 fn parse_one_expr(p: &mut Parser, in_func_call_params: bool) {
+    if in_func_call_params {
+        if let Some(len) = glob_len(p, 0) {
+            parse_glob(p, len);
+            return;
+        }
+
+        if p.peek().is_some_and(SyntaxKind::is_contextual_keyword) {
+            p.bump_as_atom();
+            return;
+        }
+    }
+
     match p.peek() {
         Some(SyntaxKind::IfKw) => parse_if(p),
+        Some(SyntaxKind::MatchKw) => parse_match(p),
+        Some(SyntaxKind::ForKw) => parse_for(p),
+        Some(SyntaxKind::WhileKw) => parse_while(p),
         Some(SyntaxKind::Atom) => parse_atom(p, in_func_call_params),
         Some(SyntaxKind::Digits)
         | Some(SyntaxKind::StringLiteral)
         | Some(SyntaxKind::True)
-        | Some(SyntaxKind::False) => p.bump(),
-        Some(SyntaxKind::Pipe) => parse_lambda(p),
+        | Some(SyntaxKind::False)
+        | Some(SyntaxKind::BreakKw)
+        | Some(SyntaxKind::ContinueKw) => p.bump(),
+        Some(SyntaxKind::Pipe) | Some(SyntaxKind::DoublePipe) => parse_lambda(p),
         Some(SyntaxKind::Dollar) => parse_binding_usage(p),
         Some(SyntaxKind::LParen) => {
             p.bump();
+
+            // Like lists, parentheses can span several lines.
+            p.skip_ws_and_eol();
             parse_expr_bp(p, 0, false);
+            p.skip_ws_and_eol();
 
             if p.peek() == Some(SyntaxKind::RParen) {
                 p.bump();
@@ -94,6 +171,7 @@ fn parse_one_expr(p: &mut Parser, in_func_call_params: bool) {
             }
         }
         Some(SyntaxKind::LBrace) => parse_block(p),
+        Some(SyntaxKind::LBracket) => parse_list_or_record(p),
         _ => p.error("expected expression"),
     }
 }
@@ -121,20 +199,145 @@ pub(crate) fn parse_if(p: &mut Parser) {
         p.error("expected block");
     }
 
-    p.skip_ws();
+    // The else branch is optional, so we only consume the whitespace after the true branch if it’s
+    // followed by one. Otherwise it belongs to whatever comes after the if-expression.
+    if p.peek() == Some(SyntaxKind::Whitespace) && p.lookahead(1) == Some(SyntaxKind::ElseKw) {
+        p.skip_ws();
+    }
 
     if p.peek() == Some(SyntaxKind::ElseKw) {
         p.bump();
         p.skip_ws();
+
+        match p.peek() {
+            Some(SyntaxKind::LBrace) => parse_block(p),
+            Some(SyntaxKind::IfKw) => parse_if(p),
+            _ => p.error("expected block or if keyword"),
+        }
+    }
+
+    p.builder.finish_node();
+}
+
+pub(crate) fn parse_match(p: &mut Parser) {
+    assert_eq!(p.peek(), Some(SyntaxKind::MatchKw));
+
+    p.builder.start_node(SyntaxKind::Match.into());
+    p.bump();
+    p.skip_ws();
+
+    parse_head_expr(p);
+    p.skip_ws();
+
+    if p.peek() == Some(SyntaxKind::LBrace) {
+        p.bump();
+    } else {
+        p.error("expected left brace");
+    }
+
+    loop {
+        p.skip_ws_and_eol();
+
+        match p.peek() {
+            Some(SyntaxKind::RBrace) => {
+                p.bump();
+                break;
+            }
+            None => break,
+            _ => {}
+        }
+
+        parse_match_arm(p);
+        p.skip_ws();
+
+        match p.peek() {
+            Some(SyntaxKind::Eol) | Some(SyntaxKind::Semicolon) => p.bump(),
+            Some(SyntaxKind::RBrace) => {
+                p.bump();
+                break;
+            }
+            None => break,
+            _ => p.error("expected end of line"),
+        }
+    }
+
+    p.builder.finish_node();
+}
+
+pub(crate) fn parse_for(p: &mut Parser) {
+    assert_eq!(p.peek(), Some(SyntaxKind::ForKw));
+
+    p.builder.start_node(SyntaxKind::For.into());
+    p.bump();
+    p.skip_ws();
+
+    if p.peek() == Some(SyntaxKind::Atom) {
+        p.bump();
+    } else {
+        p.error("expected binding name");
+    }
+
+    p.skip_ws();
+
+    if p.peek() == Some(SyntaxKind::InKw) {
+        p.bump();
     } else {
-        p.error("expected else keyword");
+        p.error("expected in keyword");
     }
 
+    p.skip_ws();
+    parse_head_expr(p);
+    p.skip_ws();
+
+    parse_loop_body(p);
+
+    p.builder.finish_node();
+}
+
+pub(crate) fn parse_while(p: &mut Parser) {
+    assert_eq!(p.peek(), Some(SyntaxKind::WhileKw));
+
+    p.builder.start_node(SyntaxKind::While.into());
+    p.bump();
+    p.skip_ws();
+
+    parse_head_expr(p);
+    p.skip_ws();
+
+    parse_loop_body(p);
+
+    p.builder.finish_node();
+}
+
+fn parse_loop_body(p: &mut Parser) {
     if p.peek() == Some(SyntaxKind::LBrace) {
         parse_block(p);
     } else {
         p.error("expected block");
     }
+}
+
+// The expressions directly before a brace-delimited body (the scrutinee of a match, the iterable of
+// a for loop and the condition of a while loop) bind more tightly than function application, so
+// that the opening brace isn’t mistaken for a parameter. Anything more complex, such as a command,
+// has to be parenthesised.
+fn parse_head_expr(p: &mut Parser) {
+    parse_expr_bp(p, HEAD_BP, false);
+}
+
+fn parse_match_arm(p: &mut Parser) {
+    p.builder.start_node(SyntaxKind::MatchArm.into());
+
+    super::pattern::parse_pattern(p);
+    p.skip_ws();
+
+    if p.peek() == Some(SyntaxKind::FatArrow) {
+        p.bump();
+    } else {
+        p.error("expected fat arrow");
+    }
+
+    parse_expr(p);
 
     p.builder.finish_node();
 }
@@ -165,7 +368,9 @@ fn parse_atom(p: &mut Parser, in_func_call_params: bool) {
 
     let at_expr = p
         .lookahead(idx_of_next_non_whitespace_token)
-        .map_or(false, SyntaxKind::can_start_expr);
+        .is_some_and(SyntaxKind::can_start_arg)
+        || glob_len(p, idx_of_next_non_whitespace_token).is_some()
+        || at_double_pipe_lambda_arg(p, idx_of_next_non_whitespace_token);
 
     // Being at an expression means that we’re at the start of a function call (i.e. we’re at the
     // name of the function being called) that has one or more parameters.
@@ -184,42 +389,250 @@ fn parse_atom(p: &mut Parser, in_func_call_params: bool) {
     }
 }
 
+// Globs can’t be recognised by the lexer, since they’re made up of several tokens: `src/*.rs`, for
+// instance, is lexed as an atom, a star and another atom. This returns the number of tokens making
+// up the glob starting `offset` tokens ahead, if there is one.
+fn glob_len(p: &Parser, offset: usize) -> Option<usize> {
+    let mut len = 0;
+    let mut contains_star = false;
+    let mut contains_atom = false;
+    let mut in_class = false;
+    let mut contains_class = false;
+
+    while let Some(kind) = p.lookahead(offset + len) {
+        match kind {
+            SyntaxKind::Star => contains_star = true,
+            SyntaxKind::LBracket => in_class = true,
+            SyntaxKind::RBracket if in_class => {
+                in_class = false;
+                contains_class = true;
+            }
+            SyntaxKind::Atom => contains_atom = true,
+            SyntaxKind::Digits | SyntaxKind::Slash | SyntaxKind::Minus | SyntaxKind::Plus => {}
+            _ => break,
+        }
+
+        len += 1;
+    }
+
+    if !contains_star {
+        // Something like `[ab].txt` is a glob with a character class, but something entirely
+        // enclosed in brackets (such as `[a]`) is a list.
+        let is_list = p.lookahead(offset) == Some(SyntaxKind::LBracket)
+            && p.lookahead(offset + len - 1) == Some(SyntaxKind::RBracket);
+
+        return if contains_class && !is_list {
+            Some(len)
+        } else {
+            None
+        };
+    }
+
+    // Something made up only of numbers and operators with a number on either side of every star
+    // (such as `2*3`) is arithmetic rather than a glob.
+    let is_arithmetic = !contains_atom
+        && !contains_class
+        && p.lookahead(offset) != Some(SyntaxKind::Star)
+        && p.lookahead(offset + len - 1) != Some(SyntaxKind::Star);
+
+    if is_arithmetic {
+        return None;
+    }
+
+    if len > 1 {
+        return Some(len);
+    }
+
+    // A lone star is only a glob if it isn’t followed by something it could be multiplying.
+    let mut idx = offset + 1;
+
+    while p.lookahead(idx) == Some(SyntaxKind::Whitespace) {
+        idx += 1;
+    }
+
+    if p.lookahead(idx).is_some_and(SyntaxKind::can_start_expr) {
+        None
+    } else {
+        Some(len)
+    }
+}
+
+fn parse_glob(p: &mut Parser, len: usize) {
+    p.builder.start_node(SyntaxKind::Glob.into());
+
+    for _ in 0..len {
+        p.bump();
+    }
+
+    p.builder.finish_node();
+}
+
+fn parse_list_or_record(p: &mut Parser) {
+    assert_eq!(p.peek(), Some(SyntaxKind::LBracket));
+
+    let checkpoint = p.builder.checkpoint();
+    p.bump();
+    p.skip_ws_and_eol();
+
+    let is_record = p.at_name_followed_by_equals();
+
+    let kind = if is_record {
+        SyntaxKind::Record
+    } else {
+        SyntaxKind::List
+    };
+    p.builder.start_node_at(checkpoint, kind.into());
+
+    loop {
+        p.skip_ws_and_eol();
+
+        match p.peek() {
+            Some(SyntaxKind::RBracket) => {
+                p.bump();
+                break;
+            }
+            None => {
+                p.error("expected right bracket");
+                break;
+            }
+            _ => {}
+        }
+
+        // Elements are separated by whitespace just like the parameters of a function call, so
+        // they are parsed in the same way: function calls and operators have to be parenthesised.
+        if is_record {
+            parse_record_field(p);
+        } else {
+            parse_one_expr(p, true);
+        }
+    }
+
+    p.builder.finish_node();
+}
+
+fn parse_record_field(p: &mut Parser) {
+    p.builder.start_node(SyntaxKind::RecordField.into());
+
+    if p.peek() == Some(SyntaxKind::Atom) {
+        p.bump();
+    } else {
+        p.error("expected field name");
+    }
+
+    p.skip_ws();
+
+    if p.peek() == Some(SyntaxKind::Equals) {
+        p.bump();
+    } else {
+        p.error("expected equals sign");
+    }
+
+    p.skip_ws();
+    parse_one_expr(p, true);
+
+    p.builder.finish_node();
+}
+
+const HEAD_BP: u8 = 8;
+
 fn infix_bp(op: VirtualOp) -> (u8, u8) {
     match op {
         VirtualOp::Op(op) => match op {
-            Op::Add | Op::Sub => (1, 2),
-            Op::Mul | Op::Div => (3, 4),
+            Op::And | Op::Or => (1, 2),
+            Op::Add | Op::Sub => (3, 4),
+            Op::Mul | Op::Div => (5, 6),
         },
-        VirtualOp::Application => (5, 5),
+        VirtualOp::Application => (7, 7),
+    }
+}
+
+// After a call, `||` can both start a lambda without parameters that is passed to the call and run
+// a command if the call failed. It’s only treated as the latter if a command follows it, so that
+// `f || 100` passes a lambda to `f`.
+fn at_double_pipe_lambda_arg(p: &Parser, offset: usize) -> bool {
+    if p.lookahead(offset) != Some(SyntaxKind::DoublePipe) {
+        return false;
     }
+
+    let next = match p.lookahead(offset + 1) {
+        Some(SyntaxKind::Whitespace) => p.lookahead(offset + 2),
+        next => next,
+    };
+
+    next.is_some_and(|kind| kind.can_start_expr() && kind != SyntaxKind::Atom)
+}
+
+/// Parses a function call (even one without any parameters), leaving any operator after it for the
+/// caller to deal with.
+pub(crate) fn parse_call(p: &mut Parser) {
+    assert_eq!(p.peek(), Some(SyntaxKind::Atom));
+
+    let (application_bp, _) = infix_bp(VirtualOp::Application);
+    parse_expr_bp(p, application_bp, false);
 }
 
 pub(crate) fn parse_lambda(p: &mut Parser) {
-    assert_eq!(p.peek(), Some(SyntaxKind::Pipe));
+    assert!(matches!(
+        p.peek(),
+        Some(SyntaxKind::Pipe) | Some(SyntaxKind::DoublePipe)
+    ));
 
     p.builder.start_node(SyntaxKind::Lambda.into());
 
     p.builder.start_node(SyntaxKind::LambdaParams.into());
 
+    // `||` is lexed as a single token, which in this position is a lambda without parameters.
+    let has_params = p.peek() == Some(SyntaxKind::Pipe);
+
     p.bump();
-    p.skip_ws();
 
-    loop {
-        if p.at_end() {
-            break;
-        }
+    if has_params {
+        p.skip_ws();
 
-        match p.peek() {
-            Some(SyntaxKind::Atom) => p.bump(),
-            Some(SyntaxKind::Pipe) => {
-                p.bump();
+        let mut seen_rest = false;
+        let mut seen_default = false;
+
+        loop {
+            if p.at_end() {
                 break;
             }
-            None => break,
-            _ => p.error("expected atom or pipe"),
-        }
 
-        p.skip_ws();
+            match p.peek() {
+                // The rest parameter has to be the last one.
+                Some(SyntaxKind::Atom) if seen_rest => p.error("expected pipe"),
+                Some(SyntaxKind::Atom) if super::pattern::at_rest(p) => {
+                    p.builder.start_node(SyntaxKind::RestParam.into());
+                    p.bump();
+                    p.builder.finish_node();
+
+                    seen_rest = true;
+                }
+                Some(SyntaxKind::Atom) if p.at_name_followed_by_equals() => {
+                    parse_default_param(p);
+                    seen_default = true;
+                }
+                // Arguments are bound to parameters in order, so a default could never be used if
+                // a parameter without one came after it.
+                Some(SyntaxKind::Atom) if seen_default => {
+                    p.error("parameters with defaults must come last")
+                }
+                Some(SyntaxKind::Atom) => p.bump(),
+                Some(SyntaxKind::Pipe) => {
+                    p.bump();
+                    break;
+                }
+                // Errors don’t consume line breaks, so we have to stop here to avoid looping
+                // forever.
+                Some(SyntaxKind::Eol) => {
+                    p.error("expected atom or pipe");
+                    break;
+                }
+                None => break,
+                _ => p.error("expected atom or pipe"),
+            }
+
+            p.skip_ws();
+        }
     }
 
     p.builder.finish_node();
@@ -230,6 +643,26 @@ pub(crate) fn parse_lambda(p: &mut Parser) {
     p.builder.finish_node();
 }
 
+// The default value of a parameter is parsed like an argument to a function call, so bare words
+// are strings and anything more complicated has to be parenthesised.
+fn parse_default_param(p: &mut Parser) {
+    p.builder.start_node(SyntaxKind::DefaultParam.into());
+    p.bump();
+    p.skip_ws();
+
+    assert_eq!(p.peek(), Some(SyntaxKind::Equals));
+    p.bump();
+    p.skip_ws();
+
+    match p.peek() {
+        // A pipe here is much more likely to be the end of the parameters than a lambda.
+        Some(kind) if kind.can_start_arg() && kind != SyntaxKind::Pipe => parse_one_expr(p, true),
+        _ => p.error("expected default value"),
+    }
+
+    p.builder.finish_node();
+}
+
 pub(crate) fn parse_binding_usage(p: &mut Parser) {
     assert_eq!(p.peek(), Some(SyntaxKind::Dollar));
 
@@ -244,7 +677,7 @@ pub(crate) fn parse_binding_usage(p: &mut Parser) {
     p.builder.finish_node();
 }
 
-fn parse_block(p: &mut Parser) {
+pub(super) fn parse_block(p: &mut Parser) {
     assert_eq!(p.peek(), Some(SyntaxKind::LBrace));
 
     p.builder.start_node(SyntaxKind::Block.into());
@@ -270,6 +703,10 @@ fn parse_block(p: &mut Parser) {
 
         match p.peek() {
             Some(SyntaxKind::Eol) => p.bump(),
+            Some(SyntaxKind::Semicolon) => {
+                p.bump();
+                p.skip_ws_and_eol();
+            }
             Some(SyntaxKind::RBrace) => {
                 p.bump();
                 break;
@@ -286,6 +723,7 @@ fn parse_block(p: &mut Parser) {
 mod tests {
     use super::*;
     use expect_test::{expect, Expect};
+    use text_size::TextRange;
 
     fn test(input: &'static str, expected_output: Expect) {
         Parser::test(parse_expr, input, expected_output);
@@ -709,33 +1147,186 @@ mod tests {
     }
 
     #[test]
-    fn parse_function_application_with_boolean() {
+    fn parse_lambda_with_rest_param() {
         test(
-            "f true false",
+            "|a ...rest| $rest",
             expect![[r#"
-            Root@0..12
-              FunctionCall@0..12
-                Atom@0..1 "f"
-                Whitespace@1..2 " "
-                FunctionCallParams@2..12
-                  True@2..6 "true"
+            Root@0..17
+              Lambda@0..17
+                LambdaParams@0..11
+                  Pipe@0..1 "|"
+                  Atom@1..2 "a"
+                  Whitespace@2..3 " "
+                  RestParam@3..10
+                    Atom@3..10 "...rest"
+                  Pipe@10..11 "|"
+                Whitespace@11..12 " "
+                BindingUsage@12..17
+                  Dollar@12..13 "$"
+                  Atom@13..17 "rest""#]],
+        );
+    }
+
+    #[test]
+    fn parse_lambda_with_default_param() {
+        test(
+            r#"|path dir="."| ls $dir"#,
+            expect![[r#"
+            Root@0..22
+              Lambda@0..22
+                LambdaParams@0..14
+                  Pipe@0..1 "|"
+                  Atom@1..5 "path"
+                  Whitespace@5..6 " "
+                  DefaultParam@6..13
+                    Atom@6..9 "dir"
+                    Equals@9..10 "="
+                    StringLiteral@10..13 "\".\""
+                  Pipe@13..14 "|"
+                Whitespace@14..15 " "
+                FunctionCall@15..22
+                  Atom@15..17 "ls"
+                  Whitespace@17..18 " "
+                  FunctionCallParams@18..22
+                    BindingUsage@18..22
+                      Dollar@18..19 "$"
+                      Atom@19..22 "dir""#]],
+        );
+    }
+
+    #[test]
+    fn recover_from_default_param_without_value() {
+        test(
+            "|a b=| $a",
+            expect![[r#"
+            Root@0..9
+              Lambda@0..9
+                LambdaParams@0..9
+                  Pipe@0..1 "|"
+                  Atom@1..2 "a"
+                  Whitespace@2..3 " "
+                  DefaultParam@3..6
+                    Atom@3..4 "b"
+                    Equals@4..5 "="
+                    Error@5..6 "|"
                   Whitespace@6..7 " "
-                  False@7..12 "false""#]],
+                  Error@7..8 "$"
+                  Error@8..9 "a""#]],
         );
     }
 
     #[test]
-    fn parse_function_application_with_parens() {
+    fn recover_from_param_after_rest_param() {
         test(
-            "f (1 + 1) (2 * 2)",
+            "|...rest a| $a",
             expect![[r#"
-            Root@0..17
-              FunctionCall@0..17
-                Atom@0..1 "f"
-                Whitespace@1..2 " "
-                FunctionCallParams@2..17
-                  LParen@2..3 "("
-                  BinOp@3..8
+            Root@0..14
+              Lambda@0..14
+                LambdaParams@0..11
+                  Pipe@0..1 "|"
+                  RestParam@1..8
+                    Atom@1..8 "...rest"
+                  Whitespace@8..9 " "
+                  Error@9..10 "a"
+                  Pipe@10..11 "|"
+                Whitespace@11..12 " "
+                BindingUsage@12..14
+                  Dollar@12..13 "$"
+                  Atom@13..14 "a""#]],
+        );
+    }
+
+    #[test]
+    fn recover_from_param_after_default_param() {
+        test(
+            "|a=1 b| $b",
+            expect![[r#"
+            Root@0..10
+              Lambda@0..10
+                LambdaParams@0..7
+                  Pipe@0..1 "|"
+                  DefaultParam@1..4
+                    Atom@1..2 "a"
+                    Equals@2..3 "="
+                    Digits@3..4 "1"
+                  Whitespace@4..5 " "
+                  Error@5..6 "b"
+                  Pipe@6..7 "|"
+                Whitespace@7..8 " "
+                BindingUsage@8..10
+                  Dollar@8..9 "$"
+                  Atom@9..10 "b""#]],
+        );
+
+        test_errors(
+            "|a=1 b| $b",
+            &[(
+                "parameters with defaults must come last",
+                TextRange::new(5.into(), 6.into()),
+            )],
+        );
+    }
+
+    #[test]
+    fn parse_lambda_with_unterminated_params() {
+        test(
+            "|a\nb",
+            expect![[r#"
+            Root@0..2
+              Lambda@0..2
+                LambdaParams@0..2
+                  Pipe@0..1 "|"
+                  Atom@1..2 "a""#]],
+        );
+    }
+
+    #[test]
+    fn parse_parenthesized_expression_spanning_lines() {
+        test(
+            "(\n  ls -l\n)",
+            expect![[r#"
+            Root@0..11
+              LParen@0..1 "("
+              Eol@1..2 "\n"
+              Whitespace@2..4 "  "
+              FunctionCall@4..9
+                Atom@4..6 "ls"
+                Whitespace@6..7 " "
+                FunctionCallParams@7..9
+                  Atom@7..9 "-l"
+              Eol@9..10 "\n"
+              RParen@10..11 ")""#]],
+        );
+    }
+
+    #[test]
+    fn parse_function_application_with_boolean() {
+        test(
+            "f true false",
+            expect![[r#"
+            Root@0..12
+              FunctionCall@0..12
+                Atom@0..1 "f"
+                Whitespace@1..2 " "
+                FunctionCallParams@2..12
+                  True@2..6 "true"
+                  Whitespace@6..7 " "
+                  False@7..12 "false""#]],
+        );
+    }
+
+    #[test]
+    fn parse_function_application_with_parens() {
+        test(
+            "f (1 + 1) (2 * 2)",
+            expect![[r#"
+            Root@0..17
+              FunctionCall@0..17
+                Atom@0..1 "f"
+                Whitespace@1..2 " "
+                FunctionCallParams@2..17
+                  LParen@2..3 "("
+                  BinOp@3..8
                     Digits@3..4 "1"
                     Whitespace@4..5 " "
                     Plus@5..6 "+"
@@ -753,4 +1344,780 @@ mod tests {
                   RParen@16..17 ")""#]],
         );
     }
+
+    #[test]
+    fn parse_function_call_with_glob() {
+        test(
+            "ls *.rs",
+            expect![[r#"
+                Root@0..7
+                  FunctionCall@0..7
+                    Atom@0..2 "ls"
+                    Whitespace@2..3 " "
+                    FunctionCallParams@3..7
+                      Glob@3..7
+                        Star@3..4 "*"
+                        Atom@4..7 ".rs""#]],
+        );
+    }
+
+    #[test]
+    fn parse_function_call_with_lone_star_glob() {
+        test(
+            "ls *",
+            expect![[r#"
+                Root@0..4
+                  FunctionCall@0..4
+                    Atom@0..2 "ls"
+                    Whitespace@2..3 " "
+                    FunctionCallParams@3..4
+                      Glob@3..4
+                        Star@3..4 "*""#]],
+        );
+    }
+
+    #[test]
+    fn parse_multiplication_of_numbers_in_function_call() {
+        test(
+            "f 2*3",
+            expect![[r#"
+                Root@0..5
+                  BinOp@0..5
+                    FunctionCall@0..3
+                      Atom@0..1 "f"
+                      Whitespace@1..2 " "
+                      FunctionCallParams@2..3
+                        Digits@2..3 "2"
+                    Star@3..4 "*"
+                    Digits@4..5 "3""#]],
+        );
+    }
+
+    #[test]
+    fn parse_function_call_with_glob_starting_with_digits() {
+        test(
+            "ls 2*",
+            expect![[r#"
+                Root@0..5
+                  FunctionCall@0..5
+                    Atom@0..2 "ls"
+                    Whitespace@2..3 " "
+                    FunctionCallParams@3..5
+                      Glob@3..5
+                        Digits@3..4 "2"
+                        Star@4..5 "*""#]],
+        );
+    }
+
+    #[test]
+    fn parse_function_call_with_glob_spanning_directories() {
+        test(
+            "wc -l src/**/*.rs Cargo.toml",
+            expect![[r#"
+                Root@0..28
+                  FunctionCall@0..28
+                    Atom@0..2 "wc"
+                    Whitespace@2..3 " "
+                    FunctionCallParams@3..28
+                      Atom@3..5 "-l"
+                      Whitespace@5..6 " "
+                      Glob@6..17
+                        Atom@6..10 "src/"
+                        Star@10..11 "*"
+                        Star@11..12 "*"
+                        Slash@12..13 "/"
+                        Star@13..14 "*"
+                        Atom@14..17 ".rs"
+                      Whitespace@17..18 " "
+                      Atom@18..28 "Cargo.toml""#]],
+        );
+    }
+
+    #[test]
+    fn star_between_expressions_is_multiplication() {
+        test(
+            "f 2 * 3",
+            expect![[r#"
+                Root@0..7
+                  BinOp@0..7
+                    FunctionCall@0..4
+                      Atom@0..1 "f"
+                      Whitespace@1..2 " "
+                      FunctionCallParams@2..4
+                        Digits@2..3 "2"
+                        Whitespace@3..4 " "
+                    Star@4..5 "*"
+                    Whitespace@5..6 " "
+                    Digits@6..7 "3""#]],
+        );
+    }
+
+    #[test]
+    fn star_without_whitespace_after_expression_is_multiplication() {
+        test(
+            "$x*2",
+            expect![[r#"
+                Root@0..4
+                  BinOp@0..4
+                    BindingUsage@0..2
+                      Dollar@0..1 "$"
+                      Atom@1..2 "x"
+                    Star@2..3 "*"
+                    Digits@3..4 "2""#]],
+        );
+    }
+
+    #[test]
+    fn parse_command_chain() {
+        test(
+            "cargo test && deploy || echo failed",
+            expect![[r#"
+                Root@0..35
+                  BinOp@0..35
+                    BinOp@0..21
+                      FunctionCall@0..11
+                        Atom@0..5 "cargo"
+                        Whitespace@5..6 " "
+                        FunctionCallParams@6..11
+                          Atom@6..10 "test"
+                          Whitespace@10..11 " "
+                      DoubleAmpersand@11..13 "&&"
+                      Whitespace@13..14 " "
+                      FunctionCall@14..20
+                        Atom@14..20 "deploy"
+                        FunctionCallParams@20..20
+                      Whitespace@20..21 " "
+                    DoublePipe@21..23 "||"
+                    Whitespace@23..24 " "
+                    FunctionCall@24..35
+                      Atom@24..28 "echo"
+                      Whitespace@28..29 " "
+                      FunctionCallParams@29..35
+                        Atom@29..35 "failed""#]],
+        );
+    }
+
+    #[test]
+    fn logical_operators_bind_more_loosely_than_arithmetic() {
+        test(
+            "1 + 2 && 3",
+            expect![[r#"
+                Root@0..10
+                  BinOp@0..10
+                    BinOp@0..6
+                      Digits@0..1 "1"
+                      Whitespace@1..2 " "
+                      Plus@2..3 "+"
+                      Whitespace@3..4 " "
+                      Digits@4..5 "2"
+                      Whitespace@5..6 " "
+                    DoubleAmpersand@6..8 "&&"
+                    Whitespace@8..9 " "
+                    Digits@9..10 "3""#]],
+        );
+    }
+
+    #[test]
+    fn parse_double_pipe_after_call_as_lambda_arg() {
+        test(
+            "f || 100",
+            expect![[r#"
+                Root@0..8
+                  FunctionCall@0..8
+                    Atom@0..1 "f"
+                    Whitespace@1..2 " "
+                    FunctionCallParams@2..8
+                      Lambda@2..8
+                        LambdaParams@2..4
+                          DoublePipe@2..4 "||"
+                        Whitespace@4..5 " "
+                        Digits@5..8 "100""#]],
+        );
+    }
+
+    #[test]
+    fn parse_double_pipe_after_call_with_params_as_lambda_arg() {
+        test(
+            "f a || { 1 }",
+            expect![[r#"
+                Root@0..12
+                  FunctionCall@0..12
+                    Atom@0..1 "f"
+                    Whitespace@1..2 " "
+                    FunctionCallParams@2..12
+                      Atom@2..3 "a"
+                      Whitespace@3..4 " "
+                      Lambda@4..12
+                        LambdaParams@4..6
+                          DoublePipe@4..6 "||"
+                        Whitespace@6..7 " "
+                        Block@7..12
+                          LBrace@7..8 "{"
+                          Whitespace@8..9 " "
+                          Digits@9..10 "1"
+                          Whitespace@10..11 " "
+                          RBrace@11..12 "}""#]],
+        );
+    }
+
+    #[test]
+    fn parse_double_pipe_after_call_followed_by_command_as_operator() {
+        test(
+            "f || g",
+            expect![[r#"
+                Root@0..6
+                  BinOp@0..6
+                    FunctionCall@0..1
+                      Atom@0..1 "f"
+                      FunctionCallParams@1..1
+                    Whitespace@1..2 " "
+                    DoublePipe@2..4 "||"
+                    Whitespace@4..5 " "
+                    FunctionCall@5..6
+                      Atom@5..6 "g"
+                      FunctionCallParams@6..6"#]],
+        );
+    }
+
+    #[test]
+    fn parse_lambda_without_params_written_as_double_pipe() {
+        test(
+            "|| 100",
+            expect![[r#"
+                Root@0..6
+                  Lambda@0..6
+                    LambdaParams@0..2
+                      DoublePipe@0..2 "||"
+                    Whitespace@2..3 " "
+                    Digits@3..6 "100""#]],
+        );
+    }
+
+    #[test]
+    fn parse_block_with_items_separated_by_semicolons() {
+        test(
+            "{ let a = 5; $a }",
+            expect![[r#"
+                Root@0..17
+                  Block@0..17
+                    LBrace@0..1 "{"
+                    Whitespace@1..2 " "
+                    BindingDef@2..11
+                      LetKw@2..5 "let"
+                      Whitespace@5..6 " "
+                      Atom@6..7 "a"
+                      Whitespace@7..8 " "
+                      Equals@8..9 "="
+                      Whitespace@9..10 " "
+                      Digits@10..11 "5"
+                    Semicolon@11..12 ";"
+                    Whitespace@12..13 " "
+                    BindingUsage@13..15
+                      Dollar@13..14 "$"
+                      Atom@14..15 "a"
+                    Whitespace@15..16 " "
+                    RBrace@16..17 "}""#]],
+        );
+    }
+
+    #[test]
+    fn parse_background_command() {
+        test(
+            "sleep 10 &",
+            expect![[r#"
+                Root@0..10
+                  Background@0..10
+                    FunctionCall@0..9
+                      Atom@0..5 "sleep"
+                      Whitespace@5..6 " "
+                      FunctionCallParams@6..9
+                        Digits@6..8 "10"
+                        Whitespace@8..9 " "
+                    Ampersand@9..10 "&""#]],
+        );
+    }
+
+    #[test]
+    fn parse_contextual_keywords_as_args() {
+        test(
+            "echo in the source",
+            expect![[r#"
+                Root@0..18
+                  FunctionCall@0..18
+                    Atom@0..4 "echo"
+                    Whitespace@4..5 " "
+                    FunctionCallParams@5..18
+                      Atom@5..7 "in"
+                      Whitespace@7..8 " "
+                      Atom@8..11 "the"
+                      Whitespace@11..12 " "
+                      Atom@12..18 "source""#]],
+        );
+    }
+
+    #[test]
+    fn parse_contextual_keyword_as_only_arg() {
+        test(
+            "echo match",
+            expect![[r#"
+                Root@0..10
+                  FunctionCall@0..10
+                    Atom@0..4 "echo"
+                    Whitespace@4..5 " "
+                    FunctionCallParams@5..10
+                      Atom@5..10 "match""#]],
+        );
+    }
+
+    #[test]
+    fn parse_import_keyword_as_arg() {
+        test(
+            "rg import src",
+            expect![[r#"
+                Root@0..13
+                  FunctionCall@0..13
+                    Atom@0..2 "rg"
+                    Whitespace@2..3 " "
+                    FunctionCallParams@3..13
+                      Atom@3..9 "import"
+                      Whitespace@9..10 " "
+                      Atom@10..13 "src""#]],
+        );
+    }
+
+    #[test]
+    fn parse_mut_keyword_as_arg() {
+        test(
+            "echo mut",
+            expect![[r#"
+                Root@0..8
+                  FunctionCall@0..8
+                    Atom@0..4 "echo"
+                    Whitespace@4..5 " "
+                    FunctionCallParams@5..8
+                      Atom@5..8 "mut""#]],
+        );
+    }
+
+    #[test]
+    fn recover_from_expr_after_ampersand() {
+        test(
+            "sleep 0 & echo",
+            expect![[r#"
+                Root@0..14
+                  Background@0..9
+                    FunctionCall@0..8
+                      Atom@0..5 "sleep"
+                      Whitespace@5..6 " "
+                      FunctionCallParams@6..8
+                        Digits@6..7 "0"
+                        Whitespace@7..8 " "
+                    Ampersand@8..9 "&"
+                  Whitespace@9..10 " "
+                  Error@10..14 "echo""#]],
+        );
+    }
+
+    #[test]
+    fn recover_from_ampersand_inside_atom() {
+        test(
+            "echo a&b",
+            expect![[r#"
+                Root@0..8
+                  Background@0..7
+                    FunctionCall@0..6
+                      Atom@0..4 "echo"
+                      Whitespace@4..5 " "
+                      FunctionCallParams@5..6
+                        Atom@5..6 "a"
+                    Ampersand@6..7 "&"
+                  Error@7..8 "b""#]],
+        );
+    }
+
+    #[test]
+    fn recover_from_backgrounded_command_chain() {
+        test(
+            "build && deploy &",
+            expect![[r#"
+                Root@0..17
+                  BinOp@0..16
+                    FunctionCall@0..5
+                      Atom@0..5 "build"
+                      FunctionCallParams@5..5
+                    Whitespace@5..6 " "
+                    DoubleAmpersand@6..8 "&&"
+                    Whitespace@8..9 " "
+                    FunctionCall@9..15
+                      Atom@9..15 "deploy"
+                      FunctionCallParams@15..15
+                    Whitespace@15..16 " "
+                  Error@16..17 "&""#]],
+        );
+    }
+
+    #[test]
+    fn recover_from_backgrounded_non_command() {
+        test(
+            "$x &",
+            expect![[r#"
+                Root@0..4
+                  BindingUsage@0..2
+                    Dollar@0..1 "$"
+                    Atom@1..2 "x"
+                  Whitespace@2..3 " "
+                  Error@3..4 "&""#]],
+        );
+    }
+
+    #[test]
+    fn parse_list() {
+        test(
+            r#"[1 foo "bar"]"#,
+            expect![[r#"
+                Root@0..13
+                  List@0..13
+                    LBracket@0..1 "["
+                    Digits@1..2 "1"
+                    Whitespace@2..3 " "
+                    Atom@3..6 "foo"
+                    Whitespace@6..7 " "
+                    StringLiteral@7..12 "\"bar\""
+                    RBracket@12..13 "]""#]],
+        );
+    }
+
+    #[test]
+    fn parse_record() {
+        test(
+            r#"[name = "fjord" version = 4]"#,
+            expect![[r#"
+                Root@0..28
+                  Record@0..28
+                    LBracket@0..1 "["
+                    RecordField@1..15
+                      Atom@1..5 "name"
+                      Whitespace@5..6 " "
+                      Equals@6..7 "="
+                      Whitespace@7..8 " "
+                      StringLiteral@8..15 "\"fjord\""
+                    Whitespace@15..16 " "
+                    RecordField@16..27
+                      Atom@16..23 "version"
+                      Whitespace@23..24 " "
+                      Equals@24..25 "="
+                      Whitespace@25..26 " "
+                      Digits@26..27 "4"
+                    RBracket@27..28 "]""#]],
+        );
+    }
+
+    #[test]
+    fn parse_function_call_with_list_and_glob_with_character_class() {
+        test(
+            "f [a b] [ab].txt",
+            expect![[r#"
+                Root@0..16
+                  FunctionCall@0..16
+                    Atom@0..1 "f"
+                    Whitespace@1..2 " "
+                    FunctionCallParams@2..16
+                      List@2..7
+                        LBracket@2..3 "["
+                        Atom@3..4 "a"
+                        Whitespace@4..5 " "
+                        Atom@5..6 "b"
+                        RBracket@6..7 "]"
+                      Whitespace@7..8 " "
+                      Glob@8..16
+                        LBracket@8..9 "["
+                        Atom@9..11 "ab"
+                        RBracket@11..12 "]"
+                        Atom@12..16 ".txt""#]],
+        );
+    }
+
+    #[test]
+    fn parse_match() {
+        test(
+            r#"match $x {
+  0 => "zero"
+  Number => "number"
+  _ => "other"
+}"#,
+            expect![[r#"
+                Root@0..62
+                  Match@0..62
+                    MatchKw@0..5 "match"
+                    Whitespace@5..6 " "
+                    BindingUsage@6..8
+                      Dollar@6..7 "$"
+                      Atom@7..8 "x"
+                    Whitespace@8..9 " "
+                    LBrace@9..10 "{"
+                    Eol@10..11 "\n"
+                    Whitespace@11..13 "  "
+                    MatchArm@13..24
+                      Digits@13..14 "0"
+                      Whitespace@14..15 " "
+                      FatArrow@15..17 "=>"
+                      Whitespace@17..18 " "
+                      StringLiteral@18..24 "\"zero\""
+                    Eol@24..25 "\n"
+                    Whitespace@25..27 "  "
+                    MatchArm@27..45
+                      Atom@27..33 "Number"
+                      Whitespace@33..34 " "
+                      FatArrow@34..36 "=>"
+                      Whitespace@36..37 " "
+                      StringLiteral@37..45 "\"number\""
+                    Eol@45..46 "\n"
+                    Whitespace@46..48 "  "
+                    MatchArm@48..60
+                      Atom@48..49 "_"
+                      Whitespace@49..50 " "
+                      FatArrow@50..52 "=>"
+                      Whitespace@52..53 " "
+                      StringLiteral@53..60 "\"other\""
+                    Eol@60..61 "\n"
+                    RBrace@61..62 "}""#]],
+        );
+    }
+
+    #[test]
+    fn parse_match_with_list_and_record_patterns() {
+        test(
+            "match $x { [first ...rest] => $rest; [name = n] => $n; [] => 0 }",
+            expect![[r#"
+                Root@0..64
+                  Match@0..64
+                    MatchKw@0..5 "match"
+                    Whitespace@5..6 " "
+                    BindingUsage@6..8
+                      Dollar@6..7 "$"
+                      Atom@7..8 "x"
+                    Whitespace@8..9 " "
+                    LBrace@9..10 "{"
+                    Whitespace@10..11 " "
+                    MatchArm@11..35
+                      ListPattern@11..26
+                        LBracket@11..12 "["
+                        Atom@12..17 "first"
+                        Whitespace@17..18 " "
+                        RestPattern@18..25
+                          Atom@18..25 "...rest"
+                        RBracket@25..26 "]"
+                      Whitespace@26..27 " "
+                      FatArrow@27..29 "=>"
+                      Whitespace@29..30 " "
+                      BindingUsage@30..35
+                        Dollar@30..31 "$"
+                        Atom@31..35 "rest"
+                    Semicolon@35..36 ";"
+                    Whitespace@36..37 " "
+                    MatchArm@37..53
+                      RecordPattern@37..47
+                        LBracket@37..38 "["
+                        RecordFieldPattern@38..46
+                          Atom@38..42 "name"
+                          Whitespace@42..43 " "
+                          Equals@43..44 "="
+                          Whitespace@44..45 " "
+                          Atom@45..46 "n"
+                        RBracket@46..47 "]"
+                      Whitespace@47..48 " "
+                      FatArrow@48..50 "=>"
+                      Whitespace@50..51 " "
+                      BindingUsage@51..53
+                        Dollar@51..52 "$"
+                        Atom@52..53 "n"
+                    Semicolon@53..54 ";"
+                    Whitespace@54..55 " "
+                    MatchArm@55..63
+                      ListPattern@55..57
+                        LBracket@55..56 "["
+                        RBracket@56..57 "]"
+                      Whitespace@57..58 " "
+                      FatArrow@58..60 "=>"
+                      Whitespace@60..61 " "
+                      Digits@61..62 "0"
+                      Whitespace@62..63 " "
+                    RBrace@63..64 "}""#]],
+        );
+    }
+
+    fn test_errors(input: &'static str, expected_errors: &[(&'static str, TextRange)]) {
+        let parse_output = Parser::new(input).parse();
+
+        let errors: Vec<_> = parse_output
+            .errors()
+            .iter()
+            .map(|error| (error.message, error.range))
+            .collect();
+
+        assert_eq!(errors, expected_errors);
+    }
+
+    #[test]
+    fn parse_if_without_else() {
+        test(
+            "if true then { 1 } ",
+            expect![[r#"
+                Root@0..19
+                  If@0..18
+                    IfKw@0..2 "if"
+                    Whitespace@2..3 " "
+                    True@3..7 "true"
+                    Whitespace@7..8 " "
+                    ThenKw@8..12 "then"
+                    Whitespace@12..13 " "
+                    Block@13..18
+                      LBrace@13..14 "{"
+                      Whitespace@14..15 " "
+                      Digits@15..16 "1"
+                      Whitespace@16..17 " "
+                      RBrace@17..18 "}"
+                  Whitespace@18..19 " ""#]],
+        );
+    }
+
+    #[test]
+    fn parse_else_if_chain() {
+        test(
+            "if false then { 1 } else if true then { 2 } else { 3 }",
+            expect![[r#"
+                Root@0..54
+                  If@0..54
+                    IfKw@0..2 "if"
+                    Whitespace@2..3 " "
+                    False@3..8 "false"
+                    Whitespace@8..9 " "
+                    ThenKw@9..13 "then"
+                    Whitespace@13..14 " "
+                    Block@14..19
+                      LBrace@14..15 "{"
+                      Whitespace@15..16 " "
+                      Digits@16..17 "1"
+                      Whitespace@17..18 " "
+                      RBrace@18..19 "}"
+                    Whitespace@19..20 " "
+                    ElseKw@20..24 "else"
+                    Whitespace@24..25 " "
+                    If@25..54
+                      IfKw@25..27 "if"
+                      Whitespace@27..28 " "
+                      True@28..32 "true"
+                      Whitespace@32..33 " "
+                      ThenKw@33..37 "then"
+                      Whitespace@37..38 " "
+                      Block@38..43
+                        LBrace@38..39 "{"
+                        Whitespace@39..40 " "
+                        Digits@40..41 "2"
+                        Whitespace@41..42 " "
+                        RBrace@42..43 "}"
+                      Whitespace@43..44 " "
+                      ElseKw@44..48 "else"
+                      Whitespace@48..49 " "
+                      Block@49..54
+                        LBrace@49..50 "{"
+                        Whitespace@50..51 " "
+                        Digits@51..52 "3"
+                        Whitespace@52..53 " "
+                        RBrace@53..54 "}""#]],
+        );
+    }
+
+    #[test]
+    fn recover_from_else_without_block() {
+        test(
+            "if true then { 1 } else 2",
+            expect![[r#"
+                Root@0..25
+                  If@0..25
+                    IfKw@0..2 "if"
+                    Whitespace@2..3 " "
+                    True@3..7 "true"
+                    Whitespace@7..8 " "
+                    ThenKw@8..12 "then"
+                    Whitespace@12..13 " "
+                    Block@13..18
+                      LBrace@13..14 "{"
+                      Whitespace@14..15 " "
+                      Digits@15..16 "1"
+                      Whitespace@16..17 " "
+                      RBrace@17..18 "}"
+                    Whitespace@18..19 " "
+                    ElseKw@19..23 "else"
+                    Whitespace@23..24 " "
+                    Error@24..25 "2""#]],
+        );
+
+        test_errors(
+            "if true then { 1 } else 2",
+            &[(
+                "expected block or if keyword",
+                TextRange::new(24.into(), 25.into()),
+            )],
+        );
+    }
+
+    #[test]
+    fn recover_from_else_at_end_of_input() {
+        test_errors(
+            "if true then { 1 } else",
+            &[(
+                "expected block or if keyword",
+                TextRange::new(19.into(), 23.into()),
+            )],
+        );
+    }
+
+    #[test]
+    fn parse_for_loop() {
+        test(
+            "for x in (ls) { continue }",
+            expect![[r#"
+                Root@0..26
+                  For@0..26
+                    ForKw@0..3 "for"
+                    Whitespace@3..4 " "
+                    Atom@4..5 "x"
+                    Whitespace@5..6 " "
+                    InKw@6..8 "in"
+                    Whitespace@8..9 " "
+                    LParen@9..10 "("
+                    FunctionCall@10..12
+                      Atom@10..12 "ls"
+                      FunctionCallParams@12..12
+                    RParen@12..13 ")"
+                    Whitespace@13..14 " "
+                    Block@14..26
+                      LBrace@14..15 "{"
+                      Whitespace@15..16 " "
+                      ContinueKw@16..24 "continue"
+                      Whitespace@24..25 " "
+                      RBrace@25..26 "}""#]],
+        );
+    }
+
+    #[test]
+    fn parse_while_loop() {
+        test(
+            "while true { break }",
+            expect![[r#"
+                Root@0..20
+                  While@0..20
+                    WhileKw@0..5 "while"
+                    Whitespace@5..6 " "
+                    True@6..10 "true"
+                    Whitespace@10..11 " "
+                    Block@11..20
+                      LBrace@11..12 "{"
+                      Whitespace@12..13 " "
+                      BreakKw@13..18 "break"
+                      Whitespace@18..19 " "
+                      RBrace@19..20 "}""#]],
+        );
+    }
 }
@@ -0,0 +1,382 @@
+//! Reparsing only the part of a syntax tree that an edit affects.
+//!
+//! Editors reparse after every keystroke, so rather than starting from scratch each time we find
+//! the smallest block or item around the edit, parse just that, and splice the result into the old
+//! tree. Everything outside the reparsed node is left untouched and keeps sharing its green nodes
+//! with the old tree. Whenever it isn’t certain that parsing part of the input on its own gives the
+//! same result as parsing all of it, we fall back to a full reparse.
+
+use super::{expr, ContainsErrors, ParseOutput, Parser, SyntaxError};
+use crate::lexer::{Lexer, SyntaxKind};
+use crate::SyntaxNode;
+use rowan::{GreenNode, NodeOrToken};
+use text_size::{TextRange, TextSize};
+
+/// A change to some text: the text in a range is deleted, and new text is inserted in its place.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct TextEdit {
+    delete: TextRange,
+    insert: String,
+}
+
+impl TextEdit {
+    /// Creates a new `TextEdit` that replaces the text in `delete` with `insert`.
+    pub fn new(delete: TextRange, insert: impl Into<String>) -> Self {
+        Self {
+            delete,
+            insert: insert.into(),
+        }
+    }
+
+    /// Returns the text that results from applying the edit to the given text.
+    ///
+    /// This panics if the range being deleted is out of bounds.
+    pub fn apply(&self, text: &str) -> String {
+        let mut text = text.to_string();
+        text.replace_range(std::ops::Range::<usize>::from(self.delete), &self.insert);
+
+        text
+    }
+
+    fn insert_len(&self) -> TextSize {
+        TextSize::of(self.insert.as_str())
+    }
+
+    // Where a range that contains the edit ends up once the edit has been applied.
+    fn apply_to_range(&self, range: TextRange) -> TextRange {
+        TextRange::new(
+            range.start(),
+            range.end() + self.insert_len() - self.delete.len(),
+        )
+    }
+}
+
+impl ParseOutput<ContainsErrors> {
+    /// Applies an edit to the code this `ParseOutput` was parsed from, and parses the result.
+    ///
+    /// Only the innermost block or top-level item containing the edit is reparsed where possible,
+    /// with the rest of the syntax tree being reused. The result is always the same as parsing the
+    /// edited code from scratch.
+    ///
+    /// This panics if the range being deleted is out of bounds.
+    pub fn reparse(&self, edit: &TextEdit) -> Self {
+        let new_text = edit.apply(&self.syntax().text().to_string());
+
+        self.reparse_incrementally(edit, &new_text)
+            .unwrap_or_else(|| Parser::new(&new_text).parse())
+    }
+
+    fn reparse_incrementally(&self, edit: &TextEdit, new_text: &str) -> Option<Self> {
+        let mut node = match self.syntax().covering_element(edit.delete) {
+            NodeOrToken::Node(node) => Some(node),
+            NodeOrToken::Token(token) => Some(token.parent()),
+        };
+
+        while let Some(current) = node {
+            let reparsed = match current.kind() {
+                SyntaxKind::Block => reparse_block(&current, edit, new_text),
+                _ if current.parent().map(|parent| parent.kind()) == Some(SyntaxKind::Root) => {
+                    reparse_item(&current, edit, new_text)
+                }
+                _ => None,
+            };
+
+            if let Some(reparsed) = reparsed {
+                return Some(self.splice(&current, reparsed, edit));
+            }
+
+            node = current.parent();
+        }
+
+        None
+    }
+
+    fn splice(&self, old_node: &SyntaxNode, reparsed: Reparsed, edit: &TextEdit) -> Self {
+        let old_range = old_node.text_range();
+        let offset = old_range.start();
+
+        let mut errors = Vec::new();
+        let mut errors_after = Vec::new();
+
+        // The parser emits errors in the order in which it comes across them, so the errors from
+        // the reparsed node go between those before and after it.
+        for error in self.errors() {
+            if reparsed.errors_range.contains_range(error.range) {
+                continue;
+            }
+
+            if error.range.end() <= offset {
+                errors.push(*error);
+            } else {
+                errors_after.push(SyntaxError {
+                    range: error.range + edit.insert_len() - edit.delete.len(),
+                    ..*error
+                });
+            }
+        }
+
+        errors.extend(reparsed.errors.into_iter().map(|error| SyntaxError {
+            range: error.range + offset,
+            ..error
+        }));
+        errors.extend(errors_after);
+
+        ParseOutput {
+            green_node: replace_node(old_node, reparsed.green_node),
+            state: ContainsErrors(errors),
+        }
+    }
+}
+
+// Replaces a node in a tree, returning the root of the new tree. Unlike `SyntaxNode::replace_with`,
+// the replacement can be of a different kind, which is needed when an edit turns one kind of item
+// into another.
+fn replace_node(node: &SyntaxNode, replacement: GreenNode) -> GreenNode {
+    let parent = match node.parent() {
+        Some(parent) => parent,
+        None => return replacement,
+    };
+
+    let children: Vec<_> = parent
+        .children_with_tokens()
+        .map(|child| match child {
+            NodeOrToken::Node(child) if child == *node => NodeOrToken::Node(replacement.clone()),
+            NodeOrToken::Node(child) => NodeOrToken::Node(child.green().clone()),
+            NodeOrToken::Token(token) => NodeOrToken::Token(token.green().clone()),
+        })
+        .collect();
+
+    parent.replace_with(GreenNode::new(parent.green().kind(), children))
+}
+
+struct Reparsed {
+    green_node: GreenNode,
+    // Relative to the start of the reparsed node.
+    errors: Vec<SyntaxError>,
+    // The range of the old tree that the errors of the old node are in.
+    errors_range: TextRange,
+}
+
+fn reparse_block(block: &SyntaxNode, edit: &TextEdit, new_text: &str) -> Option<Reparsed> {
+    let range = block.text_range();
+
+    // The braces themselves have to stay where they are, since otherwise the block could end up
+    // covering more or less than it did before. The same goes for blocks that weren’t closed
+    // properly in the first place.
+    if edit.delete.start() <= range.start()
+        || edit.delete.end() >= range.end()
+        || block.last_child_or_token()?.kind() != SyntaxKind::RBrace
+    {
+        return None;
+    }
+
+    let text = &new_text[edit.apply_to_range(range)];
+    if !lexes_cleanly(text) {
+        return None;
+    }
+
+    let mut p = Parser::new(text);
+    expr::parse_block(&mut p);
+
+    // The block has to end at the same closing brace as it would when parsing everything, which
+    // is only guaranteed if the closing brace is the very last thing in it.
+    if !p.at_end() {
+        return None;
+    }
+
+    let green_node = p.builder.finish();
+    let closing_brace = SyntaxNode::new_root(green_node.clone()).last_child_or_token()?;
+    if closing_brace.kind() != SyntaxKind::RBrace {
+        return None;
+    }
+
+    // Once the closing brace has been reached the block is finished, so any errors pointing at it
+    // come from whatever the block is part of.
+    let closing_brace_start = range.end() - TextSize::of('}');
+
+    Some(Reparsed {
+        green_node,
+        errors: p.errors,
+        errors_range: TextRange::new(range.start(), closing_brace_start),
+    })
+}
+
+fn reparse_item(item: &SyntaxNode, edit: &TextEdit, new_text: &str) -> Option<Reparsed> {
+    let range = item.text_range();
+
+    if edit.delete.start() <= range.start() || edit.delete.end() > range.end() {
+        return None;
+    }
+
+    // The parser looks ahead past the end of an item to decide where it stops, so the item is
+    // parsed together with what comes after it up to the end of the line. This is only done when
+    // the item is followed by the end of a line (or the input), because then nothing after that
+    // can affect it.
+    let mut following_text = String::new();
+    let mut next = item.next_sibling_or_token();
+
+    while let Some(element) = next {
+        let token = element.into_token()?;
+
+        match token.kind() {
+            SyntaxKind::Whitespace => following_text.push_str(token.text()),
+            SyntaxKind::Eol | SyntaxKind::Semicolon => {
+                following_text.push_str(token.text());
+                break;
+            }
+            _ => return None,
+        }
+
+        next = token.next_sibling_or_token();
+    }
+
+    let item_text = &new_text[edit.apply_to_range(range)];
+    let text = format!("{}{}", item_text, following_text);
+    if !lexes_cleanly(&text) {
+        return None;
+    }
+
+    let parse_output = Parser::new(&text).parse();
+    let root = parse_output.syntax();
+    let mut children = root.children_with_tokens();
+
+    // The edit mustn’t have turned the item into several, or extended it into what follows.
+    let reparsed_item = children.next()?.into_node()?;
+    if reparsed_item.text_range().len() != TextSize::of(item_text)
+        || children.any(|child| child.into_token().is_none())
+    {
+        return None;
+    }
+
+    let errors = parse_output.state.0;
+    if errors
+        .iter()
+        .any(|error| error.range.end() > reparsed_item.text_range().end())
+    {
+        return None;
+    }
+
+    Some(Reparsed {
+        green_node: reparsed_item.green().clone(),
+        errors,
+        errors_range: range,
+    })
+}
+
+// Checks that the lexer doesn’t fail anywhere in the text. The main case this guards against is an
+// unterminated string literal, which might be terminated by something outside the text instead.
+fn lexes_cleanly(text: &str) -> bool {
+    Lexer::new(text).all(|lexeme| lexeme.kind != SyntaxKind::Error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(input: &str, edit: TextEdit) {
+        let parse_output = Parser::new(input).parse();
+        let reparsed = parse_output.reparse(&edit);
+
+        assert_eq!(reparsed, Parser::new(&edit.apply(input)).parse());
+    }
+
+    fn check_incremental(input: &str, edit: TextEdit) {
+        let parse_output = Parser::new(input).parse();
+        let new_text = edit.apply(input);
+
+        assert!(parse_output
+            .reparse_incrementally(&edit, &new_text)
+            .is_some());
+        check(input, edit);
+    }
+
+    fn range(start: u32, end: u32) -> TextRange {
+        TextRange::new(start.into(), end.into())
+    }
+
+    #[test]
+    fn apply_edit() {
+        assert_eq!(
+            TextEdit::new(range(5, 6), "xy").apply("echo a b"),
+            "echo xy b"
+        );
+    }
+
+    #[test]
+    fn reparse_edit_in_block() {
+        check_incremental(
+            "let a = {\n  echo a\n  1 + 2\n}\n$a",
+            TextEdit::new(range(21, 22), "10"),
+        );
+    }
+
+    #[test]
+    fn reparse_edit_in_nested_block() {
+        check_incremental(
+            "if true then { { ls } } else { 5 }",
+            TextEdit::new(range(18, 18), " -l"),
+        );
+    }
+
+    #[test]
+    fn reparse_edit_at_end_of_item() {
+        check_incremental("echo a\necho hell", TextEdit::new(range(16, 16), "o"));
+    }
+
+    #[test]
+    fn reparse_edit_introducing_error_in_block() {
+        check_incremental("{ let a = 5\n}\n10", TextEdit::new(range(10, 11), ""));
+    }
+
+    #[test]
+    fn reparse_edit_fixing_error_in_item() {
+        check_incremental("let = 5\n10 )", TextEdit::new(range(3, 3), " a"));
+    }
+
+    #[test]
+    fn reparse_edit_splitting_item_in_two() {
+        check("echo a b\n1", TextEdit::new(range(6, 7), ";"));
+    }
+
+    #[test]
+    fn reparse_edit_removing_closing_brace() {
+        check("{ { 1 }\n2 }\n3", TextEdit::new(range(6, 7), ""));
+    }
+
+    #[test]
+    fn reparse_edit_opening_string_literal() {
+        check("{ echo a }\necho \"b\"", TextEdit::new(range(8, 8), "\""));
+    }
+
+    // Tries lots of small edits at every position in some code, checking that each gives the same
+    // result as a full reparse.
+    #[test]
+    fn every_small_edit_matches_full_reparse() {
+        let inputs = [
+            "let a = { echo a\n  1 + 2 }\n$a * 3",
+            "if $x then { ls *.rs } else { [1 2 [a = 3]] }; echo done",
+            "let f = { match $a { 1 => \"one\"; _ => $b } }\nf 1 2 &",
+            "for x in [1 2] { while false { break } }\n{ let = }\n) 5",
+            "{ echo \"a } b\" }\n{ } ; { ;; }\n  \n{ 1 + }",
+        ];
+        let insertions = [
+            "", "a", " ", "\n", ";", "{", "}", "(", ")", "[", "]", "\"", "=", "*", "&",
+        ];
+
+        for input in &inputs {
+            let len = input.len() as u32;
+
+            for start in 0..=len {
+                for end in start..=(start + 2).min(len) {
+                    for insertion in &insertions {
+                        if start == end && insertion.is_empty() {
+                            continue;
+                        }
+
+                        check(input, TextEdit::new(range(start, end), *insertion));
+                    }
+                }
+            }
+        }
+    }
+}
@@ -1,10 +1,13 @@
-use super::expr::parse_expr;
+use super::expr::{parse_call, parse_expr};
 use super::Parser;
 use crate::lexer::SyntaxKind;
 
 pub(crate) fn parse_item(p: &mut Parser) {
     match p.peek() {
         Some(SyntaxKind::LetKw) => parse_binding_def(p),
+        Some(SyntaxKind::ImportKw) | Some(SyntaxKind::SourceKw) => parse_import(p),
+        Some(SyntaxKind::AliasKw) => parse_alias(p),
+        Some(SyntaxKind::Atom) if p.at_name_followed_by_equals() => parse_assignment(p),
         _ => parse_expr(p),
     }
 }
@@ -16,6 +19,11 @@ pub(crate) fn parse_binding_def(p: &mut Parser) {
     p.bump();
     p.skip_ws();
 
+    if let Some(SyntaxKind::MutKw) = p.peek() {
+        p.bump();
+        p.skip_ws();
+    }
+
     if let Some(SyntaxKind::Atom) = p.peek() {
         p.bump();
     } else {
@@ -37,6 +45,73 @@ pub(crate) fn parse_binding_def(p: &mut Parser) {
     p.builder.finish_node();
 }
 
+pub(crate) fn parse_assignment(p: &mut Parser) {
+    assert_eq!(p.peek(), Some(SyntaxKind::Atom));
+
+    p.builder.start_node(SyntaxKind::Assignment.into());
+    p.bump();
+    p.skip_ws();
+
+    assert_eq!(p.peek(), Some(SyntaxKind::Equals));
+    p.bump();
+    p.skip_ws();
+
+    parse_expr(p);
+
+    p.builder.finish_node();
+}
+
+pub(crate) fn parse_import(p: &mut Parser) {
+    assert!(matches!(
+        p.peek(),
+        Some(SyntaxKind::ImportKw) | Some(SyntaxKind::SourceKw)
+    ));
+
+    p.builder.start_node(SyntaxKind::Import.into());
+    p.bump();
+    p.skip_ws();
+
+    if let Some(SyntaxKind::StringLiteral) = p.peek() {
+        p.bump();
+    } else {
+        p.error("expected module path");
+    }
+
+    p.builder.finish_node();
+}
+
+pub(crate) fn parse_alias(p: &mut Parser) {
+    assert_eq!(p.peek(), Some(SyntaxKind::AliasKw));
+
+    p.builder.start_node(SyntaxKind::Alias.into());
+    p.bump();
+    p.skip_ws();
+
+    if let Some(SyntaxKind::Atom) = p.peek() {
+        p.bump();
+    } else {
+        p.error("expected alias name");
+    }
+
+    p.skip_ws();
+
+    if let Some(SyntaxKind::Equals) = p.peek() {
+        p.bump();
+    } else {
+        p.error("expected equals sign");
+    }
+
+    p.skip_ws();
+
+    if let Some(SyntaxKind::Atom) = p.peek() {
+        parse_call(p);
+    } else {
+        p.error("expected command");
+    }
+
+    p.builder.finish_node();
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -152,4 +227,142 @@ mod tests {
                 Error@8..9 "=""#]],
         );
     }
+
+    #[test]
+    fn parse_mutable_binding_def() {
+        test(
+            "let mut count = 0",
+            expect![[r#"
+            Root@0..17
+              BindingDef@0..17
+                LetKw@0..3 "let"
+                Whitespace@3..4 " "
+                MutKw@4..7 "mut"
+                Whitespace@7..8 " "
+                Atom@8..13 "count"
+                Whitespace@13..14 " "
+                Equals@14..15 "="
+                Whitespace@15..16 " "
+                Digits@16..17 "0""#]],
+        );
+    }
+
+    #[test]
+    fn parse_assignment() {
+        test(
+            "count = $count + 1",
+            expect![[r#"
+            Root@0..18
+              Assignment@0..18
+                Atom@0..5 "count"
+                Whitespace@5..6 " "
+                Equals@6..7 "="
+                Whitespace@7..8 " "
+                BinOp@8..18
+                  BindingUsage@8..14
+                    Dollar@8..9 "$"
+                    Atom@9..14 "count"
+                  Whitespace@14..15 " "
+                  Plus@15..16 "+"
+                  Whitespace@16..17 " "
+                  Digits@17..18 "1""#]],
+        );
+    }
+
+    #[test]
+    fn parse_import() {
+        test(
+            r#"import "lib.fj""#,
+            expect![[r#"
+            Root@0..15
+              Import@0..15
+                ImportKw@0..6 "import"
+                Whitespace@6..7 " "
+                StringLiteral@7..15 "\"lib.fj\"""#]],
+        );
+    }
+
+    #[test]
+    fn parse_source() {
+        test(
+            r#"source "lib.fj""#,
+            expect![[r#"
+            Root@0..15
+              Import@0..15
+                SourceKw@0..6 "source"
+                Whitespace@6..7 " "
+                StringLiteral@7..15 "\"lib.fj\"""#]],
+        );
+    }
+
+    #[test]
+    fn recover_from_import_without_path() {
+        test(
+            "import lib",
+            expect![[r#"
+            Root@0..10
+              Import@0..10
+                ImportKw@0..6 "import"
+                Whitespace@6..7 " "
+                Error@7..10 "lib""#]],
+        );
+    }
+
+    #[test]
+    fn parse_alias() {
+        test(
+            "alias ll = ls -la",
+            expect![[r#"
+            Root@0..17
+              Alias@0..17
+                AliasKw@0..5 "alias"
+                Whitespace@5..6 " "
+                Atom@6..8 "ll"
+                Whitespace@8..9 " "
+                Equals@9..10 "="
+                Whitespace@10..11 " "
+                FunctionCall@11..17
+                  Atom@11..13 "ls"
+                  Whitespace@13..14 " "
+                  FunctionCallParams@14..17
+                    Atom@14..17 "-la""#]],
+        );
+    }
+
+    #[test]
+    fn stop_alias_before_operator() {
+        test(
+            "alias x = ls + 1",
+            expect![[r#"
+            Root@0..13
+              Alias@0..13
+                AliasKw@0..5 "alias"
+                Whitespace@5..6 " "
+                Atom@6..7 "x"
+                Whitespace@7..8 " "
+                Equals@8..9 "="
+                Whitespace@9..10 " "
+                FunctionCall@10..12
+                  Atom@10..12 "ls"
+                  FunctionCallParams@12..12
+                Whitespace@12..13 " ""#]],
+        );
+    }
+
+    #[test]
+    fn recover_from_alias_without_command() {
+        test(
+            "alias x = 5",
+            expect![[r#"
+            Root@0..11
+              Alias@0..11
+                AliasKw@0..5 "alias"
+                Whitespace@5..6 " "
+                Atom@6..7 "x"
+                Whitespace@7..8 " "
+                Equals@8..9 "="
+                Whitespace@9..10 " "
+                Error@10..11 "5""#]],
+        );
+    }
 }
@@ -0,0 +1,99 @@
+use super::Parser;
+use crate::lexer::SyntaxKind;
+
+pub(crate) fn parse_pattern(p: &mut Parser) {
+    match p.peek() {
+        // Atoms cover wildcards (`_`), type patterns (`Number`) and bindings (`x`), which are only
+        // told apart once the pattern is matched against.
+        Some(SyntaxKind::Atom)
+        | Some(SyntaxKind::Digits)
+        | Some(SyntaxKind::StringLiteral)
+        | Some(SyntaxKind::True)
+        | Some(SyntaxKind::False) => p.bump(),
+        Some(SyntaxKind::LBracket) => parse_list_or_record_pattern(p),
+        _ => p.error("expected pattern"),
+    }
+}
+
+fn parse_list_or_record_pattern(p: &mut Parser) {
+    assert_eq!(p.peek(), Some(SyntaxKind::LBracket));
+
+    let checkpoint = p.builder.checkpoint();
+    p.bump();
+    p.skip_ws_and_eol();
+
+    let is_record = p.at_name_followed_by_equals();
+
+    let kind = if is_record {
+        SyntaxKind::RecordPattern
+    } else {
+        SyntaxKind::ListPattern
+    };
+    p.builder.start_node_at(checkpoint, kind.into());
+
+    let mut seen_rest = false;
+
+    loop {
+        p.skip_ws_and_eol();
+
+        match p.peek() {
+            Some(SyntaxKind::RBracket) => {
+                p.bump();
+                break;
+            }
+            None => {
+                p.error("expected right bracket");
+                break;
+            }
+            _ => {}
+        }
+
+        if is_record {
+            parse_record_field_pattern(p);
+        } else if seen_rest {
+            p.error("expected right bracket");
+        } else if at_rest(p) {
+            p.builder.start_node(SyntaxKind::RestPattern.into());
+            p.bump();
+            p.builder.finish_node();
+
+            seen_rest = true;
+        } else {
+            parse_pattern(p);
+        }
+    }
+
+    p.builder.finish_node();
+}
+
+// A rest pattern such as `...tail` (or a rest parameter of a lambda) is lexed as a single atom,
+// since dots are allowed in atoms.
+pub(super) fn at_rest(p: &Parser) -> bool {
+    p.peek() == Some(SyntaxKind::Atom)
+        && p.lexemes
+            .last()
+            .is_some_and(|lexeme| lexeme.text.starts_with("..."))
+}
+
+fn parse_record_field_pattern(p: &mut Parser) {
+    p.builder.start_node(SyntaxKind::RecordFieldPattern.into());
+
+    if p.peek() == Some(SyntaxKind::Atom) {
+        p.bump();
+    } else {
+        p.error("expected field name");
+    }
+
+    p.skip_ws();
+
+    if p.peek() == Some(SyntaxKind::Equals) {
+        p.bump();
+    } else {
+        p.error("expected equals sign");
+    }
+
+    p.skip_ws();
+    parse_pattern(p);
+
+    p.builder.finish_node();
+}
@@ -0,0 +1,54 @@
+//! Fixtures shared between the tests of several modules.
+//!
+//! The tests of the `fjord` binary and of the language server include this file by path, since
+//! they can’t use the library’s test-only modules.
+
+use std::fs;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+
+/// A directory of its own for a test to create files in, which is removed once the test is done
+/// with it.
+pub(crate) struct TempDir {
+    path: PathBuf,
+}
+
+impl TempDir {
+    /// Creates an empty directory, whose name is made unique to the test by `name` and to the test
+    /// run by the process ID.
+    pub(crate) fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("fjord-{}-{}", name, std::process::id()));
+
+        // The directory might have been left behind by a run that panicked.
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+
+        Self { path }
+    }
+
+    /// Creates an executable shell script that does nothing, returning its path.
+    #[cfg(unix)]
+    pub(crate) fn create_executable(&self, name: &str) -> PathBuf {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = self.path.join(name);
+        fs::write(&path, "#!/bin/sh\n").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+
+        path
+    }
+}
+
+impl Deref for TempDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
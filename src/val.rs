@@ -1,6 +1,9 @@
 //! A representation of what a value in Fjord can be.
 
-use crate::ast::Lambda;
+use crate::eval::Builtin;
+use crate::hir::Lambda;
+use smol_str::SmolStr;
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 
 /// See the module-level documentation.
@@ -11,6 +14,10 @@ pub enum Val {
     Str(String),
     Bool(bool),
     Lambda(Lambda),
+    ExitStatus(i32),
+    Job(u32),
+    List(Vec<Val>),
+    Record(BTreeMap<SmolStr, Val>),
     Nil,
 }
 
@@ -21,13 +28,21 @@ impl Val {
             Self::Str(_) => Ty::Str,
             Self::Bool(_) => Ty::Bool,
             Self::Lambda(_) => Ty::Lambda,
+            Self::ExitStatus(_) => Ty::ExitStatus,
+            Self::Job(_) => Ty::Job,
+            Self::List(_) => Ty::List,
+            Self::Record(_) => Ty::Record,
             Self::Nil => Ty::Nil,
         }
     }
 }
 
 impl Val {
-    pub(crate) fn display_repr(&self) -> Option<String> {
+    /// Returns the textual representation of the value, which is used both when showing the value
+    /// to the user and when passing it to a command (apart from lists, which are passed as one
+    /// argument per element). Returns `None` for values that have no such representation, such as
+    /// lambdas.
+    pub fn display_repr(&self) -> Option<String> {
         match self {
             Self::Number(n) => Some(n.to_string()),
             Self::Str(s) => Some(s.clone()),
@@ -39,6 +54,24 @@ impl Val {
                 }
             }
             Self::Lambda(_) => None,
+            Self::ExitStatus(code) => Some(code.to_string()),
+            Self::Job(id) => Some(format!("%{}", id)),
+            Self::List(elements) => {
+                let elements = elements
+                    .iter()
+                    .map(Self::display_repr)
+                    .collect::<Option<Vec<_>>>()?;
+
+                Some(format!("[{}]", elements.join(" ")))
+            }
+            Self::Record(fields) => {
+                let fields = fields
+                    .iter()
+                    .map(|(name, val)| Some(format!("{} = {}", name, val.display_repr()?)))
+                    .collect::<Option<Vec<_>>>()?;
+
+                Some(format!("[{}]", fields.join(" ")))
+            }
             Self::Nil => Some("nil".to_string()),
         }
     }
@@ -50,10 +83,81 @@ pub(crate) enum Ty {
     Str,
     Bool,
     Lambda,
+    ExitStatus,
+    Job,
+    List,
+    Record,
     Nil,
 }
 
+impl Ty {
+    /// Looks up a type by the name it is referred to by in type patterns.
+    pub(crate) fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "Number" => Some(Self::Number),
+            "Str" => Some(Self::Str),
+            "Bool" => Some(Self::Bool),
+            "Lambda" => Some(Self::Lambda),
+            "ExitStatus" => Some(Self::ExitStatus),
+            "Job" => Some(Self::Job),
+            "List" => Some(Self::List),
+            "Record" => Some(Self::Record),
+            "Nil" => Some(Self::Nil),
+            _ => None,
+        }
+    }
+
+    /// The name of the type, as used in type patterns.
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            Self::Number => "Number",
+            Self::Str => "Str",
+            Self::Bool => "Bool",
+            Self::Lambda => "Lambda",
+            Self::ExitStatus => "ExitStatus",
+            Self::Job => "Job",
+            Self::List => "List",
+            Self::Record => "Record",
+            Self::Nil => "Nil",
+        }
+    }
+}
+
 pub(crate) enum FuncOrCommand {
     Func(Lambda),
+    Builtin(Builtin),
     Command(PathBuf),
 }
+
+/// What calling a name resolves to, along with any arguments that come before those given at the
+/// call site (which only aliases have).
+pub(crate) struct Callee {
+    pub(crate) func_or_command: FuncOrCommand,
+    pub(crate) alias_args: Vec<AliasArg>,
+}
+
+/// What an alias expands to: the name of what it calls, and the arguments it passes.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub(crate) struct Alias {
+    pub(crate) name: SmolStr,
+    pub(crate) args: Vec<AliasArg>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub(crate) enum AliasArg {
+    /// An unquoted word, which has its tildes and globs expanded each time the alias is used
+    /// rather than when it is defined.
+    Word(String),
+    Val(Val),
+}
+
+impl AliasArg {
+    /// Returns the value the argument has when passed to a lambda or builtin, which for words is
+    /// the same as that of a bare word.
+    pub(crate) fn to_val(&self) -> Val {
+        match self {
+            Self::Word(word) => Val::Str(word.clone()),
+            Self::Val(val) => val.clone(),
+        }
+    }
+}